@@ -14,19 +14,15 @@ layout (set = 1, binding = 0) readonly buffer DataBuf {
 layout (set = 1, binding = 1) uniform sampler u_sampler;
 layout (set = 1, binding = 2) uniform texture1D u_colors;
 
-layout (set = 1, binding = 3) uniform ColorMap {
-  float min_val;
-  float max_val;
-  float min_color;
-  float max_color;
-} u_color_map;
-
 struct SlotUniform {
   vec2 ab;
   uint bin_count;
+  // maps the slot's values to coordinates in the color texture, as
+  // clamp(v * x + y, z, w)
+  vec4 color_map;
 };
 
-layout (set = 1, binding = 4) readonly buffer Transform {
+layout (set = 1, binding = 3) readonly buffer Transform {
   SlotUniform slot[];
 } u_slots;
 
@@ -51,9 +47,8 @@ void main() {
 
   float v = u_data.values[row_offset + data_ix];
 
-  float v_n = (v - u_color_map.min_val) / (u_color_map.max_val - u_color_map.min_val);
-
-  float c_n = mix(u_color_map.min_color, u_color_map.max_color, v_n);
+  vec4 color_map = u_slots.slot[i_slot_id].color_map;
+  float c_n = clamp(v * color_map.x + color_map.y, color_map.z, color_map.w);
 
   vec4 sampled = texture(sampler1D(u_colors, u_sampler), c_n);
   vec4 color = isinf(v) ? vec4(1.0) : sampled;
@@ -1,21 +1,299 @@
 use anyhow::{anyhow, Result};
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::Arc;
 
-use waragraph_core::graph::{Bp, PathId, PathIndex};
+use waragraph_core::graph::{Bp, Node, PathId, PathIndex};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+use index::AnnotationIndex;
+use load::AnnotationSource;
+use path_names::PathMatchReport;
+
+pub mod bed;
+pub mod density;
+pub mod export;
+pub mod index;
+pub mod load;
+pub mod nodes;
+pub mod path_names;
+pub mod projection;
+pub mod vcf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Strand {
+    Forward,
+    Reverse,
+}
+
+/// What an annotation is placed on, and so which coordinates its
+/// ranges are in
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnnotationTarget {
+    /// A range on a path, in path coordinates
+    Path(PathId),
+    /// A range in pangenome coordinates, not tied to any path
+    Pangenome,
+    /// A set of nodes, sorted; the range is the pangenome range
+    /// spanning them
+    Nodes(Arc<[Node]>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct Annotation {
-    pub path: PathId,
+    pub target: AnnotationTarget,
     pub range: std::ops::Range<Bp>,
     pub label: Arc<String>,
     pub color: Option<egui::Color32>,
+
+    pub strand: Option<Strand>,
+    pub score: Option<f32>,
+
+    /// The part of the annotation that's drawn thick, e.g. the coding
+    /// region; the entire annotation if `None`
+    pub thick_range: Option<std::ops::Range<Bp>>,
+    /// Sub-intervals of `range`, e.g. exons, sorted by start; if
+    /// empty, the entire range is a single block
+    pub blocks: Vec<std::ops::Range<Bp>>,
+
+    /// The feature type, e.g. `gene` or `exon` for GFF3 features, or
+    /// the variant class for VCF records
+    pub feature_type: Option<Arc<String>>,
+    /// The feature this annotation is a part of, e.g. the transcript
+    /// of an exon
+    pub parent: Option<AnnotationId>,
+}
+
+/// How a part of an annotation is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FeaturePart {
+    /// Within a block, and in the thick range
+    Thick,
+    /// Within a block, but outside the thick range, e.g. an UTR
+    Thin,
+    /// Between two blocks, e.g. an intron
+    Gap,
+}
+
+impl Annotation {
+    /// An annotation covering `range` on `path`, as a single block
+    pub fn new(
+        path: PathId,
+        range: std::ops::Range<Bp>,
+        label: Arc<String>,
+        color: Option<egui::Color32>,
+    ) -> Self {
+        Self {
+            target: AnnotationTarget::Path(path),
+            range,
+            label,
+            color,
+            strand: None,
+            score: None,
+            thick_range: None,
+            blocks: Vec::new(),
+            feature_type: None,
+            parent: None,
+        }
+    }
+
+    /// An annotation covering `range` in pangenome space
+    pub fn new_pangenome(
+        range: std::ops::Range<Bp>,
+        label: Arc<String>,
+        color: Option<egui::Color32>,
+    ) -> Self {
+        Self {
+            target: AnnotationTarget::Pangenome,
+            ..Self::new(PathId::from(0usize), range, label, color)
+        }
+    }
+
+    /// An annotation covering the given nodes, which need not be
+    /// contiguous
+    pub fn new_nodes(
+        graph: &PathIndex,
+        mut nodes: Vec<Node>,
+        label: Arc<String>,
+        color: Option<egui::Color32>,
+    ) -> Self {
+        nodes.sort();
+        nodes.dedup();
+
+        let range = match (nodes.first(), nodes.last()) {
+            (Some(&first), Some(&last)) => {
+                let start = graph.node_pangenome_range(first).start;
+                let end = graph.node_pangenome_range(last).end;
+                start..end
+            }
+            _ => Bp(0)..Bp(0),
+        };
+
+        Self {
+            target: AnnotationTarget::Nodes(nodes.into()),
+            ..Self::new(PathId::from(0usize), range, label, color)
+        }
+    }
+
+    /// The path the annotation is on, if it's on one
+    pub fn path(&self) -> Option<PathId> {
+        match self.target {
+            AnnotationTarget::Path(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// The nodes covered by the annotation, in path order for
+    /// annotations on paths, and sorted otherwise
+    pub fn nodes(&self, graph: &PathIndex) -> Vec<Node> {
+        match &self.target {
+            AnnotationTarget::Path(path) => graph
+                .path_step_range_iter(*path, self.range.clone())
+                .into_iter()
+                .flatten()
+                .map(|(_, step)| step.node())
+                .collect(),
+            AnnotationTarget::Pangenome => {
+                let (start, end) = (self.range.start.0, self.range.end.0);
+
+                if start >= end || start >= graph.pangenome_len().0 {
+                    return Vec::new();
+                }
+
+                let nodes = graph.pos_range_nodes(start..end - 1);
+                (nodes.start().ix()..=nodes.end().ix())
+                    .map(Node::from)
+                    .collect()
+            }
+            AnnotationTarget::Nodes(nodes) => nodes.to_vec(),
+        }
+    }
+
+    /// Splits the annotation into the ranges of its blocks and the
+    /// gaps between them, with the blocks split by the thick range;
+    /// the ranges are in the coordinates of the annotation's target
+    pub fn feature_parts(&self) -> Vec<(std::ops::Range<Bp>, FeaturePart)> {
+        let single_block = [self.range.clone()];
+        let blocks = if self.blocks.is_empty() {
+            &single_block[..]
+        } else {
+            &self.blocks[..]
+        };
+
+        let thick = self.thick_range.clone().unwrap_or(self.range.clone());
+
+        let mut parts = Vec::new();
+        let mut prev_end: Option<Bp> = None;
+
+        for block in blocks {
+            let (a, b) = (block.start, block.end);
+
+            if let Some(prev_end) = prev_end {
+                if prev_end < a {
+                    parts.push((prev_end..a, FeaturePart::Gap));
+                }
+            }
+
+            // BED uses thickStart == thickEnd for non-coding features
+            if thick.start >= thick.end {
+                parts.push((a..b, FeaturePart::Thin));
+            } else {
+                let ts = thick.start.clamp(a, b);
+                let te = thick.end.clamp(a, b);
+
+                if a < ts {
+                    parts.push((a..ts, FeaturePart::Thin));
+                }
+                if ts < te {
+                    parts.push((ts..te, FeaturePart::Thick));
+                }
+                if te < b {
+                    parts.push((te..b, FeaturePart::Thin));
+                }
+            }
+
+            prev_end = Some(prev_end.map_or(b, |p| p.max(b)));
+        }
+
+        parts
+    }
 }
 
 pub struct AnnotationSet {
     pub name: String,
     pub annotations: Vec<Annotation>,
     pub path_annotations: HashMap<PathId, Vec<usize>>,
+    /// The annotations that aren't on a path, i.e. in pangenome space
+    /// or on node sets
+    pub pangenome_annotations: Vec<usize>,
+
+    /// The child features of each annotation that has any, in file
+    /// order
+    pub children: HashMap<AnnotationId, Vec<AnnotationId>>,
+
+    pub index: AnnotationIndex,
+
+    /// For sets loaded from files with records on named sequences,
+    /// how many of them matched paths
+    pub path_matches: Option<PathMatchReport>,
+
+    /// The file the set was loaded from, if it was loaded with
+    /// [`AnnotationSet::load`]
+    pub source: Option<AnnotationSource>,
+
+    /// For sets created with [`AnnotationSet::project_to_paths`], the
+    /// set they were projected from, so that they can be projected
+    /// again when that set is reloaded
+    pub projected_from: Option<AnnotationSetId>,
+}
+
+/// Creates an annotation from a BED record, on the given target; a
+/// `#RRGGBB` color at the end of the name is used if there's no
+/// `itemRgb`
+fn bed_annotation(
+    record: &bed::BedRecord<'_>,
+    target: AnnotationTarget,
+) -> Annotation {
+    let to_bp = |r: &std::ops::Range<u64>| Bp(r.start)..Bp(r.end);
+
+    let (label, name_color) = match record.name {
+        Some(name) => {
+            // if the name ends with a hex-encoded color string
+            // #RRGGBB, use that
+            if let Some((name, color)) = name
+                .rsplit_once(' ')
+                .and_then(|(n, c)| Some((n, parse_color(c)?)))
+            {
+                (name.to_string(), Some(color))
+            } else {
+                (name.to_string(), None)
+            }
+        }
+        None => {
+            let label = format!(
+                "{}:{}-{}",
+                record.chrom, record.range.start, record.range.end
+            );
+            (label, None)
+        }
+    };
+
+    let color = record.item_rgb.or(name_color).unwrap_or_else(|| {
+        let [r, g, b] = crate::color::util::hashed_rgb(&label);
+        egui::Color32::from_rgb(r, g, b)
+    });
+
+    Annotation {
+        target,
+        strand: record.strand,
+        score: record.score,
+        thick_range: record.thick_range.as_ref().map(to_bp),
+        blocks: record.blocks.iter().map(to_bp).collect(),
+        ..Annotation::new(
+            PathId::from(0usize),
+            to_bp(&record.range),
+            Arc::new(label),
+            Some(color),
+        )
+    }
 }
 
 fn annotation_set_name(
@@ -35,166 +313,679 @@ fn annotation_set_name(
 }
 
 impl AnnotationSet {
+    /// Creates a set from the given annotations, grouping them by path
+    /// and building the interval indexes
+    pub fn new(
+        graph: &PathIndex,
+        name: String,
+        annotations: Vec<Annotation>,
+        children: HashMap<AnnotationId, Vec<AnnotationId>>,
+    ) -> Self {
+        let mut path_annotations: HashMap<_, Vec<_>> = HashMap::new();
+        let mut pangenome_annotations = Vec::new();
+
+        for (a_id, annot) in annotations.iter().enumerate() {
+            if let Some(path) = annot.path() {
+                path_annotations.entry(path).or_default().push(a_id);
+            } else {
+                pangenome_annotations.push(a_id);
+            }
+        }
+
+        let index = AnnotationIndex::build(graph, &annotations);
+
+        Self {
+            name,
+            annotations,
+            path_annotations,
+            pangenome_annotations,
+            children,
+            index,
+            path_matches: None,
+            source: None,
+            projected_from: None,
+        }
+    }
+
     pub fn get(&self, annot_id: AnnotationId) -> Option<&Annotation> {
         self.annotations.get(annot_id.0)
     }
 
+    pub fn children(&self, annot_id: AnnotationId) -> &[AnnotationId] {
+        self.children
+            .get(&annot_id)
+            .map(|c| c.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Load a BED3 to BED12 file. The `itemRgb` column is used for the
+    /// annotation colors, if present; otherwise, a name ending with a
+    /// `#RRGGBB` color (separated by a space) sets the color.
     pub fn from_bed(
         graph: &PathIndex,
         name: Option<&str>,
         path_name_map: impl Fn(&str) -> String,
         bed_path: impl AsRef<std::path::Path>,
     ) -> Result<Self> {
-        use noodles::bed;
         use std::fs::File;
-        use std::io::BufReader;
+        use std::io::{BufRead, BufReader};
 
         let name = annotation_set_name(&bed_path, name);
 
-        let mut reader = File::open(bed_path)
-            .map(BufReader::new)
-            .map(bed::Reader::new)?;
+        let reader = File::open(bed_path).map(BufReader::new)?;
 
         let mut annotations = Vec::new();
-        let mut path_annotations: HashMap<_, Vec<_>> = HashMap::new();
+        let mut matches = PathMatchReport::default();
 
-        for result in reader.records::<4>() {
-            match result {
-                Ok(record) => {
-                    if let Some(name) = record.name() {
-                        let seqid = &record.reference_sequence_name();
-
-                        let start = record.start_position().get();
-                        let end = record.end_position().get();
-
-                        let start_bp = Bp(start as u64);
-                        let end_bp = Bp(end as u64);
-                        let range = start_bp..end_bp;
-
-                        let path_name = path_name_map(seqid);
-
-                        let path_id = graph.path_names.get_by_right(&path_name);
-
-                        let path_id = if let Some(path) = path_id {
-                            *path
-                        } else {
-                            continue;
-                        };
-
-                        let a_id = annotations.len();
-
-                        let (label, color) = if let Some((name, color_str)) =
-                            name.rsplit_once(' ')
-                        {
-                            // if `color_str` is a hex-encoded color string #RRGGBB, use that
-                            (
-                                Arc::new(name.to_string()),
-                                parse_color(&color_str),
-                            )
-                        } else {
-                            let [r, g, b] =
-                                crate::color::util::hashed_rgb(&name);
-                            let color = egui::Color32::from_rgb(r, g, b);
-                            (Arc::new(name.to_string()), Some(color))
-                        };
-
-                        let annot = Annotation {
-                            path: path_id,
-                            range,
-                            label,
-                            color,
-                        };
-
-                        annotations.push(annot);
-                        path_annotations.entry(path_id).or_default().push(a_id);
-                    }
+        for (line_ix, line) in reader.lines().enumerate() {
+            let line = line?;
+
+            let record = match bed::parse_bed_line(&line) {
+                Ok(Some(record)) => record,
+                Ok(None) => continue,
+                Err(err) => {
+                    log::error!(
+                        "Error parsing BED record on line {}: {err}",
+                        line_ix + 1
+                    );
+                    continue;
                 }
+            };
+
+            let Some(path_id) =
+                matches.match_path(graph, &path_name_map, record.chrom)
+            else {
+                continue;
+            };
+
+            annotations
+                .push(bed_annotation(&record, AnnotationTarget::Path(path_id)));
+        }
+
+        matches.log("BED");
+
+        let mut set = Self::new(graph, name, annotations, HashMap::new());
+        set.path_matches = Some(matches);
+        Ok(set)
+    }
+
+    /// Loads a BED file with ranges in pangenome coordinates, rather
+    /// than on paths; the chromosome column is ignored
+    pub fn from_pangenome_bed(
+        graph: &PathIndex,
+        name: Option<&str>,
+        bed_path: impl AsRef<std::path::Path>,
+    ) -> Result<Self> {
+        use std::fs::File;
+        use std::io::{BufRead, BufReader};
+
+        let name = annotation_set_name(&bed_path, name);
+
+        let reader = File::open(bed_path).map(BufReader::new)?;
+
+        let pangenome_len = graph.pangenome_len().0;
+
+        let mut annotations = Vec::new();
+        let mut out_of_bounds = 0;
+
+        for (line_ix, line) in reader.lines().enumerate() {
+            let line = line?;
+
+            let record = match bed::parse_bed_line(&line) {
+                Ok(Some(record)) => record,
+                Ok(None) => continue,
                 Err(err) => {
-                    log::error!("Error parsing GFF record: {err}");
+                    log::error!(
+                        "Error parsing BED record on line {}: {err}",
+                        line_ix + 1
+                    );
+                    continue;
+                }
+            };
+
+            if record.range.start >= pangenome_len {
+                out_of_bounds += 1;
+                continue;
+            }
+
+            let mut annot =
+                bed_annotation(&record, AnnotationTarget::Pangenome);
+            annot.range.end = annot.range.end.min(Bp(pangenome_len));
+
+            annotations.push(annot);
+        }
+
+        if out_of_bounds > 0 {
+            log::warn!(
+                "{out_of_bounds} pangenome BED records start past the end of the pangenome ({pangenome_len} bp)"
+            );
+        }
+
+        Ok(Self::new(graph, name, annotations, HashMap::new()))
+    }
+
+    /// Loads a node list file, with one annotation per line covering
+    /// the listed nodes; see [`nodes::parse_node_list_line`]
+    pub fn from_node_lists(
+        graph: &PathIndex,
+        name: Option<&str>,
+        list_path: impl AsRef<std::path::Path>,
+    ) -> Result<Self> {
+        use std::fs::File;
+        use std::io::{BufRead, BufReader};
+
+        let name = annotation_set_name(&list_path, name);
+
+        let reader = File::open(list_path).map(BufReader::new)?;
+
+        // GFA segment IDs are offset to get the node indices
+        let (min_id, max_id) = graph.segment_id_range;
+
+        let mut annotations = Vec::new();
+        let mut unknown_segments = 0u64;
+
+        for (line_ix, line) in reader.lines().enumerate() {
+            let line = line?;
+
+            let record = match nodes::parse_node_list_line(&line) {
+                Ok(Some(record)) => record,
+                Ok(None) => continue,
+                Err(err) => {
+                    log::error!(
+                        "Error parsing node list on line {}: {err}",
+                        line_ix + 1
+                    );
+                    continue;
                 }
+            };
+
+            let mut node_set = Vec::new();
+
+            for segments in record.segments {
+                let (start, end) = segments.into_inner();
+                let len =
+                    |a: u32, b: u32| (b as u64 + 1).saturating_sub(a as u64);
+
+                // only the part of the range that's in the graph is
+                // iterated, so a typo can't produce billions of IDs
+                let (start_in, end_in) = (start.max(min_id), end.min(max_id));
+                unknown_segments += len(start, end) - len(start_in, end_in);
+
+                node_set.extend(
+                    (start_in..=end_in).map(|id| Node::from(id - min_id)),
+                );
             }
+
+            if node_set.is_empty() {
+                continue;
+            }
+
+            let label = record.label.to_string();
+            let color = record.color.unwrap_or_else(|| {
+                let [r, g, b] = crate::color::util::hashed_rgb(&label);
+                egui::Color32::from_rgb(r, g, b)
+            });
+
+            annotations.push(Annotation::new_nodes(
+                graph,
+                node_set,
+                Arc::new(label),
+                Some(color),
+            ));
         }
 
-        Ok(Self {
-            name,
-            annotations,
-            path_annotations,
-        })
+        if unknown_segments > 0 {
+            log::warn!(
+                "Node list refers to {unknown_segments} segments not in the graph"
+            );
+        }
+
+        Ok(Self::new(graph, name, annotations, HashMap::new()))
     }
 
+    /// Writes the top-level annotations on paths in the set to a BED
+    /// file, with the path names as chromosome names; see
+    /// [`bed::format_bed_line`]
+    pub fn write_bed(
+        &self,
+        graph: &PathIndex,
+        bed_path: impl AsRef<std::path::Path>,
+    ) -> Result<()> {
+        use std::io::{BufWriter, Write};
+
+        let file = std::fs::File::create(bed_path)?;
+        let mut writer = BufWriter::new(file);
+
+        let mut skipped = 0;
+
+        for annot in &self.annotations {
+            if annot.parent.is_some() {
+                continue;
+            }
+
+            let Some(path) = annot.path() else {
+                skipped += 1;
+                continue;
+            };
+
+            let path_name = graph
+                .path_names
+                .get_by_left(&path)
+                .ok_or_else(|| anyhow!("Path not found: {path:?}"))?;
+
+            writeln!(writer, "{}", bed::format_bed_line(path_name, annot))?;
+        }
+
+        writer.flush()?;
+
+        if skipped > 0 {
+            log::warn!(
+                "{skipped} annotations not on a path were left out of the BED file"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Load a GFF3 file, keeping only the records whose type passes
+    /// `type_filter`. Features are labeled using `record_label`,
+    /// falling back to the `ID` attribute, and then the type; with
+    /// `skip_unlabeled`, records without a label are skipped instead.
+    ///
+    /// `Parent` attributes are resolved to the loaded feature with
+    /// that `ID`, on the same path; a feature with several parents is
+    /// only attached to the first. Parents are drawn with their `exon`
+    /// children as blocks, and the span of their `CDS` children as the
+    /// thick range, and all features in a hierarchy share a color.
     pub fn from_gff(
         graph: &PathIndex,
         name: Option<&str>,
         path_name_map: impl Fn(&str) -> String,
         record_label: impl Fn(&noodles::gff::Record) -> Option<String>,
+        skip_unlabeled: bool,
+        type_filter: impl Fn(&str) -> bool,
         gff_path: impl AsRef<std::path::Path>,
     ) -> Result<Self> {
-        use noodles::gff;
         use std::fs::File;
         use std::io::BufReader;
 
         let name = annotation_set_name(&gff_path, name);
 
-        let mut reader = File::open(gff_path)
-            .map(BufReader::new)
-            .map(gff::Reader::new)?;
+        let reader = File::open(gff_path).map(BufReader::new)?;
 
-        let mut annotations = Vec::new();
-        let mut path_annotations: HashMap<_, Vec<_>> = HashMap::new();
+        Self::from_gff_reader(
+            graph,
+            name,
+            path_name_map,
+            record_label,
+            skip_unlabeled,
+            type_filter,
+            reader,
+        )
+    }
+
+    fn from_gff_reader(
+        graph: &PathIndex,
+        name: String,
+        path_name_map: impl Fn(&str) -> String,
+        record_label: impl Fn(&noodles::gff::Record) -> Option<String>,
+        skip_unlabeled: bool,
+        type_filter: impl Fn(&str) -> bool,
+        reader: impl std::io::BufRead,
+    ) -> Result<Self> {
+        use noodles::gff;
+
+        let mut reader = gff::Reader::new(reader);
+
+        let mut annotations: Vec<Annotation> = Vec::new();
+        let mut matches = PathMatchReport::default();
+
+        let mut feature_types: HashMap<String, Arc<String>> = HashMap::new();
+
+        // the annotation with each `ID`, and the `Parent` of each
+        // annotation, which are resolved once all records are read
+        let mut feature_ids: HashMap<String, AnnotationId> = HashMap::new();
+        let mut parent_ids: Vec<(AnnotationId, String)> = Vec::new();
 
         for result in reader.records() {
-            match result {
-                Ok(record) => {
-                    if let Some(label) = record_label(&record) {
-                        let seqid = &record.reference_sequence_name();
+            let record = match result {
+                Ok(record) => record,
+                Err(err) => {
+                    log::error!("Error parsing GFF record: {err}");
+                    continue;
+                }
+            };
+
+            if !type_filter(record.ty()) {
+                continue;
+            }
+
+            let label = record_label(&record);
+
+            if skip_unlabeled && label.is_none() {
+                continue;
+            }
+
+            let Some(path_id) = matches.match_path(
+                graph,
+                &path_name_map,
+                record.reference_sequence_name(),
+            ) else {
+                continue;
+            };
+
+            let attribute = |key: &str| {
+                record.attributes().iter().find_map(|entry| {
+                    (entry.key() == key).then_some(entry.value())
+                })
+            };
+
+            let label = label
+                .or_else(|| attribute("ID").map(String::from))
+                .unwrap_or_else(|| record.ty().to_string());
+
+            let start = record.start().get();
+            let end = record.end().get();
+            let range = Bp(start as u64 - 1)..Bp(end as u64);
+
+            let strand = match record.strand() {
+                gff::record::Strand::Forward => Some(Strand::Forward),
+                gff::record::Strand::Reverse => Some(Strand::Reverse),
+                _ => None,
+            };
+
+            let feature_type = feature_types
+                .entry(record.ty().to_string())
+                .or_insert_with(|| Arc::new(record.ty().to_string()))
+                .clone();
+
+            let a_id = AnnotationId(annotations.len());
+
+            if let Some(id) = attribute("ID") {
+                feature_ids.entry(id.to_string()).or_insert(a_id);
+            }
+
+            if let Some(parent) =
+                attribute("Parent").and_then(|p| p.split(',').next())
+            {
+                parent_ids.push((a_id, parent.to_string()));
+            }
+
+            let annot = Annotation {
+                strand,
+                score: record.score(),
+                feature_type: Some(feature_type),
+                ..Annotation::new(path_id, range, Arc::new(label), None)
+            };
+
+            annotations.push(annot);
+        }
+
+        let mut children: HashMap<AnnotationId, Vec<AnnotationId>> =
+            HashMap::new();
+
+        for (a_id, parent) in parent_ids {
+            let Some(&parent_id) = feature_ids.get(&parent) else {
+                continue;
+            };
+
+            let same_path =
+                annotations[a_id.0].target == annotations[parent_id.0].target;
+
+            // links are added one at a time, so checking the ancestors
+            // of the parent is enough to rule out cycles
+            let is_cycle =
+                ancestors(&annotations, parent_id).any(|id| id == a_id);
+
+            if same_path && !is_cycle {
+                annotations[a_id.0].parent = Some(parent_id);
+                children.entry(parent_id).or_default().push(a_id);
+            }
+        }
+
+        // children are handled before their parents, so that e.g.
+        // genes get the exons of their transcripts as blocks
+        let mut by_depth = (0..annotations.len())
+            .map(|ix| {
+                let id = AnnotationId(ix);
+                (ancestors(&annotations, id).count(), id)
+            })
+            .collect::<Vec<_>>();
+        by_depth.sort_by(|a, b| b.cmp(a));
+
+        for &(_, a_id) in &by_depth {
+            let Some(child_ids) = children.get(&a_id) else {
+                continue;
+            };
+
+            let child_annots = child_ids
+                .iter()
+                .map(|id| &annotations[id.0])
+                .collect::<Vec<_>>();
+
+            let is_type = |annot: &&Annotation, ty: &str| {
+                annot
+                    .feature_type
+                    .as_ref()
+                    .is_some_and(|t| t.as_str() == ty)
+            };
+
+            let exons = child_annots
+                .iter()
+                .filter(|a| is_type(a, "exon"))
+                .map(|a| a.range.clone())
+                .collect::<Vec<_>>();
+
+            let blocks = if exons.is_empty() {
+                child_annots
+                    .iter()
+                    .flat_map(|a| a.blocks.iter().cloned())
+                    .collect()
+            } else {
+                exons
+            };
+
+            let cds = child_annots
+                .iter()
+                .filter(|a| is_type(a, "CDS"))
+                .map(|a| a.range.clone())
+                .collect::<Vec<_>>();
+
+            let thick_ranges = if cds.is_empty() {
+                child_annots
+                    .iter()
+                    .filter_map(|a| a.thick_range.clone())
+                    .collect()
+            } else {
+                cds
+            };
+
+            let range = annotations[a_id.0].range.clone();
+
+            let thick_range = thick_ranges
+                .into_iter()
+                .reduce(|a, b| a.start.min(b.start)..a.end.max(b.end))
+                .map(|r| r.start.max(range.start)..r.end.min(range.end));
+
+            let blocks = merge_ranges(blocks)
+                .into_iter()
+                .map(|b| b.start.max(range.start)..b.end.min(range.end))
+                .filter(|b| b.start < b.end)
+                .collect();
+
+            let annot = &mut annotations[a_id.0];
+            annot.blocks = blocks;
+            annot.thick_range = thick_range;
+        }
+
+        for ix in 0..annotations.len() {
+            let root =
+                ancestors(&annotations, AnnotationId(ix)).last().unwrap();
+            let [r, g, b] =
+                crate::color::util::hashed_rgb(&annotations[root.0].label);
+            annotations[ix].color = Some(egui::Color32::from_rgb(r, g, b));
+        }
+
+        matches.log("GFF");
+
+        let mut set = Self::new(graph, name, annotations, children);
+        set.path_matches = Some(matches);
+        Ok(set)
+    }
+
+    /// Load a VCF file, plain or bgzipped, with each variant as an
+    /// annotation covering its REF allele, colored by variant class.
+    ///
+    /// If `samples` is given, only variants where at least one of
+    /// those samples has a non-reference genotype are kept, which
+    /// requires the `#CHROM` header line with the sample names.
+    pub fn from_vcf(
+        graph: &PathIndex,
+        name: Option<&str>,
+        path_name_map: impl Fn(&str) -> String,
+        samples: Option<&HashSet<String>>,
+        vcf_path: impl AsRef<std::path::Path>,
+    ) -> Result<Self> {
+        use std::fs::File;
+        use std::io::{BufRead, BufReader, Read, Seek};
+
+        let name = annotation_set_name(&vcf_path, name);
 
-                        let start = record.start().get();
-                        let end = record.end().get();
+        let mut file = File::open(vcf_path)?;
 
-                        let start_bp = Bp(start as u64 - 1);
-                        let end_bp = Bp(end as u64);
-                        let range = start_bp..end_bp;
+        let mut magic = [0u8; 2];
+        let is_gzip =
+            file.read_exact(&mut magic).is_ok() && magic == [0x1f, 0x8b];
 
-                        let path_name = path_name_map(seqid);
+        file.rewind()?;
 
-                        let path_id = graph.path_names.get_by_right(&path_name);
+        let reader: Box<dyn BufRead> = if is_gzip {
+            Box::new(noodles::bgzf::Reader::new(file))
+        } else {
+            Box::new(BufReader::new(file))
+        };
 
-                        let path_id = if let Some(path) = path_id {
-                            *path
-                        } else {
-                            continue;
-                        };
+        let mut annotations = Vec::new();
+        let mut matches = PathMatchReport::default();
+
+        let mut class_names: HashMap<vcf::VariantClass, Arc<String>> =
+            HashMap::new();
 
-                        let a_id = annotations.len();
+        // the columns of the samples to keep variants for
+        let mut sample_columns: Option<Vec<usize>> = None;
 
-                        let [r, g, b] = crate::color::util::hashed_rgb(&label);
-                        let color = egui::Color32::from_rgb(r, g, b);
+        for (line_ix, line) in reader.lines().enumerate() {
+            let line = line?;
 
-                        let annot = Annotation {
-                            path: path_id,
-                            range,
-                            label: Arc::new(label.to_string()),
-                            color: None,
-                        };
+            if let Some(names) = vcf::parse_sample_names(&line) {
+                if let Some(samples) = samples {
+                    let columns = names
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(ix, n)| samples.contains(n).then_some(ix))
+                        .collect::<Vec<_>>();
 
-                        annotations.push(annot);
-                        path_annotations.entry(path_id).or_default().push(a_id);
+                    if columns.is_empty() {
+                        log::warn!(
+                            "None of the VCF samples are path name prefixes"
+                        );
                     }
+
+                    sample_columns = Some(columns);
                 }
+                continue;
+            }
+
+            let record = match vcf::parse_vcf_line(&line) {
+                Ok(Some(record)) => record,
+                Ok(None) => continue,
                 Err(err) => {
-                    log::error!("Error parsing GFF record: {err}");
+                    log::error!(
+                        "Error parsing VCF record on line {}: {err}",
+                        line_ix + 1
+                    );
+                    continue;
+                }
+            };
+
+            if samples.is_some() {
+                let Some(columns) = sample_columns.as_deref() else {
+                    return Err(anyhow!(
+                        "Can't filter VCF variants by sample, \
+                         no #CHROM header line before line {}",
+                        line_ix + 1
+                    ));
+                };
+
+                if !columns.iter().any(|&ix| record.sample_has_alt(ix)) {
+                    continue;
                 }
             }
+
+            let Some(path_id) =
+                matches.match_path(graph, &path_name_map, record.chrom)
+            else {
+                continue;
+            };
+
+            let class = record.class();
+            let range = record.range();
+
+            let feature_type = class_names
+                .entry(class)
+                .or_insert_with(|| Arc::new(class.name().to_string()))
+                .clone();
+
+            let annot = Annotation {
+                score: record.qual,
+                feature_type: Some(feature_type),
+                ..Annotation::new(
+                    path_id,
+                    Bp(range.start)..Bp(range.end),
+                    Arc::new(record.label()),
+                    Some(class.color()),
+                )
+            };
+
+            annotations.push(annot);
         }
 
-        Ok(Self {
-            name,
-            annotations,
-            path_annotations,
-        })
+        matches.log("VCF");
+
+        let mut set = Self::new(graph, name, annotations, HashMap::new());
+        set.path_matches = Some(matches);
+        Ok(set)
     }
 }
 
+/// The annotation followed by its parent, grandparent, and so on
+fn ancestors(
+    annotations: &[Annotation],
+    a_id: AnnotationId,
+) -> impl Iterator<Item = AnnotationId> + '_ {
+    std::iter::successors(Some(a_id), |id| annotations[id.0].parent)
+}
+
+/// Sorts the ranges, and merges those that overlap or touch
+pub(crate) fn merge_ranges(
+    mut ranges: Vec<std::ops::Range<Bp>>,
+) -> Vec<std::ops::Range<Bp>> {
+    ranges.sort_by_key(|r| (r.start, r.end));
+
+    let mut merged: Vec<std::ops::Range<Bp>> = Vec::with_capacity(ranges.len());
+
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+
+    merged
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct AnnotationSetId(pub usize);
 
@@ -210,6 +1001,11 @@ pub struct GlobalAnnotationId {
 pub struct AnnotationStore {
     pub annotation_sets: BTreeMap<AnnotationSetId, Arc<AnnotationSet>>,
     next_set_id: AnnotationSetId,
+
+    // incremented whenever a set is inserted, replaced, or removed
+    generation: u64,
+    // the generation at which each set was last inserted or replaced
+    set_generations: HashMap<AnnotationSetId, u64>,
 }
 
 impl std::default::Default for AnnotationStore {
@@ -217,25 +1013,69 @@ impl std::default::Default for AnnotationStore {
         Self {
             annotation_sets: BTreeMap::default(),
             next_set_id: AnnotationSetId(0),
+            generation: 0,
+            set_generations: HashMap::default(),
         }
     }
 }
 
 impl AnnotationStore {
-    pub fn get(&self, id: GlobalAnnotationId) -> &Annotation {
+    /// Returns `None` if the annotation's set has been removed
+    pub fn get(&self, id: GlobalAnnotationId) -> Option<&Annotation> {
         self.annotation_sets
             .get(&id.set_id)
             .and_then(|set| set.get(id.annot_id))
-            .unwrap()
     }
 
-    pub fn insert_set(&mut self, set: AnnotationSet) -> AnnotationSetId {
+    pub fn insert_set(
+        &mut self,
+        set: impl Into<Arc<AnnotationSet>>,
+    ) -> AnnotationSetId {
         let set_id = self.next_set_id;
         self.next_set_id = AnnotationSetId(set_id.0 + 1);
-        self.annotation_sets.insert(set_id, Arc::new(set));
+        self.annotation_sets.insert(set_id, set.into());
+        self.generation += 1;
+        self.set_generations.insert(set_id, self.generation);
         set_id
     }
 
+    /// Replaces the annotation set with the given ID, if it exists
+    pub fn replace_set(
+        &mut self,
+        set_id: AnnotationSetId,
+        set: impl Into<Arc<AnnotationSet>>,
+    ) {
+        if let Some(old) = self.annotation_sets.get_mut(&set_id) {
+            *old = set.into();
+            self.generation += 1;
+            self.set_generations.insert(set_id, self.generation);
+        }
+    }
+
+    /// Removes the annotation set with the given ID; annotation IDs
+    /// referring to the set are then no longer valid
+    pub fn remove_set(
+        &mut self,
+        set_id: AnnotationSetId,
+    ) -> Option<Arc<AnnotationSet>> {
+        let set = self.annotation_sets.remove(&set_id)?;
+        self.generation += 1;
+        self.set_generations.remove(&set_id);
+        Some(set)
+    }
+
+    /// Changes whenever the annotation sets in the store change, so
+    /// that anything derived from them can tell when to update
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Changes whenever the set with the given ID is replaced, e.g.
+    /// when it's reloaded; `None` if there is no such set
+    pub fn set_generation(&self, set_id: AnnotationSetId) -> Option<u64> {
+        self.set_generations.get(&set_id).copied()
+    }
+
     pub fn get_sets_for_path<'a>(
         &'a self,
         path: PathId,
@@ -274,3 +1114,158 @@ fn parse_color(color_str: &str) -> Option<egui::Color32> {
 
     Some(egui::Color32::from_rgb(r, g, b))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{test_graph, TempFile};
+
+    const GFF: &str = "\
+##gff-version 3
+seq\t.\tgene\t101\t400\t.\t-\t.\tID=gene1;Name=ABC1
+seq\t.\tmRNA\t101\t400\t.\t-\t.\tID=tx1;Parent=gene1
+seq\t.\texon\t101\t150\t.\t-\t.\tParent=tx1
+seq\t.\texon\t301\t400\t.\t-\t.\tParent=tx1
+seq\t.\tCDS\t121\t150\t.\t-\t0\tParent=tx1
+seq\t.\tCDS\t301\t350\t.\t-\t0\tParent=tx1
+";
+
+    #[test]
+    fn gff_hierarchy() -> Result<()> {
+        let graph = test_graph();
+        let path = *graph.path_names.left_values().next().unwrap();
+        let path_name = graph.path_names.get_by_left(&path).unwrap().clone();
+
+        let load = |skip_unlabeled: bool, type_filter: fn(&str) -> bool| {
+            AnnotationSet::from_gff_reader(
+                &graph,
+                "test".to_string(),
+                |_| path_name.clone(),
+                |record| {
+                    let attrs = record.attributes();
+                    attrs.iter().find_map(|entry| {
+                        (entry.key() == "Name").then(|| entry.value().into())
+                    })
+                },
+                skip_unlabeled,
+                type_filter,
+                GFF.as_bytes(),
+            )
+        };
+
+        let set = load(false, |_| true)?;
+        assert_eq!(set.annotations.len(), 6);
+
+        let (gene, tx) = (&set.annotations[0], &set.annotations[1]);
+        assert_eq!(gene.label.as_str(), "ABC1");
+        assert_eq!(tx.label.as_str(), "tx1");
+        assert_eq!(set.annotations[2].label.as_str(), "exon");
+        assert_eq!(gene.strand, Some(Strand::Reverse));
+
+        assert_eq!(gene.parent, None);
+        assert_eq!(tx.parent, Some(AnnotationId(0)));
+        assert_eq!(set.children(AnnotationId(0)), &[AnnotationId(1)]);
+        assert_eq!(set.children(AnnotationId(1)).len(), 4);
+
+        // the transcript structure propagates up to the gene
+        for annot in [gene, tx] {
+            assert_eq!(annot.blocks, vec![Bp(100)..Bp(150), Bp(300)..Bp(400)]);
+            assert_eq!(annot.thick_range, Some(Bp(120)..Bp(350)));
+            assert_eq!(annot.color, set.annotations[5].color);
+        }
+
+        let set = load(false, |ty| ty == "CDS")?;
+        assert_eq!(set.annotations.len(), 2);
+        assert!(set.children.is_empty());
+        assert!(set.annotations.iter().all(|a| a.parent.is_none()));
+
+        // only the gene has a `Name`
+        let set = load(true, |_| true)?;
+        assert_eq!(set.annotations.len(), 1);
+        assert_eq!(set.annotations[0].label.as_str(), "ABC1");
+
+        Ok(())
+    }
+
+    #[test]
+    fn vcf_samples_need_header() -> Result<()> {
+        let graph = test_graph();
+        let path = *graph.path_names.left_values().next().unwrap();
+        let path_name = graph.path_names.get_by_left(&path).unwrap().clone();
+
+        let header =
+            "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tA\tB\n";
+        let variant = "seq\t11\t.\tA\tG\t.\tPASS\t.\tGT\t0|0\t0|1\n";
+
+        let samples = HashSet::from(["B".to_string()]);
+
+        let load = |vcf: String| {
+            let file_path = TempFile::new("vcf", vcf)?;
+
+            AnnotationSet::from_vcf(
+                &graph,
+                None,
+                |_| path_name.clone(),
+                Some(&samples),
+                &file_path,
+            )
+        };
+
+        let set = load(format!("{header}{variant}"))?;
+        assert_eq!(set.annotations.len(), 1);
+
+        assert!(load(variant.to_string()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn store_remove_set() -> Result<()> {
+        let graph = test_graph();
+        let path = *graph.path_names.left_values().next().unwrap();
+
+        let label = Arc::new("a".to_string());
+        let annot = Annotation::new(path, Bp(10)..Bp(20), label, None);
+        let set = |name: &str| {
+            let name = name.to_string();
+            AnnotationSet::new(
+                &graph,
+                name,
+                vec![annot.clone()],
+                HashMap::new(),
+            )
+        };
+
+        let mut store = AnnotationStore::default();
+        let a = store.insert_set(set("a"));
+        let b = store.insert_set(set("b"));
+
+        let generation = store.generation();
+        assert_eq!(
+            store.remove_set(a).map(|set| set.name.clone()),
+            Some("a".into())
+        );
+        assert_ne!(store.generation(), generation);
+
+        let annot_id = AnnotationId(0);
+        assert!(store
+            .get(GlobalAnnotationId {
+                set_id: a,
+                annot_id
+            })
+            .is_none());
+        assert!(store
+            .get(GlobalAnnotationId {
+                set_id: b,
+                annot_id
+            })
+            .is_some());
+
+        // removing a set twice leaves the store unchanged
+        let generation = store.generation();
+        assert!(store.remove_set(a).is_none());
+        assert_eq!(store.generation(), generation);
+
+        Ok(())
+    }
+}
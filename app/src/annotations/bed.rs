@@ -0,0 +1,338 @@
+//! Parsing BED3 through BED12 records, in the 0-based, half-open
+//! coordinates used by BED.
+
+use anyhow::{anyhow, Context, Result};
+
+use waragraph_core::graph::Bp;
+
+use super::{Annotation, Strand};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BedRecord<'a> {
+    pub chrom: &'a str,
+    pub range: std::ops::Range<u64>,
+
+    pub name: Option<&'a str>,
+    pub score: Option<f32>,
+    pub strand: Option<Strand>,
+
+    pub thick_range: Option<std::ops::Range<u64>>,
+    pub item_rgb: Option<egui::Color32>,
+
+    /// Absolute ranges of the BED12 blocks (e.g. exons), sorted
+    pub blocks: Vec<std::ops::Range<u64>>,
+}
+
+/// Parses a line of a BED file, returning `None` for empty lines,
+/// comments, and `track` and `browser` lines.
+///
+/// Fields are tab-separated; lines without tabs are split on
+/// whitespace. Optional fields can be `.` to leave them out.
+pub fn parse_bed_line(line: &str) -> Result<Option<BedRecord<'_>>> {
+    let line = line.trim_end_matches(['\n', '\r']);
+
+    if line.trim().is_empty()
+        || line.starts_with('#')
+        || line.starts_with("track")
+        || line.starts_with("browser")
+    {
+        return Ok(None);
+    }
+
+    let fields: Vec<&str> = if line.contains('\t') {
+        line.split('\t').collect()
+    } else {
+        line.split_whitespace().collect()
+    };
+
+    if fields.len() < 3 {
+        anyhow::bail!("BED record must have at least 3 fields: `{line}`");
+    }
+
+    let field = |ix: usize| -> Option<&str> {
+        fields
+            .get(ix)
+            .map(|f| f.trim())
+            .filter(|f| !f.is_empty() && *f != ".")
+    };
+
+    let parse_u64 = |ix: usize, col: &str| -> Result<u64> {
+        fields[ix]
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid BED {col}: `{}`", fields[ix]))
+    };
+
+    let chrom = fields[0];
+    let start = parse_u64(1, "chromStart")?;
+    let end = parse_u64(2, "chromEnd")?;
+
+    if end < start {
+        anyhow::bail!("BED record ends before it starts: `{line}`");
+    }
+
+    let name = field(3);
+
+    let score = field(4)
+        .map(|s| {
+            s.parse::<f32>()
+                .with_context(|| format!("Invalid score `{s}`"))
+        })
+        .transpose()?;
+
+    let strand = match field(5) {
+        Some("+") => Some(Strand::Forward),
+        Some("-") => Some(Strand::Reverse),
+        None => None,
+        Some(s) => anyhow::bail!("Invalid strand `{s}`"),
+    };
+
+    let thick_range = match (field(6), field(7)) {
+        (Some(_), Some(_)) => {
+            let thick_start = parse_u64(6, "thickStart")?;
+            let thick_end = parse_u64(7, "thickEnd")?;
+            Some(thick_start..thick_end)
+        }
+        _ => None,
+    };
+
+    let item_rgb = field(8).and_then(parse_item_rgb);
+
+    let blocks = if let Some(count) = field(9) {
+        let count: usize = count
+            .parse()
+            .with_context(|| format!("Invalid blockCount `{count}`"))?;
+
+        let parse_list = |ix: usize, col: &str| -> Result<Vec<u64>> {
+            let list = field(ix).ok_or_else(|| anyhow!("Missing {col}"))?;
+            list.split(',')
+                .filter(|s| !s.is_empty())
+                .map(|s| {
+                    s.trim()
+                        .parse()
+                        .with_context(|| format!("Invalid {col} `{list}`"))
+                })
+                .collect()
+        };
+
+        let sizes = parse_list(10, "blockSizes")?;
+        let starts = parse_list(11, "blockStarts")?;
+
+        if sizes.len() != count || starts.len() != count {
+            anyhow::bail!(
+                "blockCount is {count}, but there are {} sizes and {} starts",
+                sizes.len(),
+                starts.len()
+            );
+        }
+
+        let mut blocks = starts
+            .into_iter()
+            .zip(sizes)
+            .map(|(rel_start, size)| {
+                let block_start = start + rel_start;
+                block_start..(block_start + size).min(end)
+            })
+            .collect::<Vec<_>>();
+
+        blocks.sort_by_key(|b| (b.start, b.end));
+        blocks
+    } else {
+        Vec::new()
+    };
+
+    Ok(Some(BedRecord {
+        chrom,
+        range: start..end,
+        name,
+        score,
+        strand,
+        thick_range,
+        item_rgb,
+        blocks,
+    }))
+}
+
+/// Parses an `itemRgb` field, `R,G,B`; `0` means no color
+fn parse_item_rgb(s: &str) -> Option<egui::Color32> {
+    let mut channels = s.split(',').map(|c| c.trim().parse::<u8>());
+
+    let r = channels.next()?.ok()?;
+    let g = channels.next()?.ok()?;
+    let b = channels.next()?.ok()?;
+
+    Some(egui::Color32::from_rgb(r, g, b))
+}
+
+/// Formats an annotation as a BED record on `chrom`, the inverse of
+/// [`parse_bed_line`].
+///
+/// The color is appended to the name as `#RRGGBB`, the convention
+/// used when loading BED files. Score and strand are written if
+/// either is set, and the thick range and blocks as BED12 if the
+/// annotation has them.
+pub fn format_bed_line(chrom: &str, annot: &Annotation) -> String {
+    bed_fields(chrom, annot, false).join("\t")
+}
+
+/// Formats an annotation as a BED12 line, whether or not it has a
+/// thick range or blocks, followed by `extra` columns
+pub fn format_bed12_line(
+    chrom: &str,
+    annot: &Annotation,
+    extra: &[String],
+) -> String {
+    let mut fields = bed_fields(chrom, annot, true);
+    fields.extend_from_slice(extra);
+    fields.join("\t")
+}
+
+fn bed_fields(chrom: &str, annot: &Annotation, bed12: bool) -> Vec<String> {
+    let range = &annot.range;
+
+    let mut name = annot.label.replace('\t', " ");
+    if let Some(color) = annot.color {
+        let [r, g, b, _] = color.to_array();
+        name.push_str(&format!(" #{r:02x}{g:02x}{b:02x}"));
+    }
+
+    let mut fields = vec![
+        chrom.to_string(),
+        range.start.0.to_string(),
+        range.end.0.to_string(),
+        name,
+    ];
+
+    let is_bed12 =
+        bed12 || annot.thick_range.is_some() || !annot.blocks.is_empty();
+
+    if is_bed12 || annot.score.is_some() || annot.strand.is_some() {
+        fields.push(
+            annot
+                .score
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| "0".to_string()),
+        );
+        fields.push(
+            match annot.strand {
+                Some(Strand::Forward) => "+",
+                Some(Strand::Reverse) => "-",
+                None => ".",
+            }
+            .to_string(),
+        );
+    }
+
+    if is_bed12 {
+        let thick = annot.thick_range.clone().unwrap_or(range.clone());
+        fields.push(thick.start.0.to_string());
+        fields.push(thick.end.0.to_string());
+        // the color is already in the name
+        fields.push("0".to_string());
+
+        let single_block = [range.clone()];
+        let blocks = if annot.blocks.is_empty() {
+            &single_block[..]
+        } else {
+            &annot.blocks[..]
+        };
+
+        let list = |f: &dyn Fn(&std::ops::Range<Bp>) -> u64| {
+            blocks
+                .iter()
+                .map(|b| format!("{},", f(b)))
+                .collect::<String>()
+        };
+
+        fields.push(blocks.len().to_string());
+        fields.push(list(&|b| b.end.0 - b.start.0));
+        fields.push(list(&|b| b.start.0 - range.start.0));
+    }
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::annotations::FeaturePart;
+    use std::sync::Arc;
+    use waragraph_core::graph::PathId;
+
+    #[test]
+    fn bed_records() -> Result<()> {
+        assert_eq!(parse_bed_line("# comment")?, None);
+        assert_eq!(parse_bed_line("track name=genes")?, None);
+
+        let bed3 = parse_bed_line("chr1\t10\t20")?.unwrap();
+        assert_eq!(bed3.chrom, "chr1");
+        assert_eq!(bed3.range, 10..20);
+        assert_eq!(bed3.name, None);
+
+        let bed6 = parse_bed_line("chr1 10 20 gene1 500 -")?.unwrap();
+        assert_eq!(bed6.name, Some("gene1"));
+        assert_eq!(bed6.score, Some(500.0));
+        assert_eq!(bed6.strand, Some(Strand::Reverse));
+        assert_eq!(bed6.item_rgb, None);
+
+        let bed12 = parse_bed_line(
+            "chr1\t100\t200\ttx1\t0\t+\t110\t190\t255,0,0\t2\t30,40,\t0,60,",
+        )?
+        .unwrap();
+        assert_eq!(bed12.thick_range, Some(110..190));
+        assert_eq!(bed12.item_rgb, Some(egui::Color32::from_rgb(255, 0, 0)));
+        assert_eq!(bed12.blocks, vec![100..130, 160..200]);
+
+        let to_bp = |r: &std::ops::Range<u64>| Bp(r.start)..Bp(r.end);
+        let annot = Annotation {
+            thick_range: bed12.thick_range.as_ref().map(to_bp),
+            blocks: bed12.blocks.iter().map(to_bp).collect(),
+            ..Annotation::new(
+                PathId::from(0usize),
+                to_bp(&bed12.range),
+                Default::default(),
+                None,
+            )
+        };
+
+        use FeaturePart as F;
+        assert_eq!(
+            annot.feature_parts(),
+            vec![
+                (Bp(100)..Bp(110), F::Thin),
+                (Bp(110)..Bp(130), F::Thick),
+                (Bp(130)..Bp(160), F::Gap),
+                (Bp(160)..Bp(190), F::Thick),
+                (Bp(190)..Bp(200), F::Thin),
+            ]
+        );
+
+        // written records parse back into the same fields
+        let annot = Annotation {
+            color: Some(egui::Color32::from_rgb(0x12, 0xab, 0xff)),
+            label: Arc::new("tx 1".to_string()),
+            ..annot
+        };
+        let line = format_bed_line("chr1", &annot);
+        let record = parse_bed_line(&line)?.unwrap();
+        assert_eq!(record.name, Some("tx 1 #12abff"));
+        assert_eq!(record.range, bed12.range);
+        assert_eq!(record.thick_range, bed12.thick_range);
+        assert_eq!(record.blocks, bed12.blocks);
+
+        let bed4 = Annotation::new(
+            PathId::from(0usize),
+            Bp(5)..Bp(15),
+            Arc::new("region".to_string()),
+            None,
+        );
+        assert_eq!(format_bed_line("chr2", &bed4), "chr2\t5\t15\tregion");
+
+        assert!(parse_bed_line("chr1\t20\t10").is_err());
+        assert!(
+            parse_bed_line("chr1\t0\t10\tx\t0\t+\t0\t10\t0\t2\t5\t0").is_err()
+        );
+
+        Ok(())
+    }
+}
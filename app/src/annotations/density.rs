@@ -0,0 +1,213 @@
+//! Annotation density as data sources, for sets with too many features
+//! for their labels to be useful when zoomed out.
+
+use std::sync::{Arc, OnceLock};
+
+use waragraph_core::graph::{Node, PathId, PathIndex};
+
+use crate::app::resource::{
+    path_range_node_overlaps, project_node_values, DataSourceId,
+    DataSourceMeta, GraphDataCache, ValueDomain,
+};
+use crate::color::ColorSchemeId;
+
+use super::{AnnotationSet, AnnotationTarget};
+
+/// Per-node density of the top-level features of an annotation set
+#[derive(Debug, Clone)]
+pub struct AnnotationDensity {
+    /// The number of features overlapping each node
+    pub counts: Vec<f32>,
+    /// The fraction of each node covered by features; where features
+    /// overlap, the covered length is capped at the node length
+    pub covered: Vec<f32>,
+}
+
+impl AnnotationDensity {
+    pub fn from_set(graph: &PathIndex, set: &AnnotationSet) -> Self {
+        let mut counts = vec![0f32; graph.node_count];
+        let mut covered_bp = vec![0u64; graph.node_count];
+
+        let mut overlaps: Vec<(Node, u64)> = Vec::new();
+
+        // child features are covered by their parents
+        for annot in set.annotations.iter().filter(|a| a.parent.is_none()) {
+            overlaps.clear();
+
+            let range = annot.range.start.0..annot.range.end.0;
+
+            match &annot.target {
+                AnnotationTarget::Path(path) => {
+                    path_range_node_overlaps(
+                        graph,
+                        *path,
+                        range,
+                        &mut overlaps,
+                    );
+                }
+                AnnotationTarget::Pangenome => {
+                    overlaps.extend(annot.nodes(graph).into_iter().map(
+                        |node| {
+                            let node_range = graph.node_pangenome_range(node);
+                            let start = node_range.start.0.max(range.start);
+                            let end = node_range.end.0.min(range.end);
+                            (node, end.saturating_sub(start))
+                        },
+                    ));
+                }
+                AnnotationTarget::Nodes(nodes) => {
+                    overlaps.extend(
+                        nodes.iter().map(|&n| (n, graph.node_length(n).0)),
+                    );
+                }
+            }
+
+            for &(node, len) in &overlaps {
+                covered_bp[node.ix()] += len;
+            }
+
+            // a path can visit a node more than once
+            overlaps.sort_unstable();
+            overlaps.dedup_by_key(|(node, _)| *node);
+
+            for &(node, _) in &overlaps {
+                counts[node.ix()] += 1.0;
+            }
+        }
+
+        let covered = covered_bp
+            .into_iter()
+            .enumerate()
+            .map(|(ix, bp)| {
+                let len = graph.node_length(Node::from(ix)).0;
+                if len == 0 {
+                    0.0
+                } else {
+                    bp.min(len) as f32 / len as f32
+                }
+            })
+            .collect();
+
+        Self { counts, covered }
+    }
+}
+
+/// Registers the density of the annotation set `set` as data sources
+/// in `cache`, named `{name}_density` for the fraction of each node
+/// covered by features, and `{name}_count` for the number of features
+/// overlapping each node.
+///
+/// Both are available as graph-level sources, and as path-level
+/// sources by projecting the node values onto each path. Returns the
+/// IDs of the density and count sources, respectively.
+///
+/// The density is only computed once any of the sources is first used.
+pub fn register_annotation_density(
+    cache: &GraphDataCache,
+    name: &str,
+    set: Arc<AnnotationSet>,
+    color_scheme: ColorSchemeId,
+) -> [DataSourceId; 2] {
+    let graph = cache.graph().clone();
+
+    let density_meta = DataSourceMeta {
+        name: format!("{name}_density"),
+        description: format!("Fraction covered by features in {}", set.name),
+        unit: Some("bp/bp".to_string()),
+        value_domain: ValueDomain::Fraction,
+        default_color_scheme: color_scheme,
+    };
+
+    let count_meta = DataSourceMeta {
+        name: format!("{name}_count"),
+        description: format!("Number of features in {}", set.name),
+        unit: Some("features".to_string()),
+        value_domain: ValueDomain::Count,
+        default_color_scheme: color_scheme,
+    };
+
+    let density = Arc::new(OnceLock::new());
+
+    let get_density = {
+        let graph = graph.clone();
+        move || -> Arc<AnnotationDensity> {
+            density
+                .get_or_init(|| {
+                    Arc::new(AnnotationDensity::from_set(&graph, &set))
+                })
+                .clone()
+        }
+    };
+
+    type NodeValuesFn =
+        Arc<dyn Fn(&AnnotationDensity) -> &[f32] + Send + Sync + 'static>;
+
+    let covered: NodeValuesFn = Arc::new(|d| &d.covered);
+    let counts: NodeValuesFn = Arc::new(|d| &d.counts);
+
+    [(density_meta, covered), (count_meta, counts)].map(|(meta, values)| {
+        {
+            let get_density = get_density.clone();
+            let values = values.clone();
+            cache.register_graph_f32(
+                meta.clone(),
+                Arc::new(move || Ok(values(&get_density()).to_vec())),
+            );
+        }
+
+        let get_density = get_density.clone();
+        let graph = graph.clone();
+        cache.register_path_f32(
+            meta,
+            Arc::new(move |path: PathId| {
+                let density = get_density();
+                Ok(project_node_values(&graph, path, values(&density)))
+            }),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use waragraph_core::graph::Bp;
+
+    use super::*;
+    use crate::annotations::Annotation;
+    use crate::test_util::test_graph;
+
+    #[test]
+    fn annotation_node_density() -> anyhow::Result<()> {
+        let graph = test_graph();
+
+        let node = Node::from(404usize);
+        let node_range = graph.node_pangenome_range(node);
+        let node_len = graph.node_length(node).0;
+
+        let label = Arc::new("a".to_string());
+        let half = node_range.start..Bp(node_range.start.0 + node_len / 2);
+
+        let annotations = vec![
+            Annotation::new_pangenome(half.clone(), label.clone(), None),
+            Annotation::new_pangenome(half, label.clone(), None),
+            Annotation::new_nodes(&graph, vec![node], label, None),
+        ];
+
+        let set = AnnotationSet::new(
+            &graph,
+            "test".to_string(),
+            annotations,
+            HashMap::new(),
+        );
+
+        let density = AnnotationDensity::from_set(&graph, &set);
+
+        assert_eq!(density.counts[node.ix()], 3.0);
+        assert_eq!(density.covered[node.ix()], 1.0);
+        assert_eq!(density.counts[node.ix() + 1], 0.0);
+        assert_eq!(density.covered[node.ix() + 1], 0.0);
+
+        Ok(())
+    }
+}
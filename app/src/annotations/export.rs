@@ -0,0 +1,153 @@
+//! Exporting a selection of annotations, e.g. the ones pinned in a
+//! viewer, as BED with the nodes each annotation covers.
+
+use anyhow::{anyhow, Result};
+use std::io::Write;
+
+use waragraph_core::graph::PathIndex;
+
+use super::bed::format_bed12_line;
+use super::nodes::format_node_list;
+use super::projection::project_range;
+use super::{
+    Annotation, AnnotationStore, AnnotationTarget, GlobalAnnotationId,
+};
+
+/// The chromosome name used for annotations that aren't on a path,
+/// with ranges in pangenome coordinates
+pub const PANGENOME_CHROM: &str = "pangenome";
+
+/// Writes the annotations as BED12, on the path names of annotations
+/// on paths, and on [`PANGENOME_CHROM`] otherwise. Each line has a
+/// 13th column with the GFA segment IDs of the nodes the annotation
+/// covers, formatted as in node lists (see [`super::nodes`]).
+///
+/// With `include_projections`, each annotation on a path is followed
+/// by its projections onto the other paths, see [`project_range`].
+///
+/// Annotations that are no longer in the store are skipped. Returns
+/// the number of lines written.
+pub fn write_annotations_bed(
+    graph: &PathIndex,
+    store: &AnnotationStore,
+    annot_ids: impl IntoIterator<Item = GlobalAnnotationId>,
+    include_projections: bool,
+    mut writer: impl Write,
+) -> Result<usize> {
+    let mut lines = 0;
+
+    let mut write_line = |annot: &Annotation| -> Result<()> {
+        let chrom = match &annot.target {
+            AnnotationTarget::Path(path) => graph
+                .path_names
+                .get_by_left(path)
+                .ok_or_else(|| anyhow!("Path not found: {path:?}"))?
+                .as_str(),
+            AnnotationTarget::Pangenome | AnnotationTarget::Nodes(_) => {
+                PANGENOME_CHROM
+            }
+        };
+
+        let (min_id, _) = graph.segment_id_range;
+        let nodes = format_node_list(
+            annot
+                .nodes(graph)
+                .into_iter()
+                .map(|node| node.ix() as u32 + min_id),
+        );
+
+        writeln!(writer, "{}", format_bed12_line(chrom, annot, &[nodes]))?;
+        lines += 1;
+
+        Ok(())
+    };
+
+    for annot_id in annot_ids {
+        let Some(annot) = store.get(annot_id) else {
+            continue;
+        };
+
+        write_line(annot)?;
+
+        let Some(path) = annot.path().filter(|_| include_projections) else {
+            continue;
+        };
+
+        for (other, range) in project_range(graph, path, annot.range.clone()) {
+            write_line(&Annotation {
+                score: annot.score,
+                strand: annot.strand,
+                feature_type: annot.feature_type.clone(),
+                ..Annotation::new(
+                    other,
+                    range,
+                    annot.label.clone(),
+                    annot.color,
+                )
+            })?;
+        }
+    }
+
+    writer.flush()?;
+
+    Ok(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use waragraph_core::graph::{Bp, Node};
+
+    use super::*;
+    use crate::annotations::{AnnotationId, AnnotationSet};
+    use crate::test_util::test_graph;
+
+    #[test]
+    fn export_annotations_bed() -> Result<()> {
+        let graph = test_graph();
+        let path = *graph.path_names.left_values().next().unwrap();
+        let path_name = graph.path_names.get_by_left(&path).unwrap();
+
+        let label = Arc::new("a".to_string());
+        let nodes = vec![Node::from(2usize), Node::from(3usize)];
+
+        let annotations = vec![
+            Annotation::new(path, Bp(0)..Bp(100), label.clone(), None),
+            Annotation::new_nodes(&graph, nodes, label, None),
+        ];
+
+        let mut store = AnnotationStore::default();
+        let set_id = store.insert_set(AnnotationSet::new(
+            &graph,
+            "test".to_string(),
+            annotations,
+            HashMap::new(),
+        ));
+
+        let ids = (0..2).map(|i| GlobalAnnotationId {
+            set_id,
+            annot_id: AnnotationId(i),
+        });
+
+        let mut out = Vec::new();
+        let lines =
+            write_annotations_bed(&graph, &store, ids, false, &mut out)?;
+        assert_eq!(lines, 2);
+
+        let out = String::from_utf8(out)?;
+        let lines = out.lines().collect::<Vec<_>>();
+
+        let fields = lines[0].split('\t').collect::<Vec<_>>();
+        assert_eq!(fields.len(), 13);
+        assert_eq!(&fields[..3], &[path_name.as_str(), "0", "100"]);
+
+        let fields = lines[1].split('\t').collect::<Vec<_>>();
+        assert_eq!(fields[0], PANGENOME_CHROM);
+        let (min_id, _) = graph.segment_id_range;
+        assert_eq!(fields[12], format!("{}-{}", min_id + 2, min_id + 3));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,515 @@
+//! Interval indexes over the annotations in a set, both in the
+//! coordinates of each path and in pangenome space, so that finding
+//! the annotations in a view doesn't require scanning the whole set.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use iset::IntervalMap;
+use waragraph_core::graph::{Bp, Node, PathId, PathIndex};
+
+use super::{merge_ranges, Annotation, AnnotationId, AnnotationTarget};
+
+#[derive(Default)]
+struct IntervalIndex {
+    intervals: IntervalMap<u64, AnnotationId>,
+
+    // sorted by position, for nearest-feature queries
+    starts: Vec<(u64, AnnotationId)>,
+    ends: Vec<(u64, AnnotationId)>,
+}
+
+impl IntervalIndex {
+    fn insert(&mut self, range: &Range<Bp>, annot_id: AnnotationId) {
+        let start = range.start.0;
+        // iset doesn't allow empty intervals
+        let end = range.end.0.max(start + 1);
+
+        self.intervals.force_insert(start..end, annot_id);
+        self.starts.push((start, annot_id));
+        self.ends.push((end, annot_id));
+    }
+
+    fn finish(&mut self) {
+        self.starts.sort();
+        self.ends.sort();
+    }
+
+    fn overlapping(
+        &self,
+        range: Range<Bp>,
+    ) -> impl Iterator<Item = (Range<Bp>, AnnotationId)> + '_ {
+        let end = range.end.0.max(range.start.0 + 1);
+        self.intervals
+            .iter(range.start.0..end)
+            .map(|(r, id)| (Bp(r.start)..Bp(r.end), *id))
+    }
+
+    /// The annotations overlapping `pos`, if any, or the closest
+    /// annotation on either side, with the distance to it
+    fn nearest(&self, pos: Bp) -> Option<(AnnotationId, u64)> {
+        let pos = pos.0;
+
+        if let Some(id) = self.intervals.values_overlap(pos).next() {
+            return Some((*id, 0));
+        }
+
+        // the first annotation starting after `pos`, and the last one
+        // ending at or before it
+        let right_ix = self.starts.partition_point(|&(s, _)| s <= pos);
+        let right = self.starts.get(right_ix).map(|&(s, id)| (id, s - pos));
+
+        let left_ix = self.ends.partition_point(|&(e, _)| e <= pos);
+        let left = left_ix
+            .checked_sub(1)
+            .map(|ix| self.ends[ix])
+            .map(|(e, id)| (id, pos - e));
+
+        match (left, right) {
+            (Some(l), Some(r)) => Some(if r.1 < l.1 { r } else { l }),
+            (l, r) => l.or(r),
+        }
+    }
+}
+
+/// Per-path and pangenome-space interval indexes for an annotation set
+#[derive(Default)]
+pub struct AnnotationIndex {
+    paths: HashMap<PathId, IntervalIndex>,
+    pangenome: IntervalIndex,
+
+    // the ranges of each annotation in pangenome space
+    pangenome_ranges: Vec<Vec<Range<Bp>>>,
+}
+
+impl AnnotationIndex {
+    pub fn build(graph: &PathIndex, annotations: &[Annotation]) -> Self {
+        let mut index = Self::default();
+
+        for (ix, annot) in annotations.iter().enumerate() {
+            let annot_id = AnnotationId(ix);
+
+            if let Some(path) = annot.path() {
+                index
+                    .paths
+                    .entry(path)
+                    .or_default()
+                    .insert(&annot.range, annot_id);
+            }
+
+            let ranges = target_range_pangenome_ranges(
+                graph,
+                &annot.target,
+                &annot.range,
+            );
+
+            for range in &ranges {
+                index.pangenome.insert(range, annot_id);
+            }
+
+            index.pangenome_ranges.push(ranges);
+        }
+
+        for path_index in index.paths.values_mut() {
+            path_index.finish();
+        }
+        index.pangenome.finish();
+
+        index
+    }
+
+    /// The annotations on `path` overlapping `range`, in path
+    /// coordinates, sorted by start
+    pub fn path_overlapping(
+        &self,
+        path: PathId,
+        range: Range<Bp>,
+    ) -> impl Iterator<Item = (Range<Bp>, AnnotationId)> + '_ {
+        self.paths
+            .get(&path)
+            .into_iter()
+            .flat_map(move |index| index.overlapping(range.clone()))
+    }
+
+    /// The annotations on `path` overlapping `pos`, if any, or else
+    /// the closest one, with its distance from `pos`
+    pub fn path_nearest(
+        &self,
+        path: PathId,
+        pos: Bp,
+    ) -> Option<(AnnotationId, u64)> {
+        self.paths.get(&path)?.nearest(pos)
+    }
+
+    /// The annotations overlapping `range` in pangenome space, sorted
+    /// by start; annotations that are split across several ranges can
+    /// be returned more than once
+    pub fn pangenome_overlapping(
+        &self,
+        range: Range<Bp>,
+    ) -> impl Iterator<Item = (Range<Bp>, AnnotationId)> + '_ {
+        self.pangenome.overlapping(range)
+    }
+
+    /// The annotations overlapping `pos` in pangenome space, if any,
+    /// or else the closest one, with its distance from `pos`
+    pub fn pangenome_nearest(&self, pos: Bp) -> Option<(AnnotationId, u64)> {
+        self.pangenome.nearest(pos)
+    }
+
+    /// The pangenome-space ranges covered by the annotation, sorted
+    pub fn pangenome_ranges(&self, annot_id: AnnotationId) -> &[Range<Bp>] {
+        self.pangenome_ranges
+            .get(annot_id.0)
+            .map(|r| r.as_slice())
+            .unwrap_or_default()
+    }
+}
+
+/// The pangenome ranges covered by `range` on `path`, sorted, with
+/// adjacent ranges merged; only the parts of the nodes at the ends
+/// of the range that are on the range are included, flipped for
+/// steps that traverse the node in reverse
+pub fn path_range_pangenome_ranges(
+    graph: &PathIndex,
+    path: PathId,
+    range: &Range<Bp>,
+) -> Vec<Range<Bp>> {
+    let Some(steps) =
+        graph.path_step_range_iter(path, range.start.0..range.end.0)
+    else {
+        return Vec::new();
+    };
+    let step_offsets = &graph.path_step_offsets[path.ix()];
+
+    let ranges = steps
+        .filter_map(|(step_ix, step)| {
+            let step_start = step_offsets.select(step_ix as u64)?;
+            let (offset, len) = graph.node_offset_length(step.node());
+
+            // the part of the step that's in the range
+            let lo = range.start.0.max(step_start) - step_start;
+            let hi = range.end.0.min(step_start + len.0);
+            let hi = hi.checked_sub(step_start)?;
+
+            if lo >= hi {
+                return None;
+            }
+
+            let (lo, hi) = if step.is_reverse() {
+                (len.0 - hi, len.0 - lo)
+            } else {
+                (lo, hi)
+            };
+
+            Some(Bp(offset.0 + lo)..Bp(offset.0 + hi))
+        })
+        .collect::<Vec<_>>();
+
+    merge_ranges(ranges)
+}
+
+/// The pangenome ranges covered by `range`, in the coordinates of an
+/// annotation with the given target; for node sets, these are the
+/// ranges of the nodes in the set that overlap `range`
+pub fn target_range_pangenome_ranges(
+    graph: &PathIndex,
+    target: &AnnotationTarget,
+    range: &Range<Bp>,
+) -> Vec<Range<Bp>> {
+    match target {
+        AnnotationTarget::Path(path) => {
+            path_range_pangenome_ranges(graph, *path, range)
+        }
+        AnnotationTarget::Pangenome => vec![range.clone()],
+        AnnotationTarget::Nodes(nodes) => {
+            let ranges = nodes
+                .iter()
+                .map(|&node| graph.node_pangenome_range(node))
+                .filter(|r| r.start < range.end && range.start < r.end)
+                .collect();
+
+            merge_ranges(ranges)
+        }
+    }
+}
+
+/// The ranges on `path` covering the parts of `range`, in pangenome
+/// space, that the path visits, sorted; the parts of nodes that the
+/// path traverses in reverse are flipped.
+///
+/// Like in [`super::projection::project_range`], the visits are
+/// grouped into runs, split wherever the gap between two visits on
+/// the path is longer than `range` itself, so that repeated visits to
+/// the same region are kept apart.
+pub fn pangenome_range_path_ranges(
+    graph: &PathIndex,
+    path: PathId,
+    range: &Range<Bp>,
+) -> Vec<Range<Bp>> {
+    let Some(path_steps) = graph.path_steps.get(path.ix()) else {
+        return Vec::new();
+    };
+
+    if range.start >= graph.pangenome_len() {
+        return Vec::new();
+    }
+
+    let last_pos = range.end.0.max(range.start.0 + 1) - 1;
+    let nodes = graph.pos_range_nodes(range.start.0..last_pos);
+
+    let mut visits = Vec::new();
+
+    for ix in nodes.start().ix()..=nodes.end().ix() {
+        let node = Node::from(ix);
+        let (offset, len) = graph.node_offset_length(node);
+
+        // the part of the node that's in the range
+        let lo = range.start.0.saturating_sub(offset.0).min(len.0);
+        let hi = range.end.0.saturating_sub(offset.0).min(len.0);
+
+        let Some(steps) = graph.node_path_step_offsets(node, path) else {
+            continue;
+        };
+
+        for (step_ix, pos) in steps {
+            let (lo, hi) = if path_steps[step_ix].is_reverse() {
+                (len.0 - hi, len.0 - lo)
+            } else {
+                (lo, hi)
+            };
+
+            visits.push(Bp(pos.0 + lo)..Bp(pos.0 + hi));
+        }
+    }
+
+    visits.sort_by_key(|r| (r.start, r.end));
+
+    let max_gap = range.end.0.saturating_sub(range.start.0);
+
+    let mut runs: Vec<Range<Bp>> = Vec::new();
+
+    for visit in visits {
+        match runs.last_mut() {
+            Some(run) if visit.start.0 <= run.end.0 + max_gap => {
+                run.end = run.end.max(visit.end);
+            }
+            _ => runs.push(visit),
+        }
+    }
+
+    runs
+}
+
+/// The run of [`pangenome_range_path_ranges`] that contains the
+/// path's visit to `anchor`, e.g. where a range selection started,
+/// or the longest run if the path doesn't visit `anchor`; `None` if
+/// the path doesn't visit any of the nodes in `range`
+pub fn pangenome_range_path_range(
+    graph: &PathIndex,
+    path: PathId,
+    range: &Range<Bp>,
+    anchor: Bp,
+) -> Option<Range<Bp>> {
+    let runs = pangenome_range_path_ranges(graph, path, range);
+
+    let last_pos = Bp(range.end.0.max(range.start.0 + 1) - 1);
+    let anchor = anchor.clamp(range.start, last_pos);
+    let anchor_visits =
+        pangenome_range_path_ranges(graph, path, &(anchor..Bp(anchor.0 + 1)));
+
+    runs.iter()
+        .find(|run| {
+            anchor_visits
+                .iter()
+                .any(|v| run.start <= v.start && v.end <= run.end)
+        })
+        .or_else(|| runs.iter().max_by_key(|run| run.end.0 - run.start.0))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::test_util::{test_graph, TempFile};
+
+    #[test]
+    fn nearest_intervals() {
+        let mut index = IntervalIndex::default();
+
+        for (ix, range) in [10..20, 15..30, 50..60].into_iter().enumerate() {
+            index.insert(&(Bp(range.start)..Bp(range.end)), AnnotationId(ix));
+        }
+        index.finish();
+
+        let overlapping = |a: u64, b: u64| {
+            index
+                .overlapping(Bp(a)..Bp(b))
+                .map(|(_, id)| id.0)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(overlapping(0, 10), Vec::<usize>::new());
+        assert_eq!(overlapping(18, 19), vec![0, 1]);
+        assert_eq!(overlapping(25, 55), vec![1, 2]);
+
+        assert_eq!(index.nearest(Bp(12)), Some((AnnotationId(0), 0)));
+        assert_eq!(index.nearest(Bp(5)), Some((AnnotationId(0), 5)));
+        assert_eq!(index.nearest(Bp(35)), Some((AnnotationId(1), 5)));
+        assert_eq!(index.nearest(Bp(45)), Some((AnnotationId(2), 5)));
+        assert_eq!(index.nearest(Bp(100)), Some((AnnotationId(2), 40)));
+    }
+
+    #[test]
+    fn path_queries() {
+        let graph = test_graph();
+        let mut paths = graph.path_names.left_values().copied();
+        let (path, other) = (paths.next().unwrap(), paths.next().unwrap());
+
+        let annot = |range: Range<u64>| {
+            let range = Bp(range.start)..Bp(range.end);
+            Annotation::new(path, range, Arc::new("a".into()), None)
+        };
+        let index =
+            AnnotationIndex::build(&graph, &[annot(10..20), annot(50..60)]);
+
+        let overlapping = |path, a: u64, b: u64| {
+            index
+                .path_overlapping(path, Bp(a)..Bp(b))
+                .map(|(_, id)| id.0)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(overlapping(path, 0, 100), vec![0, 1]);
+        assert_eq!(overlapping(path, 15, 16), vec![0]);
+        assert!(overlapping(other, 0, 100).is_empty());
+
+        assert_eq!(
+            index.path_nearest(path, Bp(40)),
+            Some((AnnotationId(1), 10))
+        );
+        assert_eq!(index.path_nearest(other, Bp(40)), None);
+    }
+
+    #[test]
+    fn path_parts_within_node() {
+        let graph = test_graph();
+        let path = *graph.path_names.left_values().next().unwrap();
+
+        // a step on a node long enough to hold a multi-block record
+        let (step_ix, step) = graph.path_steps[path.ix()]
+            .iter()
+            .enumerate()
+            .find(|(_, step)| graph.node_length(step.node()).0 >= 40)
+            .unwrap();
+        let step_start = graph.path_step_offsets[path.ix()]
+            .select(step_ix as u64)
+            .unwrap();
+        let (node_offset, len) = graph.node_offset_length(step.node());
+
+        let at = |a: u64, b: u64| Bp(step_start + a)..Bp(step_start + b);
+
+        let annot = Annotation {
+            blocks: vec![at(5, 10), at(20, 30)],
+            thick_range: Some(at(8, 25)),
+            ..Annotation::new(path, at(5, 30), Arc::new("a".into()), None)
+        };
+
+        let parts = annot.feature_parts();
+        assert_eq!(parts.len(), 5);
+
+        for (part, _) in parts {
+            let (lo, hi) = (part.start.0 - step_start, part.end.0 - step_start);
+            let (lo, hi) = if step.is_reverse() {
+                (len.0 - hi, len.0 - lo)
+            } else {
+                (lo, hi)
+            };
+
+            assert_eq!(
+                path_range_pangenome_ranges(&graph, path, &part),
+                vec![Bp(node_offset.0 + lo)..Bp(node_offset.0 + hi)]
+            );
+        }
+    }
+
+    #[test]
+    fn pangenome_to_path_range() -> anyhow::Result<()> {
+        let graph = test_graph();
+        let path = *graph.path_names.left_values().next().unwrap();
+
+        // the pangenome range of a node maps back to the steps of
+        // the path on that node, and part of it to the same part of
+        // the steps
+        let node = graph.step_at_pos(path, 1000u64).unwrap().node();
+        let node_range = graph.node_pangenome_range(node);
+        let len = graph.node_length(node);
+
+        let steps = graph
+            .node_path_step_offsets(node, path)
+            .unwrap()
+            .map(|(_, pos)| pos)
+            .collect::<Vec<_>>();
+        let first = *steps.iter().min().unwrap();
+
+        let runs = pangenome_range_path_ranges(&graph, path, &node_range);
+        for &pos in &steps {
+            let step = pos..Bp(pos.0 + len.0);
+            assert!(runs
+                .iter()
+                .any(|r| r.start <= step.start && step.end <= r.end));
+        }
+
+        let anchored = pangenome_range_path_range(
+            &graph,
+            path,
+            &node_range,
+            node_range.start,
+        );
+        assert_eq!(anchored.map(|r| r.start), Some(first));
+
+        let half = node_range.start..Bp(node_range.start.0 + len.0 / 2);
+        let runs = pangenome_range_path_ranges(&graph, path, &half);
+        assert_eq!(runs.first(), Some(&(first..Bp(first.0 + len.0 / 2))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reverse_and_repeated_visits() -> anyhow::Result<()> {
+        // node 1 is 10bp, at 0..10 in pangenome space, and node 2 is
+        // 4bp, at 10..14; `a` visits node 1 twice, and `b` traverses
+        // it in reverse
+        let gfa = "S\t1\tAAAAACCCCC\nS\t2\tGGTT\n\
+                   P\ta\t1+,2+,1+\t*\nP\tb\t2+,1-\t*\n";
+
+        let file_path = TempFile::new("gfa", gfa)?;
+        let graph = PathIndex::from_gfa(&file_path)?;
+
+        let path = |name: &str| {
+            *graph.path_names.get_by_right(&name.to_string()).unwrap()
+        };
+        let (a, b) = (path("a"), path("b"));
+
+        let range = Bp(8)..Bp(12);
+        let ranges = |path, range: &Range<Bp>| {
+            pangenome_range_path_ranges(&graph, path, range)
+        };
+
+        // on `a`, the end of the first visit to node 1 and the start
+        // of node 2 form one run, and the second visit another
+        assert_eq!(ranges(a, &range), vec![Bp(8)..Bp(12), Bp(22)..Bp(24)]);
+        assert_eq!(
+            pangenome_range_path_range(&graph, a, &range, Bp(11)),
+            Some(Bp(8)..Bp(12))
+        );
+
+        // node 1 starts at 4 on `b`, and is flipped
+        assert_eq!(ranges(b, &(Bp(2)..Bp(5))), vec![Bp(9)..Bp(12)]);
+        assert_eq!(ranges(b, &range), vec![Bp(0)..Bp(6)]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+//! Loading annotation files of any of the supported formats, with the
+//! options given on the command line or in the settings.
+
+use anyhow::Result;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use waragraph_core::graph::PathIndex;
+
+use super::path_names::PathNameMap;
+use super::AnnotationSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AnnotationFormat {
+    Bed,
+    Gff,
+    Vcf,
+    /// BED with ranges in pangenome coordinates
+    PangenomeBed,
+    /// See [`super::nodes`]
+    NodeList,
+}
+
+impl AnnotationFormat {
+    /// Detects the format of a file on paths from its extension; BED
+    /// in pangenome coordinates and node lists can't be told apart
+    /// from other files, and must be given explicitly
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let name = path.as_ref().file_name()?.to_str()?.to_ascii_lowercase();
+
+        let has_ext =
+            |exts: &[&str]| exts.iter().any(|ext| name.ends_with(ext));
+
+        if has_ext(&[".vcf", ".vcf.gz", ".vcf.bgz"]) {
+            Some(Self::Vcf)
+        } else if has_ext(&[".bed"]) {
+            Some(Self::Bed)
+        } else if has_ext(&[".gff", ".gff3"]) {
+            Some(Self::Gff)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Bed => "BED",
+            Self::Gff => "GFF3",
+            Self::Vcf => "VCF",
+            Self::PangenomeBed => "pangenome BED",
+            Self::NodeList => "node list",
+        }
+    }
+}
+
+/// The file an annotation set was loaded from, so that it can be
+/// reloaded, e.g. with a different path name mapping
+#[derive(Debug, Clone)]
+pub struct AnnotationSource {
+    pub path: PathBuf,
+    pub format: AnnotationFormat,
+    /// Options used for this file instead of the global ones
+    pub options: Option<LoadOptions>,
+}
+
+impl AnnotationSource {
+    pub fn new(path: impl Into<PathBuf>, format: AnnotationFormat) -> Self {
+        Self {
+            path: path.into(),
+            format,
+            options: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct LoadOptions {
+    pub path_names: PathNameMap,
+
+    /// The GFF3 attribute to use for labels, `Name` by default
+    pub gff_attr: Option<String>,
+    /// Skip GFF3 records without the label attribute, rather than
+    /// labeling them by `ID` or type
+    pub gff_skip_unlabeled: bool,
+    /// If set, only GFF3 records of these types are loaded
+    pub gff_types: Option<Vec<String>>,
+
+    /// If set, only VCF variants carried by these samples are loaded
+    pub vcf_samples: Option<HashSet<String>>,
+}
+
+impl AnnotationSet {
+    /// Loads an annotation file in the given format, see the
+    /// format-specific loaders, e.g. [`AnnotationSet::from_bed`]. The
+    /// options of the source, if any, are used instead of `options`
+    pub fn load(
+        graph: &PathIndex,
+        options: &LoadOptions,
+        source: AnnotationSource,
+    ) -> Result<Self> {
+        let options = source.options.as_ref().unwrap_or(options);
+        let path_name_map = |name: &str| options.path_names.apply(name);
+        let path = &source.path;
+
+        let mut set = match source.format {
+            AnnotationFormat::Bed => {
+                Self::from_bed(graph, None, path_name_map, path)?
+            }
+            AnnotationFormat::Gff => {
+                let attr = options.gff_attr.as_deref().unwrap_or("Name");
+
+                Self::from_gff(
+                    graph,
+                    None,
+                    path_name_map,
+                    |record| {
+                        let attrs = record.attributes();
+                        let label = attrs.iter().find_map(|entry| {
+                            (entry.key() == attr).then_some(entry.value())
+                        })?;
+
+                        Some(label.to_string())
+                    },
+                    options.gff_skip_unlabeled,
+                    |ty| match &options.gff_types {
+                        Some(types) => types.iter().any(|t| t == ty),
+                        None => true,
+                    },
+                    path,
+                )?
+            }
+            AnnotationFormat::Vcf => Self::from_vcf(
+                graph,
+                None,
+                path_name_map,
+                options.vcf_samples.as_ref(),
+                path,
+            )?,
+            AnnotationFormat::PangenomeBed => {
+                Self::from_pangenome_bed(graph, None, path)?
+            }
+            AnnotationFormat::NodeList => {
+                Self::from_node_lists(graph, None, path)?
+            }
+        };
+
+        set.source = Some(source);
+
+        Ok(set)
+    }
+}
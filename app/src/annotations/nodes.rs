@@ -0,0 +1,121 @@
+//! Parsing node lists, annotations of graph regions given as sets of
+//! nodes, e.g. the nodes of a bubble as reported by another tool.
+//!
+//! Each line is a tab-separated label, node list, and optional
+//! `#RRGGBB` color. The node list holds GFA segment IDs separated by
+//! commas or spaces, with inclusive ranges written as `start-end`.
+
+use anyhow::{Context, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodeListRecord<'a> {
+    pub label: &'a str,
+    /// Inclusive ranges of GFA segment IDs
+    pub segments: Vec<std::ops::RangeInclusive<u32>>,
+    pub color: Option<egui::Color32>,
+}
+
+/// Parses a line of a node list file, returning `None` for empty lines
+/// and `#` comments
+pub fn parse_node_list_line(line: &str) -> Result<Option<NodeListRecord<'_>>> {
+    let line = line.trim_end_matches(['\n', '\r']);
+
+    if line.trim().is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let fields: Vec<&str> = line.split('\t').collect();
+
+    if fields.len() < 2 {
+        anyhow::bail!("Node list record must have a label and nodes: `{line}`");
+    }
+
+    let parse_id = |s: &str| -> Result<u32> {
+        s.trim()
+            .parse()
+            .with_context(|| format!("Invalid segment ID `{s}`"))
+    };
+
+    let segments = fields[1]
+        .split([',', ' '])
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| {
+            if let Some((start, end)) = s.split_once('-') {
+                let (start, end) = (parse_id(start)?, parse_id(end)?);
+                if end < start {
+                    anyhow::bail!("Segment range ends before it starts: `{s}`");
+                }
+                Ok(start..=end)
+            } else {
+                let id = parse_id(s)?;
+                Ok(id..=id)
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let color = fields.get(2).and_then(|c| super::parse_color(c));
+
+    Ok(Some(NodeListRecord {
+        label: fields[0].trim(),
+        segments,
+        color,
+    }))
+}
+
+/// Formats GFA segment IDs as a node list, e.g. `5,7-9,12`, with runs
+/// of consecutive IDs as ranges
+pub fn format_node_list(segment_ids: impl IntoIterator<Item = u32>) -> String {
+    let mut ids = segment_ids.into_iter().collect::<Vec<_>>();
+    ids.sort_unstable();
+    ids.dedup();
+
+    let mut runs: Vec<std::ops::RangeInclusive<u32>> = Vec::new();
+
+    for id in ids {
+        match runs.last_mut() {
+            Some(run) if *run.end() + 1 == id => *run = *run.start()..=id,
+            _ => runs.push(id..=id),
+        }
+    }
+
+    runs.iter()
+        .map(|run| {
+            if run.start() == run.end() {
+                run.start().to_string()
+            } else {
+                format!("{}-{}", run.start(), run.end())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_list_records() -> Result<()> {
+        assert_eq!(parse_node_list_line("# bubbles")?, None);
+        assert_eq!(parse_node_list_line("")?, None);
+
+        let record =
+            parse_node_list_line("bubble 1\t5,7-9 12\t#ff0000")?.unwrap();
+        assert_eq!(record.label, "bubble 1");
+        assert_eq!(record.segments, vec![5..=5, 7..=9, 12..=12]);
+        assert_eq!(record.color, Some(egui::Color32::from_rgb(255, 0, 0)));
+
+        assert_eq!(format_node_list([12, 8, 5, 7, 9, 8]), "5,7-9,12");
+        assert_eq!(format_node_list([]), "");
+
+        let record = parse_node_list_line("b2\t3")?.unwrap();
+        assert_eq!(record.segments, vec![3..=3]);
+        assert_eq!(record.color, None);
+
+        assert!(parse_node_list_line("no nodes").is_err());
+        assert!(parse_node_list_line("b3\t9-7").is_err());
+        assert!(parse_node_list_line("b4\tx").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,210 @@
+//! Mapping the sequence names used in annotation files, e.g. BED
+//! chromosomes and GFF3 seqids, to the names of the paths in the
+//! graph, and reporting which names didn't match any path.
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, HashMap};
+
+use waragraph_core::graph::{PathId, PathIndex};
+
+/// How sequence names are turned into path names. A name found in the
+/// rename table is used as is; otherwise the regex rewrite is applied,
+/// followed by the template.
+#[derive(Debug, Default, Clone)]
+pub struct PathNameMap {
+    /// Template with a `{name}` placeholder, e.g. `SGDref#1#{name}`
+    template: Option<String>,
+    /// Exact renames, e.g. read from a two-column table
+    pub renames: HashMap<String, String>,
+    /// Regex and replacement, using `$1` etc. for capture groups
+    rewrite: Option<(regex::Regex, String)>,
+}
+
+impl PathNameMap {
+    pub fn apply(&self, name: &str) -> String {
+        if let Some(renamed) = self.renames.get(name) {
+            return renamed.clone();
+        }
+
+        let name = match &self.rewrite {
+            Some((regex, replacement)) => {
+                regex.replace(name, replacement.as_str()).into_owned()
+            }
+            None => name.to_string(),
+        };
+
+        match &self.template {
+            Some(template) => template.replace("{name}", &name),
+            None => name,
+        }
+    }
+
+    pub fn template(&self) -> Option<&str> {
+        self.template.as_deref()
+    }
+
+    /// Sets the template, which must contain `{name}`
+    pub fn set_template(&mut self, template: Option<&str>) -> Result<()> {
+        if let Some(template) = template {
+            if !template.contains("{name}") {
+                anyhow::bail!(
+                    "Path name template `{template}` doesn't contain `{{name}}`"
+                );
+            }
+        }
+
+        self.template = template.map(String::from);
+        Ok(())
+    }
+
+    pub fn rewrite(&self) -> Option<(&str, &str)> {
+        self.rewrite
+            .as_ref()
+            .map(|(regex, replacement)| (regex.as_str(), replacement.as_str()))
+    }
+
+    pub fn set_rewrite(&mut self, rewrite: Option<(&str, &str)>) -> Result<()> {
+        self.rewrite = rewrite
+            .map(|(pattern, replacement)| {
+                let regex = regex::Regex::new(pattern).with_context(|| {
+                    format!("Invalid path name regex `{pattern}`")
+                })?;
+                anyhow::Ok((regex, replacement.to_string()))
+            })
+            .transpose()?;
+        Ok(())
+    }
+}
+
+/// Reads a rename table, with a sequence name and the path name to use
+/// for it on each line, separated by tabs or spaces; lines starting
+/// with `#` are ignored
+pub fn load_rename_table(
+    path: impl AsRef<std::path::Path>,
+) -> Result<HashMap<String, String>> {
+    let text = std::fs::read_to_string(path)?;
+    parse_rename_table(&text)
+}
+
+fn parse_rename_table(text: &str) -> Result<HashMap<String, String>> {
+    let mut renames = HashMap::new();
+
+    for (line_ix, line) in text.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut fields = line.split_whitespace();
+
+        match (fields.next(), fields.next(), fields.next()) {
+            (Some(from), Some(to), None) => {
+                renames.insert(from.to_string(), to.to_string());
+            }
+            _ => anyhow::bail!(
+                "Rename table line {} must have two columns: `{line}`",
+                line_ix + 1
+            ),
+        }
+    }
+
+    Ok(renames)
+}
+
+/// How many records in an annotation file were on sequences that
+/// matched a path, and which sequences didn't
+#[derive(Debug, Default, Clone)]
+pub struct PathMatchReport {
+    pub matched: usize,
+    /// The sequence names that didn't match, with the path name they
+    /// were mapped to and their number of records
+    pub unmatched: BTreeMap<String, (String, usize)>,
+}
+
+impl PathMatchReport {
+    /// Maps the sequence name of a record to a path name, and returns
+    /// the path with that name, if any, recording whether it matched
+    pub fn match_path(
+        &mut self,
+        graph: &PathIndex,
+        path_name_map: impl Fn(&str) -> String,
+        seq_name: &str,
+    ) -> Option<PathId> {
+        if let Some((_, count)) = self.unmatched.get_mut(seq_name) {
+            *count += 1;
+            return None;
+        }
+
+        let path_name = path_name_map(seq_name);
+
+        if let Some(path) = graph.path_names.get_by_right(&path_name) {
+            self.matched += 1;
+            Some(*path)
+        } else {
+            self.unmatched.insert(seq_name.to_string(), (path_name, 1));
+            None
+        }
+    }
+
+    pub fn unmatched_records(&self) -> usize {
+        self.unmatched.values().map(|(_, count)| count).sum()
+    }
+
+    /// Logs the number of matched records, and a warning with the
+    /// unmatched sequence names if there are any
+    pub fn log(&self, kind: &str) {
+        log::info!(
+            "{} {kind} records matched paths in the graph",
+            self.matched
+        );
+
+        if self.unmatched.is_empty() {
+            return;
+        }
+
+        let names = self
+            .unmatched
+            .iter()
+            .map(|(name, (path_name, _))| {
+                if name == path_name {
+                    name.clone()
+                } else {
+                    format!("{name} (as {path_name})")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        log::warn!(
+            "Skipped {} {kind} records on sequences that aren't paths \
+             in the graph: {names}",
+            self.unmatched_records()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn path_name_mapping() -> Result<()> {
+        let mut map = PathNameMap::default();
+        assert_eq!(map.apply("chrI"), "chrI");
+
+        map.set_template(Some("SGDref#1#{name}"))?;
+        assert_eq!(map.apply("chrI"), "SGDref#1#chrI");
+        assert!(map.set_template(Some("SGDref#1#")).is_err());
+
+        map.set_rewrite(Some(("^chr", "")))?;
+        assert_eq!(map.apply("chrI"), "SGDref#1#I");
+        assert!(map.set_rewrite(Some(("(", ""))).is_err());
+
+        map.renames = parse_rename_table("# from to\nmito\tS288C#0#chrM\n")?;
+        assert_eq!(map.apply("mito"), "S288C#0#chrM");
+        assert_eq!(map.apply("chrII"), "SGDref#1#II");
+
+        assert!(parse_rename_table("a b c").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,161 @@
+//! Projecting annotations from the path they're defined on, usually a
+//! reference assembly, onto the other paths through the nodes they
+//! share.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ops::Range;
+
+use waragraph_core::graph::{Bp, Node, PathId, PathIndex};
+
+use super::{Annotation, AnnotationSet};
+
+/// Maps `range` on `path` onto every other path that visits any of
+/// the nodes in the range, rounded out to node boundaries.
+///
+/// The steps of each other path on those nodes are grouped into runs,
+/// split wherever the gap between two steps is longer than `range`
+/// itself, so that e.g. multiple copies of a gene on a path are
+/// projected separately.
+pub fn project_range(
+    graph: &PathIndex,
+    path: PathId,
+    range: Range<Bp>,
+) -> Vec<(PathId, Range<Bp>)> {
+    let Some(steps) = graph.path_step_range_iter(path, range.clone()) else {
+        return Vec::new();
+    };
+
+    let nodes = steps
+        .map(|(_, step)| step.node())
+        .collect::<BTreeSet<Node>>();
+
+    let mut path_steps: BTreeMap<PathId, Vec<Range<Bp>>> = BTreeMap::new();
+
+    for &node in &nodes {
+        let len = graph.node_length(node);
+
+        for other in graph.paths_on_node(node).into_iter().flatten() {
+            if other == path {
+                continue;
+            }
+
+            if let Some(offsets) = graph.node_path_step_offsets(node, other) {
+                path_steps
+                    .entry(other)
+                    .or_default()
+                    .extend(offsets.map(|(_, pos)| pos..Bp(pos.0 + len.0)));
+            }
+        }
+    }
+
+    let max_gap = range.end.0.saturating_sub(range.start.0);
+
+    let mut result = Vec::new();
+
+    for (other, mut steps) in path_steps {
+        steps.sort_by_key(|r| (r.start, r.end));
+
+        let mut run: Option<Range<Bp>> = None;
+
+        for step in steps {
+            match run.as_mut() {
+                Some(run) if step.start.0 <= run.end.0 + max_gap => {
+                    run.end = run.end.max(step.end);
+                }
+                _ => {
+                    if let Some(run) = run.replace(step) {
+                        result.push((other, run));
+                    }
+                }
+            }
+        }
+
+        result.extend(run.map(|run| (other, run)));
+    }
+
+    result
+}
+
+impl AnnotationSet {
+    /// Projects the top-level annotations on paths in the set onto all
+    /// the other paths, see [`project_range`]. The projected annotations
+    /// keep the label, color, and feature type, but are single blocks.
+    pub fn project_to_paths(&self, graph: &PathIndex) -> Self {
+        let mut annotations = Vec::new();
+
+        for annot in &self.annotations {
+            let Some(path) = annot.path() else {
+                continue;
+            };
+
+            if annot.parent.is_some() {
+                continue;
+            }
+
+            let projected = project_range(graph, path, annot.range.clone());
+
+            for (path, range) in projected {
+                annotations.push(Annotation {
+                    score: annot.score,
+                    feature_type: annot.feature_type.clone(),
+                    ..Annotation::new(
+                        path,
+                        range,
+                        annot.label.clone(),
+                        annot.color,
+                    )
+                });
+            }
+        }
+
+        let name = format!("{} (projected)", self.name);
+        Self::new(graph, name, annotations, HashMap::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_graph;
+
+    #[test]
+    fn projected_ranges() -> anyhow::Result<()> {
+        let graph = test_graph();
+        let path = *graph.path_names.left_values().next().unwrap();
+
+        let range = Bp(1000)..Bp(1500);
+        let projected = project_range(&graph, path, range.clone());
+
+        let first_node = graph.step_at_pos(path, 1000u64).unwrap().node();
+        let sharing_paths = graph
+            .paths_on_node(first_node)
+            .unwrap()
+            .filter(|&p| p != path)
+            .collect::<BTreeSet<_>>();
+
+        assert!(!sharing_paths.is_empty());
+
+        // every step of the other paths on the shared node is covered
+        // by one of the ranges projected onto that path
+        for other in sharing_paths {
+            let steps =
+                graph.node_path_step_offsets(first_node, other).unwrap();
+
+            for (_, pos) in steps {
+                assert!(projected
+                    .iter()
+                    .any(|(p, r)| *p == other && r.contains(&pos)));
+            }
+        }
+
+        for (other, range) in &projected {
+            assert_ne!(*other, path);
+            assert!(range.start < range.end);
+
+            let len = graph.path_step_offsets[other.ix()].max().unwrap();
+            assert!(range.start.0 <= len);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+//! Parsing VCF records, enough to place variants on paths and tell
+//! which samples carry them; positions are 1-based, as in VCF.
+
+use anyhow::{Context, Result};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VcfRecord<'a> {
+    pub chrom: &'a str,
+    pub pos: u64,
+    pub id: Option<&'a str>,
+    pub ref_allele: &'a str,
+    pub alt_alleles: Vec<&'a str>,
+    pub qual: Option<f32>,
+
+    /// The `END` INFO field, used by structural variants
+    pub info_end: Option<u64>,
+
+    /// The `GT` field of each sample column, if there is one
+    pub genotypes: Vec<Option<&'a str>>,
+}
+
+/// Variants with a length difference of at least this many bases
+/// between the REF and ALT alleles are classified as structural
+pub const SV_MIN_LENGTH: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VariantClass {
+    Snv,
+    /// Multi-nucleotide substitution, with equal length alleles
+    Mnp,
+    Indel,
+    /// Structural variant, either with symbolic or breakend alleles,
+    /// or large enough
+    Sv,
+}
+
+impl VariantClass {
+    pub fn from_alleles(ref_allele: &str, alt_alleles: &[&str]) -> Self {
+        let is_symbolic = |alt: &&str| {
+            alt.starts_with('<') || alt.contains('[') || alt.contains(']')
+        };
+
+        if alt_alleles.iter().any(is_symbolic) {
+            return VariantClass::Sv;
+        }
+
+        let ref_len = ref_allele.len();
+
+        let max_diff = alt_alleles
+            .iter()
+            .map(|alt| alt.len().abs_diff(ref_len))
+            .max()
+            .unwrap_or_default();
+
+        if max_diff >= SV_MIN_LENGTH {
+            VariantClass::Sv
+        } else if max_diff > 0 {
+            VariantClass::Indel
+        } else if ref_len == 1 {
+            VariantClass::Snv
+        } else {
+            VariantClass::Mnp
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            VariantClass::Snv => "SNV",
+            VariantClass::Mnp => "MNP",
+            VariantClass::Indel => "indel",
+            VariantClass::Sv => "SV",
+        }
+    }
+
+    pub fn color(&self) -> egui::Color32 {
+        match self {
+            VariantClass::Snv => egui::Color32::from_rgb(0x1f, 0x77, 0xb4),
+            VariantClass::Mnp => egui::Color32::from_rgb(0x2c, 0xa0, 0x2c),
+            VariantClass::Indel => egui::Color32::from_rgb(0xff, 0x7f, 0x0e),
+            VariantClass::Sv => egui::Color32::from_rgb(0xd6, 0x27, 0x28),
+        }
+    }
+}
+
+impl<'a> VcfRecord<'a> {
+    pub fn class(&self) -> VariantClass {
+        VariantClass::from_alleles(self.ref_allele, &self.alt_alleles)
+    }
+
+    /// The 0-based, half-open range covered by the REF allele, or up
+    /// to `END` if it's given
+    pub fn range(&self) -> std::ops::Range<u64> {
+        let start = self.pos.saturating_sub(1);
+        let ref_end = start + self.ref_allele.len() as u64;
+        let end = self.info_end.map_or(ref_end, |end| end.max(ref_end));
+        start..end
+    }
+
+    /// `ID REF>ALT`, with the ID left out if it's missing, and long
+    /// alleles replaced by their lengths
+    pub fn label(&self) -> String {
+        fn allele(a: &str) -> String {
+            if a.len() > 10 && !a.starts_with('<') {
+                format!("{}bp", a.len())
+            } else {
+                a.to_string()
+            }
+        }
+
+        let alts = self
+            .alt_alleles
+            .iter()
+            .map(|a| allele(a))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let change = format!("{}>{alts}", allele(self.ref_allele));
+
+        if let Some(id) = self.id {
+            format!("{id} {change}")
+        } else {
+            change
+        }
+    }
+
+    /// True if the genotype of the sample in column `sample_ix`
+    /// contains a non-reference allele
+    pub fn sample_has_alt(&self, sample_ix: usize) -> bool {
+        let Some(Some(gt)) = self.genotypes.get(sample_ix) else {
+            return false;
+        };
+
+        gt.split(['/', '|'])
+            .any(|a| a.parse::<usize>().is_ok_and(|a| a > 0))
+    }
+}
+
+/// The sample names on the `#CHROM` header line, if `line` is that
+/// line
+pub fn parse_sample_names(line: &str) -> Option<Vec<String>> {
+    let line = line.strip_prefix("#CHROM")?;
+
+    Some(
+        line.trim_end()
+            .split('\t')
+            .skip(9)
+            .map(String::from)
+            .collect(),
+    )
+}
+
+/// Parses a line of a VCF file, returning `None` for empty and header
+/// lines
+pub fn parse_vcf_line(line: &str) -> Result<Option<VcfRecord<'_>>> {
+    let line = line.trim_end_matches(['\n', '\r']);
+
+    if line.trim().is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let fields: Vec<&str> = line.split('\t').collect();
+
+    if fields.len() < 8 {
+        anyhow::bail!("VCF record must have at least 8 fields: `{line}`");
+    }
+
+    fn missing(f: &str) -> Option<&str> {
+        (f != ".").then_some(f)
+    }
+
+    let chrom = fields[0];
+    let pos = fields[1]
+        .parse()
+        .with_context(|| format!("Invalid VCF POS `{}`", fields[1]))?;
+    let id = missing(fields[2]);
+    let ref_allele = fields[3];
+
+    let alt_alleles = missing(fields[4])
+        .map(|alts| alts.split(',').collect())
+        .unwrap_or_default();
+
+    let qual = missing(fields[5])
+        .map(|q| {
+            q.parse::<f32>()
+                .with_context(|| format!("Invalid VCF QUAL `{q}`"))
+        })
+        .transpose()?;
+
+    let info_end = fields[7]
+        .split(';')
+        .find_map(|entry| entry.strip_prefix("END="))
+        .and_then(|end| end.parse().ok());
+
+    let gt_ix = fields
+        .get(8)
+        .and_then(|format| format.split(':').position(|key| key == "GT"));
+
+    let genotypes = fields
+        .iter()
+        .skip(9)
+        .map(|sample| sample.split(':').nth(gt_ix?))
+        .collect();
+
+    Ok(Some(VcfRecord {
+        chrom,
+        pos,
+        id,
+        ref_allele,
+        alt_alleles,
+        qual,
+        info_end,
+        genotypes,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vcf_records() -> Result<()> {
+        let header =
+            "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO\tFORMAT\tHG1\tHG2";
+        assert_eq!(
+            parse_sample_names(header),
+            Some(vec!["HG1".to_string(), "HG2".to_string()])
+        );
+        assert_eq!(parse_vcf_line(header)?, None);
+
+        let snv = parse_vcf_line(
+            "chr1\t100\trs1\tA\tG\t30\tPASS\t.\tGT:DP\t0|1:10\t0|0:12",
+        )?
+        .unwrap();
+        assert_eq!(snv.range(), 99..100);
+        assert_eq!(snv.label(), "rs1 A>G");
+        assert_eq!(snv.class(), VariantClass::Snv);
+        assert!(snv.sample_has_alt(0));
+        assert!(!snv.sample_has_alt(1));
+
+        let indel =
+            parse_vcf_line("chr1\t200\t.\tACGT\tA,ACGTT\t.\t.\t.")?.unwrap();
+        assert_eq!(indel.range(), 199..203);
+        assert_eq!(indel.label(), "ACGT>A,ACGTT");
+        assert_eq!(indel.class(), VariantClass::Indel);
+        assert!(!indel.sample_has_alt(0));
+
+        let sv = parse_vcf_line(
+            "chr1\t300\tdel1\tN\t<DEL>\t.\tPASS\tSVTYPE=DEL;END=1300",
+        )?
+        .unwrap();
+        assert_eq!(sv.range(), 299..1300);
+        assert_eq!(sv.class(), VariantClass::Sv);
+
+        assert_eq!(
+            VariantClass::from_alleles("AC", &["GT"]),
+            VariantClass::Mnp
+        );
+
+        assert!(parse_vcf_line("chr1\tx\t.\tA\tG\t.\t.\t.").is_err());
+
+        Ok(())
+    }
+}
@@ -11,14 +11,24 @@ use winit::{
     window::WindowId,
 };
 
-use std::{collections::HashMap, path::PathBuf, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+};
 
 use anyhow::Result;
 
 use crate::{
-    annotations::{AnnotationSet, AnnotationStore},
+    annotations::{
+        density::register_annotation_density,
+        load::{AnnotationFormat, AnnotationSource, LoadOptions},
+        path_names::{load_rename_table, PathNameMap},
+        AnnotationSet, AnnotationSetId, AnnotationStore,
+    },
     color::{ColorSchemeId, ColorStore},
     context::{widget::ContextInspector, ContextState},
+    gui::annotations::{AnnotationFilesWidget, PathNamesWidget},
     viewer_1d::Viewer1D,
     viewer_2d::Viewer2D,
 };
@@ -34,8 +44,8 @@ pub mod resource;
 pub use window::AppWindowState;
 
 use self::{
-    resource::{AnyArcMap, GraphDataCache},
-    settings_menu::SettingsWindow,
+    resource::{AnyArcMap, DataSource, DataSourceId, GraphDataCache},
+    settings_menu::{SettingsUiContext, SettingsWindow},
     window::{AppWindows, AsleepWindow, WindowDelta},
     workspace::Workspace,
 };
@@ -48,15 +58,131 @@ pub struct SharedState {
     pub graph_data_cache: Arc<GraphDataCache>,
 
     pub annotations: Arc<RwLock<AnnotationStore>>,
+    /// The options annotation files are loaded with, see
+    /// `AnnotationSet::load`
+    pub annotation_options: Arc<RwLock<LoadOptions>>,
+    // the names of the density sources of each annotation set, see
+    // `register_annotation_density`
+    annotation_source_names: Arc<RwLock<HashMap<AnnotationSetId, String>>>,
 
     pub colors: Arc<RwLock<ColorStore>>,
 
     pub workspace: Arc<RwLock<Workspace>>,
     // gfa_path: Arc<PathBuf>,
     // tsv_path: Option<Arc<RwLock<PathBuf>>>,
-    pub data_color_schemes: Arc<RwLock<HashMap<String, ColorSchemeId>>>,
+    /// Color schemes used for data sources, overriding their default
+    pub data_color_schemes: Arc<RwLock<HashMap<DataSourceId, ColorSchemeId>>>,
 
     pub app_msg_send: tokio::sync::mpsc::Sender<AppMsg>,
+
+    /// For opening file dialogs from the viewers
+    pub settings_ctx: Arc<SettingsUiContext>,
+}
+
+impl SharedState {
+    /// The color scheme to use for the data source `data`, which is
+    /// the data source's default unless it's been overridden
+    pub fn data_color_scheme(&self, data: DataSourceId) -> ColorSchemeId {
+        if let Some(scheme) = self.data_color_schemes.blocking_read().get(&data)
+        {
+            return *scheme;
+        }
+
+        self.graph_data_cache
+            .data_source_meta(data)
+            .default_color_scheme
+    }
+
+    /// Register the density of the annotation set with ID `set_id` as
+    /// data sources, see `register_annotation_density`, replacing the
+    /// sources of the set that had that ID, if any.
+    ///
+    /// The sources are named after the set; if that name is already
+    /// used by another set, e.g. one loaded from a file with the same
+    /// name in another directory, a number is appended to it.
+    pub fn register_annotation_density(
+        &self,
+        set_id: AnnotationSetId,
+        set: &Arc<AnnotationSet>,
+    ) -> [DataSourceId; 2] {
+        let color_scheme = self
+            .colors
+            .blocking_read()
+            .get_color_scheme_id("viridis")
+            .unwrap();
+
+        let name = {
+            let mut names = self.annotation_source_names.blocking_write();
+
+            let is_taken = |name: &str| {
+                ["density", "count"].iter().any(|suffix| {
+                    let source = format!("{name}_{suffix}");
+                    self.graph_data_cache.data_source_id(&source).is_ok()
+                })
+            };
+
+            names
+                .entry(set_id)
+                .or_insert_with(|| {
+                    std::iter::once(set.name.clone())
+                        .chain((2..).map(|i| format!("{}_{i}", set.name)))
+                        .find(|name| !is_taken(name))
+                        .unwrap()
+                })
+                .clone()
+        };
+
+        register_annotation_density(
+            &self.graph_data_cache,
+            &name,
+            set.clone(),
+            color_scheme,
+        )
+    }
+
+    /// Removes the data sources registered for the annotation set with
+    /// ID `set_id` by `register_annotation_density`
+    pub fn unregister_annotation_density(&self, set_id: AnnotationSetId) {
+        let Some(name) = self
+            .annotation_source_names
+            .blocking_write()
+            .remove(&set_id)
+        else {
+            return;
+        };
+
+        for suffix in ["density", "count"] {
+            let source = format!("{name}_{suffix}");
+            if let Ok(id) = self.graph_data_cache.data_source_id(&source) {
+                self.graph_data_cache.unregister_source(id);
+            }
+        }
+    }
+
+    /// Register a data source plugin, making it available to all
+    /// viewers. A source with the same name as an existing source
+    /// replaces it.
+    pub fn register_data_source(
+        &self,
+        source: impl DataSource,
+    ) -> DataSourceId {
+        let color_scheme = {
+            let colors = self.colors.blocking_read();
+            let name = source.color_scheme();
+
+            colors.get_color_scheme_id(name).unwrap_or_else(|| {
+                log::warn!(
+                    "Unknown color scheme `{name}` for data source `{}`, \
+                     using `spectral`",
+                    source.name()
+                );
+                colors.get_color_scheme_id("spectral").unwrap()
+            })
+        };
+
+        self.graph_data_cache
+            .register_data_source(Arc::new(source), color_scheme)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -125,83 +251,204 @@ impl App {
                 settings.register_widget("General", "Graph & Layout", ws);
             }
 
-            let graph_data_cache = Arc::new(GraphDataCache::init(&path_index));
-
             let colors = Arc::new(RwLock::new(ColorStore::init(state)));
 
-            let mut data_color_schemes = HashMap::default();
+            let spectral = colors
+                .blocking_read()
+                .get_color_scheme_id("spectral")
+                .unwrap();
 
-            {
-                let mut colors = colors.blocking_write();
+            let graph_data_cache = Arc::new(GraphDataCache::init(
+                &path_index,
+                &colors.blocking_read(),
+            ));
 
-                let mut add_entry = |data: &str, color: &str| {
-                    let scheme = colors.get_color_scheme_id(color).unwrap();
+            if let Some(mb) = args.data_cache_mb {
+                graph_data_cache.set_path_data_budget(mb << 20);
+            }
 
-                    colors.create_color_scheme_texture(state, color);
+            settings.register_widget(
+                "General",
+                "Data Sources",
+                Arc::new(RwLock::new(resource::DataSourcesWidget {
+                    cache: graph_data_cache.clone(),
+                })),
+            );
+
+            settings.register_widget(
+                "General",
+                "Data Cache",
+                Arc::new(RwLock::new(resource::GraphDataCacheWidget {
+                    cache: graph_data_cache.clone(),
+                })),
+            );
+
+            let gaf_filter = resource::gaf::GafFilter {
+                min_mapq: args.gaf_min_mapq.unwrap_or(0),
+                min_identity: args.gaf_min_identity.unwrap_or(0.0),
+            };
+
+            for gaf_path in args.gaf.iter() {
+                let name = gaf_path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy())
+                    .unwrap_or("gaf".into());
+                let key = format!("{name}_coverage");
+
+                resource::gaf::register_gaf_coverage(
+                    &graph_data_cache,
+                    &key,
+                    gaf_path,
+                    gaf_filter,
+                    args.gaf_per_base,
+                    spectral,
+                );
+            }
 
-                    data_color_schemes.insert(data.into(), scheme);
+            for (signal_path, target_path) in args.signals.iter() {
+                let file = match resource::linear::LinearSignalFile::from_path(
+                    signal_path,
+                ) {
+                    Ok(file) => file,
+                    Err(e) => {
+                        log::error!("{e:?}");
+                        continue;
+                    }
                 };
 
-                add_entry("depth", "spectral");
-                add_entry("strand", "black_red");
+                let name = signal_path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy())
+                    .unwrap_or("signal".into());
+
+                let key = match &file {
+                    resource::linear::LinearSignalFile::Bam(_) => {
+                        format!("{name}_coverage")
+                    }
+                    resource::linear::LinearSignalFile::BedGraph(_) => {
+                        name.to_string()
+                    }
+                };
+
+                // if a target path is given, everything in the file
+                // is mapped onto that path
+                let target_path = target_path.clone();
+                resource::linear::register_path_signal(
+                    &graph_data_cache,
+                    &key,
+                    file,
+                    move |ref_name| {
+                        target_path
+                            .clone()
+                            .unwrap_or_else(|| ref_name.to_string())
+                    },
+                    spectral,
+                );
+            }
+
+            // scripts come last, so that they can use all of the
+            // other data sources
+            for script_path in args.data_scripts.iter() {
+                if let Err(e) = resource::script::load_script_file(
+                    &graph_data_cache,
+                    script_path,
+                    spectral,
+                ) {
+                    log::error!("Error loading data script: {e:?}");
+                }
             }
 
             let mut annotations = AnnotationStore::default();
 
-            for annot_path in args.annotations.iter() {
-                if let Some(ext) = annot_path.extension() {
-                    let result = if ext == "bed" {
-                        AnnotationSet::from_bed(
-                            &path_index,
-                            None,
-                            |name| name.to_string(),
-                            annot_path,
-                        )
-                    } else if ext == "gff" {
-                        let attr = args
-                            .gff_attr
-                            .as_ref()
-                            .map(|s| s.as_str())
-                            .unwrap_or("Name");
-
-                        // TODO the name and record functions should be configurable
-                        AnnotationSet::from_gff(
-                            &path_index,
-                            None,
-                            |name| name.to_string(),
-                            // |name| format!("S288C.{name}"),
-                            // |name| format!("SGDref#1#{name}"),
-                            |record| {
-                                let attrs = record.attributes();
-                                let label = attrs.iter().find_map(|entry| {
-                                    (entry.key() == attr)
-                                        .then_some(entry.value())
-                                })?;
-
-                                Some(label.to_string())
-                            },
-                            annot_path,
-                        )
-                    } else {
-                        log::error!("Unknown annotation file extension `{ext:?}`, ignoring");
-                        continue;
-                    };
+            // the sample names of PanSN (`sample#haplotype#contig`) paths
+            let vcf_samples = args.vcf_pansn_samples.then(|| {
+                path_index
+                    .path_names
+                    .right_values()
+                    .filter_map(|name| name.split_once('#'))
+                    .map(|(sample, _)| sample.to_string())
+                    .collect::<HashSet<_>>()
+            });
+
+            let mut path_names = PathNameMap::default();
+            path_names.set_template(args.path_template.as_deref())?;
+            path_names.set_rewrite(
+                args.path_regex
+                    .as_deref()
+                    .map(|re| (re, args.path_replace.as_deref().unwrap_or(""))),
+            )?;
+            if let Some(table) = &args.path_renames {
+                path_names.renames = load_rename_table(table)?;
+            }
+
+            let annotation_options = LoadOptions {
+                path_names,
+                gff_attr: args.gff_attr.clone(),
+                gff_skip_unlabeled: args.gff_skip_unlabeled,
+                gff_types: args.gff_types.clone(),
+                vcf_samples,
+            };
+
+            let sources = args
+                .annotations
+                .iter()
+                .filter_map(|path| {
+                    let format = AnnotationFormat::from_path(path);
+                    if format.is_none() {
+                        log::error!(
+                            "Unknown annotation file extension {:?}, ignoring",
+                            path.as_os_str()
+                        );
+                    }
+                    Some((path, format?))
+                })
+                .chain(
+                    args.pangenome_bed
+                        .iter()
+                        .map(|path| (path, AnnotationFormat::PangenomeBed)),
+                )
+                .chain(
+                    args.node_lists
+                        .iter()
+                        .map(|path| (path, AnnotationFormat::NodeList)),
+                );
+
+            for (path, format) in sources {
+                let source = AnnotationSource::new(path, format);
+
+                match AnnotationSet::load(
+                    &path_index,
+                    &annotation_options,
+                    source,
+                ) {
+                    Ok(set) => {
+                        log::warn!(
+                            "loaded annotation set with {} annotations",
+                            set.annotations.len()
+                        );
 
-                    match result {
-                        Ok(set) => {
+                        if args.project_annotations {
+                            let mut projected =
+                                set.project_to_paths(&path_index);
                             log::warn!(
-                                "loaded annotation set with {} annotations",
-                                set.annotations.len()
+                                "projected to {} annotations on other paths",
+                                projected.annotations.len()
                             );
+                            let set_id = annotations.insert_set(set);
 
+                            if !projected.annotations.is_empty() {
+                                projected.projected_from = Some(set_id);
+                                annotations.insert_set(projected);
+                            }
+                        } else {
                             annotations.insert_set(set);
                         }
-                        Err(e) => {
-                            log::error!(
-                                "Error loading annotation file {:?}: {e:?}",
-                                annot_path.as_os_str()
-                            );
-                        }
+                    }
+                    Err(e) => {
+                        log::error!(
+                            "Error loading annotation file {:?}: {e:?}",
+                            path.as_os_str()
+                        );
                     }
                 }
             }
@@ -209,23 +456,55 @@ impl App {
             let annotations: Arc<RwLock<AnnotationStore>> =
                 Arc::new(RwLock::new(annotations));
 
+            let annotation_options = Arc::new(RwLock::new(annotation_options));
+
             SharedState {
                 graph: path_index,
 
                 // shared: Arc::new(RwLock::new(AnyArcMap::default())),
                 graph_data_cache,
                 annotations,
+                annotation_options,
+                annotation_source_names: Default::default(),
 
                 colors,
 
-                data_color_schemes: Arc::new(data_color_schemes.into()),
+                data_color_schemes: Arc::new(RwLock::new(HashMap::default())),
 
                 workspace,
 
                 app_msg_send,
+
+                settings_ctx: settings.ui_context(),
             }
         };
 
+        for (set_id, set) in
+            shared.annotations.blocking_read().annotation_sets.iter()
+        {
+            shared.register_annotation_density(*set_id, set);
+        }
+
+        settings.register_widget(
+            "Annotations",
+            "Files",
+            Arc::new(RwLock::new(AnnotationFilesWidget::new(shared.clone()))),
+        );
+
+        settings.register_widget(
+            "Annotations",
+            "Path Names",
+            Arc::new(RwLock::new(PathNamesWidget::new(shared.clone()))),
+        );
+
+        settings.register_widget(
+            "General",
+            "Scripted Data Sources",
+            Arc::new(RwLock::new(resource::script::ScriptSourceWidget::new(
+                shared.clone(),
+            ))),
+        );
+
         let context_state = ContextState::default();
 
         let context_inspector = ContextInspector::with_default_widgets(&shared);
@@ -477,6 +756,8 @@ impl App {
                             self.settings.show(app.egui.ctx());
                         }
 
+                        self.settings.show_window_file_dialogs(app.egui.ctx());
+
                         if context_inspector_tgts.contains(app_type) {
                             egui::Window::new("Context Inspector")
                                 .default_pos([100.0, 100.0])
@@ -580,7 +861,43 @@ pub struct Args {
 
     pub annotations: Vec<PathBuf>,
     pub gff_attr: Option<String>,
+    /// Skip GFF3 records that don't have the label attribute
+    pub gff_skip_unlabeled: bool,
+    /// If set, only GFF3 records of these types are loaded
+    pub gff_types: Option<Vec<String>>,
+    /// Only load the VCF variants carried by the samples that are
+    /// also PanSN path name prefixes
+    pub vcf_pansn_samples: bool,
+    /// Also project each annotation set onto all other paths
+    pub project_annotations: bool,
+    /// BED files with ranges in pangenome coordinates
+    pub pangenome_bed: Vec<PathBuf>,
+    /// Files listing annotated sets of nodes
+    pub node_lists: Vec<PathBuf>,
+    /// Template for the path names of annotation file sequences,
+    /// with a `{name}` placeholder
+    pub path_template: Option<String>,
+    /// Two-column table of sequence names and path names
+    pub path_renames: Option<PathBuf>,
+    /// Regex rewriting sequence names into path names, with the
+    /// replacement (empty by default)
+    pub path_regex: Option<String>,
+    pub path_replace: Option<String>,
     // pub annotations: Option<PathBuf>,
+    pub gaf: Vec<PathBuf>,
+    pub gaf_min_mapq: Option<u8>,
+    pub gaf_min_identity: Option<f32>,
+    pub gaf_per_base: bool,
+
+    /// Linear signal files (BAM or bedGraph), optionally with the
+    /// name of the path to project the signal onto
+    pub signals: Vec<(PathBuf, Option<String>)>,
+
+    /// Memory budget for cached path data, in megabytes
+    pub data_cache_mb: Option<usize>,
+
+    /// Files defining scripted data sources
+    pub data_scripts: Vec<PathBuf>,
 }
 
 pub fn parse_args() -> std::result::Result<Args, pico_args::Error> {
@@ -588,19 +905,39 @@ pub fn parse_args() -> std::result::Result<Args, pico_args::Error> {
 
     // let init_range = pargs.opt_value_from_fn("--range", parse_range)?;
 
-    let mut annotations = Vec::new();
+    let mut annotations = pargs.values_from_os_str("--bed", parse_path)?;
+    annotations.extend(pargs.values_from_os_str("--gff", parse_path)?);
 
-    let bed = pargs.opt_value_from_os_str("--bed", parse_path)?;
-    if let Some(bed) = bed {
-        annotations.push(bed);
-    }
+    let gff_attr = pargs.opt_value_from_str("--gff-attr")?;
+    let gff_skip_unlabeled = pargs.contains("--gff-skip-unlabeled");
+    let gff_types = pargs.opt_value_from_fn("--gff-types", parse_list)?;
 
-    let gff = pargs.opt_value_from_os_str("--gff", parse_path)?;
-    if let Some(gff) = gff {
-        annotations.push(gff);
-    }
+    annotations.extend(pargs.values_from_os_str("--vcf", parse_path)?);
 
-    let gff_attr = pargs.opt_value_from_str("--gff-attr")?;
+    let vcf_pansn_samples = pargs.contains("--vcf-pansn-samples");
+
+    let project_annotations = pargs.contains("--project-annotations");
+
+    let pangenome_bed =
+        pargs.values_from_os_str("--pangenome-bed", parse_path)?;
+    let node_lists = pargs.values_from_os_str("--nodes", parse_path)?;
+
+    let path_template = pargs.opt_value_from_str("--path-template")?;
+    let path_renames =
+        pargs.opt_value_from_os_str("--path-renames", parse_path)?;
+    let path_regex = pargs.opt_value_from_str("--path-regex")?;
+    let path_replace = pargs.opt_value_from_str("--path-replace")?;
+
+    let gaf = pargs.values_from_os_str("--gaf", parse_path)?;
+    let gaf_min_mapq = pargs.opt_value_from_str("--gaf-min-mapq")?;
+    let gaf_min_identity = pargs.opt_value_from_str("--gaf-min-identity")?;
+    let gaf_per_base = pargs.contains("--gaf-per-base");
+
+    let signals = pargs.values_from_os_str("--signal", parse_signal_arg)?;
+
+    let data_cache_mb = pargs.opt_value_from_str("--data-cache-mb")?;
+
+    let data_scripts = pargs.values_from_os_str("--data-script", parse_path)?;
 
     let args = Args {
         gfa: pargs.free_from_os_str(parse_path)?,
@@ -608,16 +945,55 @@ pub fn parse_args() -> std::result::Result<Args, pico_args::Error> {
 
         annotations,
         gff_attr,
+        gff_skip_unlabeled,
+        gff_types,
+        vcf_pansn_samples,
+        project_annotations,
+        pangenome_bed,
+        node_lists,
+        path_template,
+        path_renames,
+        path_regex,
+        path_replace,
         // init_range,
+        gaf,
+        gaf_min_mapq,
+        gaf_min_identity,
+        gaf_per_base,
+        signals,
+        data_cache_mb,
+        data_scripts,
     };
 
     Ok(args)
 }
 
+// comma-separated list, e.g. `gene,mRNA`
+fn parse_list(s: &str) -> Result<Vec<String>, &'static str> {
+    Ok(s.split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect())
+}
+
 fn parse_path(s: &std::ffi::OsStr) -> Result<std::path::PathBuf, &'static str> {
     Ok(s.into())
 }
 
+// `FILE` or `FILE=PATH_NAME`
+fn parse_signal_arg(
+    s: &std::ffi::OsStr,
+) -> Result<(std::path::PathBuf, Option<String>), &'static str> {
+    let s = s.to_str().ok_or("signal argument must be valid UTF-8")?;
+
+    if let Some((file, path_name)) = s.split_once('=') {
+        Ok((file.into(), Some(path_name.to_string())))
+    } else {
+        Ok((s.into(), None))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum AppMsg {
     InitViewer1D,
@@ -4,6 +4,29 @@ use egui::epaint::ahash::HashMap;
 use tokio::sync::RwLock;
 use waragraph_core::graph::{sampling::PathData, Node, PathId, PathIndex};
 
+use crate::color::{ColorSchemeId, ColorStore};
+
+use super::settings_menu::{
+    SettingsUiContext, SettingsUiResponse, SettingsWidget,
+};
+
+use self::{
+    lru::{ByteSize, LruCache},
+    source::DataSourceRegistry,
+};
+
+pub mod gaf;
+pub mod linear;
+pub mod loops;
+mod lru;
+pub mod position;
+pub mod script;
+pub mod sequence;
+mod source;
+
+pub use lru::CacheStats;
+pub use source::{DataSource, DataSourceId, DataSourceMeta, ValueDomain};
+
 #[derive(Default)]
 pub struct AnyArcMap {
     values: HashMap<(std::any::TypeId, u64), Box<dyn std::any::Any>>,
@@ -58,40 +81,207 @@ pub struct GraphPathData<T, Stats> {
     pub path_stats: Stats,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+impl<T, Stats> ByteSize for GraphPathData<T, Stats> {
+    fn byte_size(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.path_data.capacity() * std::mem::size_of::<T>()
+    }
+}
+
+/// Summary statistics for a set of values, ignoring NaNs
+#[derive(Debug, Clone, PartialEq)]
 pub struct FStats {
     pub min: f32,
     pub max: f32,
-    // mean: Option<f32>,
-    // var: Option<f32>,
-    // std_dev: f32,
+    pub mean: f32,
+    pub std_dev: f32,
+    pub count: usize,
+
+    /// The percentiles and histogram of the values, only computed for
+    /// graph-level data, see `from_values_with_distribution`
+    distribution: Option<Arc<Distribution>>,
+}
+
+/// The distribution of a set of values, used to pick the value ranges
+/// of color maps
+#[derive(Debug, PartialEq)]
+pub struct Distribution {
+    /// The values at each percentile 0, 1, .., 100
+    percentiles: [f32; 101],
+
+    /// Value counts in `FStats::HISTOGRAM_BINS` equal-width bins over
+    /// `min..=max`
+    pub histogram: [u32; FStats::HISTOGRAM_BINS],
 }
 
 impl FStats {
+    pub const HISTOGRAM_BINS: usize = 64;
+
+    /// The quantiles used for the default value range of a color map;
+    /// skewed data, such as depth, would otherwise map almost all
+    /// values to the bottom of the color scheme
+    pub const AUTO_RANGE_QUANTILES: [f32; 2] = [0.0, 0.99];
+
+    /// The min, max, mean and standard deviation of the items, without
+    /// the distribution
     pub fn from_items(items: impl Iterator<Item = f32>) -> Self {
         let mut result = Self {
             min: std::f32::INFINITY,
             max: std::f32::NEG_INFINITY,
-            // var: 0.0,
-            // std_dev: 0.0,
+            mean: 0.0,
+            std_dev: 0.0,
+            count: 0,
+            distribution: None,
         };
 
-        // let mut sum = 0.0;
-        // let mut count = 0.0;
+        // accumulate in f64, since there can be millions of values
+        let mut sum = 0f64;
+        let mut sum_sq = 0f64;
 
-        for item in items {
-            result.min = result.min.min(item);
-            result.max = result.max.max(item);
-            // result.sum += item;
+        for v in items.filter(|v| !v.is_nan()) {
+            result.min = result.min.min(v);
+            result.max = result.max.max(v);
+            result.count += 1;
+            sum += v as f64;
+            sum_sq += (v as f64).powi(2);
+        }
 
-            // sum += item;
-            // count += 1.0;
+        if result.count > 0 {
+            let n = result.count as f64;
+            let mean = sum / n;
+            let var = (sum_sq / n - mean * mean).max(0.0);
+
+            result.mean = mean as f32;
+            result.std_dev = var.sqrt() as f32;
+        }
+
+        result
+    }
+
+    /// Like `from_items`, also computing the percentiles and histogram
+    /// of the values
+    pub fn from_values_with_distribution(values: &[f32]) -> Self {
+        let mut result = Self::from_items(values.iter().copied());
+
+        if result.is_empty() {
+            return result;
         }
 
-        // result.mean = sum / count;
+        let mut sorted = values
+            .iter()
+            .copied()
+            .filter(|v| !v.is_nan())
+            .collect::<Vec<_>>();
+
+        let count = sorted.len();
+        let mut percentile_ixs = (0..=100)
+            .map(|p| (p * (count - 1)) / 100)
+            .collect::<Vec<_>>();
+        percentile_ixs.dedup();
+
+        // only the elements at the percentiles are put in place, which
+        // is much cheaper than sorting all the values
+        select_sorted(&mut sorted, &percentile_ixs, 0);
+
+        let mut percentiles = [0.0; 101];
+        for (p, pv) in percentiles.iter_mut().enumerate() {
+            *pv = sorted[(p * (count - 1)) / 100];
+        }
+
+        let mut histogram = [0; Self::HISTOGRAM_BINS];
+        let range = result.max - result.min;
+        for &v in sorted.iter() {
+            let bin = if range > 0.0 {
+                let t = (v - result.min) / range;
+                ((t * Self::HISTOGRAM_BINS as f32) as usize)
+                    .min(Self::HISTOGRAM_BINS - 1)
+            } else {
+                0
+            };
+            histogram[bin] += 1;
+        }
+
+        result.distribution = Some(Arc::new(Distribution {
+            percentiles,
+            histogram,
+        }));
 
         result
     }
+
+    /// The histogram of the values, if the distribution was computed
+    pub fn histogram(&self) -> Option<&[u32; Self::HISTOGRAM_BINS]> {
+        self.distribution.as_ref().map(|d| &d.histogram)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// The value at quantile `q` (clamped to [0, 1]), interpolated
+    /// between percentiles, or between the min and max if the
+    /// distribution wasn't computed
+    pub fn quantile(&self, q: f32) -> f32 {
+        if self.is_empty() {
+            return 0.0;
+        }
+
+        let q = q.clamp(0.0, 1.0);
+
+        let Some(dist) = self.distribution.as_ref() else {
+            return self.min + (self.max - self.min) * q;
+        };
+
+        let p = q * 100.0;
+        let lo = p.floor() as usize;
+        let hi = p.ceil() as usize;
+        let t = p - lo as f32;
+
+        let a = dist.percentiles[lo];
+        let b = dist.percentiles[hi];
+        a + (b - a) * t
+    }
+
+    /// The value range between the quantiles `lo` and `hi`, falling
+    /// back to [0, 1] if there's no data, and to the full range if
+    /// the quantiles are equal
+    pub fn quantile_range(&self, [lo, hi]: [f32; 2]) -> [f32; 2] {
+        if self.is_empty() {
+            return [0.0, 1.0];
+        }
+
+        let range = [self.quantile(lo), self.quantile(hi)];
+
+        if range[0] < range[1] {
+            range
+        } else if self.min < self.max {
+            [self.min, self.max]
+        } else {
+            [self.min, self.min + 1.0]
+        }
+    }
+
+    /// The default value range to use for a color map
+    pub fn auto_range(&self) -> [f32; 2] {
+        self.quantile_range(Self::AUTO_RANGE_QUANTILES)
+    }
+}
+
+/// Partially sorts `values` so that each of the (sorted, unique)
+/// indices `ixs`, minus `offset`, holds the value it would hold if
+/// all of `values` were sorted
+fn select_sorted(values: &mut [f32], ixs: &[usize], offset: usize) {
+    if ixs.is_empty() || values.is_empty() {
+        return;
+    }
+
+    let mid = ixs.len() / 2;
+    let ix = ixs[mid] - offset;
+
+    let (left, _, right) = values.select_nth_unstable_by(ix, f32::total_cmp);
+
+    select_sorted(left, &ixs[..mid], offset);
+    select_sorted(right, &ixs[mid + 1..], offset + ix + 1);
 }
 
 // impl<T, S> PathData<T> for GraphPathData<T, S> {
@@ -107,31 +297,117 @@ pub type GraphDataSourceFn<T> =
 pub type PathDataSourceFn<T> =
     Arc<dyn Fn(PathId) -> anyhow::Result<Vec<T>> + Send + Sync + 'static>;
 
+#[derive(Default)]
 pub struct GraphDataSources {
-    graph_f32: HashMap<String, GraphDataSourceFn<f32>>,
-    path_f32: HashMap<String, PathDataSourceFn<f32>>,
+    registry: DataSourceRegistry,
+
+    graph_f32: HashMap<DataSourceId, GraphDataSourceFn<f32>>,
+    path_f32: HashMap<DataSourceId, PathDataSourceFn<f32>>,
+
+    // the sources the data of each source is computed from, see
+    // `GraphDataCache::set_source_inputs`
+    inputs: HashMap<DataSourceId, Vec<DataSourceId>>,
 }
 
 impl GraphDataSources {
-    pub fn tmp_init(graph: &Arc<PathIndex>) -> Self {
-        let mut graph_f32: HashMap<String, GraphDataSourceFn<f32>> =
-            HashMap::default();
-        let mut path_f32: HashMap<String, PathDataSourceFn<f32>> =
-            HashMap::default();
+    /// Registers a `DataSource` plugin, replacing the source with the
+    /// same name, if any
+    fn insert_data_source(
+        &mut self,
+        graph: &Arc<PathIndex>,
+        source: Arc<dyn DataSource>,
+        color_scheme: ColorSchemeId,
+    ) -> DataSourceId {
+        let id = self.registry.register(DataSourceMeta {
+            name: source.name().to_string(),
+            description: source.description().to_string(),
+            unit: source.unit().map(String::from),
+            value_domain: source.value_domain(),
+            default_color_scheme: color_scheme,
+        });
+
+        {
+            let graph = graph.clone();
+            let source = source.clone();
+            let ctor = move || source.graph_f32(&graph);
+            self.graph_f32.insert(id, Arc::new(ctor));
+        }
+
+        if source.has_path_data() {
+            let graph = graph.clone();
+            let ctor = move |path: PathId| source.path_f32(&graph, path);
+            self.path_f32.insert(id, Arc::new(ctor));
+        } else {
+            self.path_f32.remove(&id);
+        }
+
+        id
+    }
+
+    fn remove_source(&mut self, id: DataSourceId) {
+        self.registry.unregister(id);
+        self.graph_f32.remove(&id);
+        self.path_f32.remove(&id);
+        self.inputs.remove(&id);
+    }
+
+    /// `id` and the sources computed from it, directly or through
+    /// other sources
+    fn with_dependents(&self, id: DataSourceId) -> Vec<DataSourceId> {
+        let mut ids = vec![id];
+        let mut ix = 0;
+
+        while let Some(&input) = ids.get(ix) {
+            for (&source, inputs) in self.inputs.iter() {
+                if inputs.contains(&input) && !ids.contains(&source) {
+                    ids.push(source);
+                }
+            }
+            ix += 1;
+        }
+
+        ids
+    }
+
+    pub fn tmp_init(graph: &Arc<PathIndex>, colors: &ColorStore) -> Self {
+        let mut sources = Self {
+            registry: DataSourceRegistry::default(),
+            graph_f32: HashMap::default(),
+            path_f32: HashMap::default(),
+            inputs: HashMap::default(),
+        };
+
+        let spectral = colors.get_color_scheme_id("spectral").unwrap();
+        let black_red = colors.get_color_scheme_id("black_red").unwrap();
+        let viridis = colors.get_color_scheme_id("viridis").unwrap();
+        let loop_classes = colors.get_color_scheme_id("loop_classes").unwrap();
 
         // graph node ids
         {
-            let name = "node_id".to_string();
+            let id = sources.registry.register(DataSourceMeta {
+                name: "node_id".to_string(),
+                description: "Node index".to_string(),
+                unit: None,
+                value_domain: ValueDomain::Categorical,
+                default_color_scheme: spectral,
+            });
             let graph = graph.clone();
             let ctor =
                 move || Ok((0..graph.node_count).map(|i| i as f32).collect());
 
-            graph_f32.insert(name, Arc::new(ctor));
+            sources.graph_f32.insert(id, Arc::new(ctor));
         }
 
+        let depth = sources.registry.register(DataSourceMeta {
+            name: "depth".to_string(),
+            description: "Number of path steps on each node".to_string(),
+            unit: Some("steps".to_string()),
+            value_domain: ValueDomain::Count,
+            default_color_scheme: spectral,
+        });
+
         // graph path depth
         {
-            let name = "depth".to_string();
             let graph = graph.clone();
 
             let ctor = move || {
@@ -146,12 +422,11 @@ impl GraphDataSources {
                 Ok(node_data)
             };
 
-            graph_f32.insert(name, Arc::new(ctor));
+            sources.graph_f32.insert(depth, Arc::new(ctor));
         }
 
         // path depth
         {
-            let name = "depth".to_string();
             let graph = graph.clone();
             let ctor = move |path: PathId| {
                 let mut path_data: BTreeMap<Node, f32> = BTreeMap::default();
@@ -163,12 +438,20 @@ impl GraphDataSources {
                 Ok(path_data)
             };
 
-            path_f32.insert(name, Arc::new(ctor));
+            sources.path_f32.insert(depth, Arc::new(ctor));
         }
 
         // path strand
         {
-            let name = "strand".to_string();
+            let id = sources.registry.register(DataSourceMeta {
+                name: "strand".to_string(),
+                description: "Fraction of the path's steps on each node \
+                              that are in reverse orientation"
+                    .to_string(),
+                unit: None,
+                value_domain: ValueDomain::Fraction,
+                default_color_scheme: black_red,
+            });
             let graph = graph.clone();
             let ctor = move |path: PathId| {
                 let path_steps = &graph.path_steps[path.ix()];
@@ -194,31 +477,150 @@ impl GraphDataSources {
                 Ok(path_data)
             };
 
-            path_f32.insert(name, Arc::new(ctor));
+            sources.path_f32.insert(id, Arc::new(ctor));
         }
 
-        Self {
-            graph_f32,
-            path_f32,
+        sequence::register_sequence_sources(
+            &mut sources,
+            graph,
+            spectral,
+            black_red,
+        );
+
+        position::register_position_sources(&mut sources, graph, viridis);
+
+        loops::register_loop_sources(
+            &mut sources,
+            graph,
+            loop_classes,
+            spectral,
+        );
+
+        sources
+    }
+}
+
+/// Fills `out` with the nodes (and overlap lengths, in bp) that the
+/// range `range` on the path `path` covers. A node is emitted once
+/// for every step it overlaps.
+pub(crate) fn path_range_node_overlaps(
+    graph: &PathIndex,
+    path: PathId,
+    range: std::ops::Range<u64>,
+    out: &mut Vec<(Node, u64)>,
+) {
+    let Some(steps) = graph.path_step_range_iter(path, range.clone()) else {
+        return;
+    };
+    let offsets = &graph.path_step_offsets[path.ix()];
+
+    for (step_ix, step) in steps {
+        let Some(offset) = offsets.select(step_ix as u64) else {
+            continue;
+        };
+
+        let node = step.node();
+        let len = graph.node_length(node).0;
+
+        let start = offset.max(range.start);
+        let end = (offset + len).min(range.end);
+
+        if start < end {
+            out.push((node, end - start));
         }
     }
 }
 
+/// Project graph-level node values onto a path, using the same order
+/// as the other path data sources (the path's unique nodes, ordered
+/// by node ID)
+pub(crate) fn project_node_values(
+    graph: &PathIndex,
+    path: PathId,
+    node_values: &[f32],
+) -> Vec<f32> {
+    graph.path_node_sets[path.ix()]
+        .iter()
+        .map(|node| node_values[node as usize])
+        .collect()
+}
+
+/// Loads that are currently in progress, so that concurrent requests
+/// for the same data can wait on the same load
+type InFlight<K, V> =
+    std::sync::Mutex<HashMap<K, Arc<tokio::sync::OnceCell<V>>>>;
+
+/// Runs `load` for `key`, unless a load for `key` is already in
+/// progress, in which case its result is used instead. If that load
+/// fails, or is cancelled, one of the waiting callers runs its own
+/// `load` in its place.
+///
+/// `load` must store its result in the cache before returning, as the
+/// in-flight entry is removed once it's done.
+async fn coalesce_load<K, V, F, Fut>(
+    in_flight: &InFlight<K, V>,
+    key: K,
+    load: F,
+) -> anyhow::Result<V>
+where
+    K: Clone + Eq + std::hash::Hash,
+    V: Clone,
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = anyhow::Result<V>>,
+{
+    let cell = in_flight
+        .lock()
+        .unwrap()
+        .entry(key.clone())
+        .or_default()
+        .clone();
+
+    let result = cell.get_or_try_init(load).await.cloned();
+
+    let mut in_flight = in_flight.lock().unwrap();
+    if in_flight
+        .get(&key)
+        .map(|current| Arc::ptr_eq(current, &cell))
+        .unwrap_or(false)
+    {
+        in_flight.remove(&key);
+    }
+
+    result
+}
+
 pub struct GraphDataCache {
     graph: Arc<PathIndex>,
-    graph_f32: RwLock<HashMap<String, Arc<GraphData<f32, FStats>>>>,
-    path_f32:
-        RwLock<HashMap<(String, PathId), Arc<GraphPathData<f32, FStats>>>>,
-
-    sources: GraphDataSources,
+    graph_f32: RwLock<HashMap<DataSourceId, Arc<GraphData<f32, FStats>>>>,
+    // path data is evicted once it exceeds the memory budget, since
+    // there can be one entry per path and data source
+    path_f32: RwLock<
+        LruCache<(DataSourceId, PathId), Arc<GraphPathData<f32, FStats>>>,
+    >,
+
+    graph_f32_in_flight: InFlight<DataSourceId, Arc<GraphData<f32, FStats>>>,
+    path_f32_in_flight:
+        InFlight<(DataSourceId, PathId), Arc<GraphPathData<f32, FStats>>>,
+
+    // sources can be registered after the cache has been created
+    // (e.g. by file loaders), so they live behind a (sync) lock
+    sources: std::sync::RwLock<GraphDataSources>,
 }
 
 impl GraphDataCache {
-    pub fn init(graph: &Arc<PathIndex>) -> Self {
-        let sources = GraphDataSources::tmp_init(graph);
+    /// The default memory budget for cached path data, in bytes
+    pub const DEFAULT_PATH_DATA_BUDGET: usize = 1 << 30;
+
+    pub fn init(graph: &Arc<PathIndex>, colors: &ColorStore) -> Self {
+        Self::with_sources(graph, GraphDataSources::tmp_init(graph, colors))
+    }
+
+    fn with_sources(graph: &Arc<PathIndex>, sources: GraphDataSources) -> Self {
+        let sources = sources.into();
 
         let graph_f32 = RwLock::new(HashMap::default());
-        let path_f32 = RwLock::new(HashMap::default());
+        let path_f32 =
+            RwLock::new(LruCache::new(Self::DEFAULT_PATH_DATA_BUDGET));
 
         let graph = graph.clone();
 
@@ -226,94 +628,362 @@ impl GraphDataCache {
             graph,
             graph_f32,
             path_f32,
+            graph_f32_in_flight: Default::default(),
+            path_f32_in_flight: Default::default(),
             sources,
         }
     }
 
-    pub fn graph_data_source_names<'a>(
-        &'a self,
-    ) -> impl Iterator<Item = &'a str> + 'a {
-        self.sources.graph_f32.keys().map(|s| s.as_str())
+    /// Set the memory budget for cached path data, in bytes, evicting
+    /// the least recently used data if it's exceeded
+    pub fn set_path_data_budget(&self, budget_bytes: usize) {
+        self.path_f32.blocking_write().set_budget(budget_bytes);
+    }
+
+    /// The graph the cached data belongs to
+    pub fn graph(&self) -> &Arc<PathIndex> {
+        &self.graph
+    }
+
+    pub fn path_data_stats(&self) -> CacheStats {
+        self.path_f32.blocking_read().stats()
+    }
+
+    pub fn debug_ui(&self, ui: &mut egui::Ui) {
+        let stats = self.path_data_stats();
+
+        let mb = |bytes: usize| bytes as f64 / (1024.0 * 1024.0);
+
+        egui::Grid::new("GraphDataCache-Stats").show(ui, |ui| {
+            ui.label("Path data entries");
+            ui.label(stats.entries.to_string());
+            ui.end_row();
+
+            ui.label("Memory used");
+            ui.label(format!(
+                "{:.1} / {:.1} MB",
+                mb(stats.used_bytes),
+                mb(stats.budget_bytes)
+            ));
+            ui.end_row();
+
+            ui.label("Hits / misses");
+            ui.label(format!(
+                "{} / {} ({:.1}% hits)",
+                stats.hits,
+                stats.misses,
+                100.0 * stats.hit_rate()
+            ));
+            ui.end_row();
+
+            ui.label("Evictions");
+            ui.label(stats.evictions.to_string());
+            ui.end_row();
+
+            ui.label("Graph data entries");
+            ui.label(self.graph_f32.blocking_read().len().to_string());
+            ui.end_row();
+        });
+    }
+
+    /// Look up a registered data source by name
+    pub fn data_source_id(&self, name: &str) -> anyhow::Result<DataSourceId> {
+        self.sources
+            .read()
+            .unwrap()
+            .registry
+            .id(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown data source `{name}`"))
+    }
+
+    pub fn data_source_meta(&self, id: DataSourceId) -> Arc<DataSourceMeta> {
+        self.sources.read().unwrap().registry.meta(id).clone()
+    }
+
+    /// The IDs of all registered data sources, sorted by name
+    pub fn data_sources(&self) -> Vec<DataSourceId> {
+        let sources = self.sources.read().unwrap();
+        let mut ids = sources.registry.ids().collect::<Vec<_>>();
+        ids.sort_by_key(|id| &sources.registry.meta(*id).name);
+        ids
+    }
+
+    /// The IDs of all data sources with graph-level data, sorted by name
+    pub fn graph_data_sources(&self) -> Vec<DataSourceId> {
+        let sources = self.sources.read().unwrap();
+        let mut ids = sources.graph_f32.keys().copied().collect::<Vec<_>>();
+        ids.sort_by_key(|id| &sources.registry.meta(*id).name);
+        ids
+    }
+
+    /// The IDs of all data sources with path-level data, sorted by name
+    pub fn path_data_sources(&self) -> Vec<DataSourceId> {
+        let sources = self.sources.read().unwrap();
+        let mut ids = sources.path_f32.keys().copied().collect::<Vec<_>>();
+        ids.sort_by_key(|id| &sources.registry.meta(*id).name);
+        ids
+    }
+
+    pub fn has_graph_data(&self, id: DataSourceId) -> bool {
+        self.sources.read().unwrap().graph_f32.contains_key(&id)
+    }
+
+    pub fn has_path_data(&self, id: DataSourceId) -> bool {
+        self.sources.read().unwrap().path_f32.contains_key(&id)
+    }
+
+    /// Register a data source that doesn't provide any data through
+    /// the cache, e.g. one that's sampled using a custom sampler, so
+    /// that it can be referred to using a `DataSourceId`.
+    pub fn register_source(&self, meta: DataSourceMeta) -> DataSourceId {
+        self.sources.write().unwrap().registry.register(meta)
+    }
+
+    /// Register a graph-level (one value per node) data source.
+    ///
+    /// If a source with the same name already exists, it's replaced,
+    /// and any data cached for it is dropped.
+    pub fn register_graph_f32(
+        &self,
+        meta: DataSourceMeta,
+        source: GraphDataSourceFn<f32>,
+    ) -> DataSourceId {
+        let id = {
+            let mut sources = self.sources.write().unwrap();
+            let id = sources.registry.register(meta);
+            sources.graph_f32.insert(id, source);
+            id
+        };
+        self.drop_cached_data(id);
+        id
+    }
+
+    /// Register a path-level (one value per unique node on the path,
+    /// ordered by node ID) data source.
+    ///
+    /// If a source with the same name already exists, it's replaced,
+    /// and any data cached for it is dropped.
+    pub fn register_path_f32(
+        &self,
+        meta: DataSourceMeta,
+        source: PathDataSourceFn<f32>,
+    ) -> DataSourceId {
+        let id = {
+            let mut sources = self.sources.write().unwrap();
+            let id = sources.registry.register(meta);
+            sources.path_f32.insert(id, source);
+            id
+        };
+        self.drop_cached_data(id);
+        id
+    }
+
+    /// Register a `DataSource` plugin, with `color_scheme` as its
+    /// default color scheme.
+    ///
+    /// If a source with the same name already exists, it's replaced,
+    /// and any data cached for it is dropped.
+    pub fn register_data_source(
+        &self,
+        source: Arc<dyn DataSource>,
+        color_scheme: ColorSchemeId,
+    ) -> DataSourceId {
+        let id = self.sources.write().unwrap().insert_data_source(
+            &self.graph,
+            source,
+            color_scheme,
+        );
+        self.drop_cached_data(id);
+        id
+    }
+
+    /// Removes a data source, and any data cached for it. The ID can
+    /// still be used to get the source's metadata, but no longer
+    /// provides any data.
+    pub fn unregister_source(&self, id: DataSourceId) {
+        self.drop_cached_data(id);
+        self.sources.write().unwrap().remove_source(id);
+    }
+
+    /// Records that the data of `id` is computed from the data of the
+    /// sources `inputs`, so that it's dropped from the cache whenever
+    /// one of the inputs is replaced or removed
+    pub fn set_source_inputs(
+        &self,
+        id: DataSourceId,
+        inputs: Vec<DataSourceId>,
+    ) {
+        self.sources.write().unwrap().inputs.insert(id, inputs);
+    }
+
+    /// Whether the data of `id` is computed from the data of `other`,
+    /// directly or through other sources, see `set_source_inputs`
+    pub fn depends_on(&self, id: DataSourceId, other: DataSourceId) -> bool {
+        id != other
+            && self
+                .sources
+                .read()
+                .unwrap()
+                .with_dependents(other)
+                .contains(&id)
+    }
+
+    // drops the data cached for `id`, and for the sources computed
+    // from it, which would otherwise be out of date
+    fn drop_cached_data(&self, id: DataSourceId) {
+        let ids = self.sources.read().unwrap().with_dependents(id);
+
+        let mut graph_f32 = self.graph_f32.blocking_write();
+        for id in &ids {
+            graph_f32.remove(id);
+        }
+
+        self.path_f32
+            .blocking_write()
+            .retain(|(k, _)| !ids.contains(k));
+    }
+
+    fn graph_f32_source(
+        &self,
+        id: DataSourceId,
+    ) -> Option<GraphDataSourceFn<f32>> {
+        self.sources.read().unwrap().graph_f32.get(&id).cloned()
     }
 
-    pub fn path_data_source_names<'a>(
-        &'a self,
-    ) -> impl Iterator<Item = &'a str> + 'a {
-        self.sources.path_f32.keys().map(|s| s.as_str())
+    fn path_f32_source(
+        &self,
+        id: DataSourceId,
+    ) -> Option<PathDataSourceFn<f32>> {
+        self.sources.read().unwrap().path_f32.get(&id).cloned()
     }
 
     pub fn fetch_graph_data_blocking(
         &self,
-        key: &str,
+        key: DataSourceId,
     ) -> Option<Arc<GraphData<f32, FStats>>> {
-        if let Some(data) = self.graph_f32.blocking_read().get(key) {
+        if let Some(data) = self.graph_f32.blocking_read().get(&key) {
             return Some(data.clone());
         }
 
-        let source = self.sources.graph_f32.get(key)?;
+        let source = self.graph_f32_source(key)?;
 
-        let node_data = source().unwrap();
+        let node_data = match source() {
+            Ok(data) => data,
+            Err(e) => {
+                let name = &self.data_source_meta(key).name;
+                log::error!("Error loading graph data `{name}`: {e:?}");
+                return None;
+            }
+        };
 
-        let stats = FStats::from_items(node_data.iter().copied());
+        let stats = FStats::from_values_with_distribution(&node_data);
 
         let data = Arc::new(GraphData { node_data, stats });
 
-        self.graph_f32
-            .blocking_write()
-            .insert(key.to_string(), data.clone());
+        self.graph_f32.blocking_write().insert(key, data.clone());
 
         Some(data)
     }
 
-    pub async fn fetch_path_data(
+    /// Fetch the graph-level data for `key`, loading it on a blocking
+    /// thread if it's not already cached. Concurrent requests for the
+    /// same data share a single load.
+    pub async fn fetch_graph_data(
         &self,
-        data_key: &str,
-        path: PathId,
-    ) -> anyhow::Result<Arc<GraphPathData<f32, FStats>>> {
-        {
-            let data_key = data_key.to_string();
-            let path_data = self.path_f32.read().await;
-            if let Some(data) = path_data.get(&(data_key, path)) {
+        key: DataSourceId,
+    ) -> anyhow::Result<Arc<GraphData<f32, FStats>>> {
+        if let Some(data) = self.graph_f32.read().await.get(&key) {
+            return Ok(data.clone());
+        }
+
+        coalesce_load(&self.graph_f32_in_flight, key, || async move {
+            // the data may have been loaded by a request that
+            // finished after the check above
+            if let Some(data) = self.graph_f32.read().await.get(&key) {
                 return Ok(data.clone());
             }
-        }
 
-        let source = self.sources.path_f32.get(data_key).ok_or_else(|| {
-            anyhow::anyhow!("Path data source `{data_key}` not found")
-        })?;
+            let source = self.graph_f32_source(key).ok_or_else(|| {
+                let name = &self.data_source_meta(key).name;
+                anyhow::anyhow!("Data source `{name}` has no graph data")
+            })?;
 
-        let source = source.clone();
-        let path_data =
-            tokio::task::spawn_blocking(move || source(path)).await??;
+            let data = tokio::task::spawn_blocking(move || {
+                let node_data = source()?;
+                let stats = FStats::from_values_with_distribution(&node_data);
+                anyhow::Ok(Arc::new(GraphData { node_data, stats }))
+            })
+            .await??;
 
-        let path_stats = FStats::from_items(path_data.iter().copied());
+            self.graph_f32.write().await.insert(key, data.clone());
 
-        let data = Arc::new(GraphPathData {
-            path,
-            path_data,
-            path_stats,
-        });
+            Ok(data)
+        })
+        .await
+    }
+
+    /// Fetch the data for `path` from the path data source
+    /// `data_key`, loading it on a blocking thread if it's not already
+    /// cached. Concurrent requests for the same data share a single
+    /// load.
+    pub async fn fetch_path_data(
+        &self,
+        data_key: DataSourceId,
+        path: PathId,
+    ) -> anyhow::Result<Arc<GraphPathData<f32, FStats>>> {
+        let key = (data_key, path);
 
-        let key = (data_key.to_string(), path);
-        self.path_f32.write().await.insert(key, data.clone());
+        if let Some(data) = self.path_f32.read().await.get(&key) {
+            return Ok(data.clone());
+        }
 
-        Ok(data)
+        coalesce_load(&self.path_f32_in_flight, key, || async move {
+            if let Some(data) = self.path_f32.read().await.peek(&key) {
+                return Ok(data.clone());
+            }
+
+            let source = self.path_f32_source(data_key).ok_or_else(|| {
+                let name = &self.data_source_meta(data_key).name;
+                anyhow::anyhow!("Data source `{name}` has no path data")
+            })?;
+
+            let data = tokio::task::spawn_blocking(move || {
+                let path_data = source(path)?;
+                let path_stats = FStats::from_items(path_data.iter().copied());
+                anyhow::Ok(Arc::new(GraphPathData {
+                    path,
+                    path_data,
+                    path_stats,
+                }))
+            })
+            .await??;
+
+            self.path_f32.write().await.insert(key, data.clone());
+
+            Ok(data)
+        })
+        .await
     }
 
     pub fn fetch_path_data_blocking(
         &self,
-        data_key: &str,
+        data_key: DataSourceId,
         path: PathId,
     ) -> Option<Arc<GraphPathData<f32, FStats>>> {
-        let data_key = data_key.to_string();
-        if let Some(data) =
-            self.path_f32.blocking_read().get(&(data_key.clone(), path))
+        if let Some(data) = self.path_f32.blocking_read().get(&(data_key, path))
         {
             return Some(data.clone());
         }
 
-        let source = self.sources.path_f32.get(&data_key)?;
-        let path_data = source(path).unwrap();
+        let source = self.path_f32_source(data_key)?;
+        let path_data = match source(path) {
+            Ok(data) => data,
+            Err(e) => {
+                let name = &self.data_source_meta(data_key).name;
+                log::error!("Error loading path data `{name}`: {e:?}");
+                return None;
+            }
+        };
         let path_stats = FStats::from_items(path_data.iter().copied());
 
         let data = Arc::new(GraphPathData {
@@ -329,3 +999,202 @@ impl GraphDataCache {
         Some(data)
     }
 }
+
+/// Settings for the data cache memory budget, together with the
+/// cache statistics
+pub struct GraphDataCacheWidget {
+    pub cache: Arc<GraphDataCache>,
+}
+
+impl SettingsWidget for GraphDataCacheWidget {
+    fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        _settings_ctx: &SettingsUiContext,
+    ) -> SettingsUiResponse {
+        let resp = ui.vertical(|ui| {
+            let stats = self.cache.path_data_stats();
+            let mut budget_mb = stats.budget_bytes >> 20;
+
+            ui.horizontal(|ui| {
+                ui.label("Path data memory budget (MB)");
+
+                let budget = egui::DragValue::new(&mut budget_mb)
+                    .clamp_range(16..=(1 << 20));
+
+                if ui.add(budget).changed() {
+                    self.cache.set_path_data_budget(budget_mb << 20);
+                }
+            });
+
+            ui.separator();
+
+            self.cache.debug_ui(ui);
+        });
+
+        SettingsUiResponse {
+            response: resp.response,
+        }
+    }
+}
+
+/// Lists all registered data sources and their metadata
+pub struct DataSourcesWidget {
+    pub cache: Arc<GraphDataCache>,
+}
+
+impl SettingsWidget for DataSourcesWidget {
+    fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        _settings_ctx: &SettingsUiContext,
+    ) -> SettingsUiResponse {
+        let resp = ui.vertical(|ui| {
+            for id in self.cache.data_sources() {
+                let meta = self.cache.data_source_meta(id);
+
+                let mut data = Vec::new();
+                if self.cache.has_graph_data(id) {
+                    data.push("graph");
+                }
+                if self.cache.has_path_data(id) {
+                    data.push("path");
+                }
+
+                ui.collapsing(&meta.name, |ui| {
+                    if !meta.description.is_empty() {
+                        ui.label(&meta.description);
+                    }
+
+                    egui::Grid::new(("DataSourcesWidget", id)).show(ui, |ui| {
+                        ui.label("Values");
+                        ui.label(meta.value_domain.to_string());
+                        ui.end_row();
+
+                        if let Some(unit) = &meta.unit {
+                            ui.label("Unit");
+                            ui.label(unit);
+                            ui.end_row();
+                        }
+
+                        ui.label("Data");
+                        if data.is_empty() {
+                            ui.label("none (custom sampler)");
+                        } else {
+                            ui.label(data.join(", "));
+                        }
+                        ui.end_row();
+                    });
+                });
+            }
+        });
+
+        SettingsUiResponse {
+            response: resp.response,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_graph;
+
+    #[test]
+    fn fstats_quantiles() {
+        let values = (0..=100).map(|v| v as f32).chain([f32::NAN, 1000.0]);
+        let stats =
+            FStats::from_values_with_distribution(&values.collect::<Vec<_>>());
+
+        assert_eq!(stats.count, 102);
+        assert_eq!([stats.min, stats.max], [0.0, 1000.0]);
+        let histogram = stats.histogram().unwrap();
+        assert_eq!(histogram.iter().sum::<u32>(), 102);
+        assert_eq!(histogram[FStats::HISTOGRAM_BINS - 1], 1);
+
+        assert_eq!(stats.quantile(0.5), 50.0);
+        assert_eq!(stats.quantile_range([0.0, 0.99]), [0.0, 99.0]);
+
+        // the outlier doesn't stretch the auto range
+        assert!(stats.auto_range()[1] < 1000.0);
+
+        let empty = FStats::from_values_with_distribution(&[]);
+        assert!(empty.is_empty());
+        assert_eq!(empty.auto_range(), [0.0, 1.0]);
+
+        let constant = FStats::from_values_with_distribution(&[2.0; 10]);
+        assert_eq!(constant.auto_range(), [2.0, 3.0]);
+
+        // the summary stats alone match, and fall back to the min-max
+        // range for quantiles
+        let values = [3.0, 1.0, f32::NAN, 2.0];
+        let summary = FStats::from_items(values.into_iter());
+        let full = FStats::from_values_with_distribution(&values);
+        assert!(summary.histogram().is_none());
+        assert_eq!(
+            [summary.min, summary.max, summary.mean, summary.std_dev],
+            [full.min, full.max, full.mean, full.std_dev]
+        );
+        assert_eq!(summary.quantile(0.5), 2.0);
+    }
+
+    struct NodeLength;
+
+    impl DataSource for NodeLength {
+        fn name(&self) -> &str {
+            "node_length"
+        }
+
+        fn value_domain(&self) -> ValueDomain {
+            ValueDomain::Count
+        }
+
+        fn graph_f32(&self, graph: &PathIndex) -> anyhow::Result<Vec<f32>> {
+            Ok((0..graph.node_count)
+                .map(|ix| graph.node_sequence(Node::from(ix)).len() as f32)
+                .collect())
+        }
+    }
+
+    #[test]
+    fn data_source_plugin() -> anyhow::Result<()> {
+        let graph = test_graph();
+
+        let mut sources = GraphDataSources {
+            registry: DataSourceRegistry::default(),
+            graph_f32: HashMap::default(),
+            path_f32: HashMap::default(),
+            inputs: HashMap::default(),
+        };
+
+        let id = sources.insert_data_source(
+            &graph,
+            Arc::new(NodeLength),
+            ColorSchemeId::default(),
+        );
+
+        assert_eq!(sources.registry.id("node_length"), Some(id));
+        assert_eq!(sources.registry.meta(id).value_domain, ValueDomain::Count);
+        assert!(!sources.path_f32.contains_key(&id));
+
+        let data = sources.graph_f32[&id]()?;
+        assert_eq!(data.len(), graph.node_count);
+        assert_eq!(data.iter().sum::<f32>() as u64, graph.pangenome_len().0);
+
+        sources.remove_source(id);
+        assert_eq!(sources.registry.id("node_length"), None);
+        assert_eq!(sources.registry.ids().count(), 0);
+        assert!(!sources.graph_f32.contains_key(&id));
+
+        // the old ID isn't reused
+        let new_id = sources.insert_data_source(
+            &graph,
+            Arc::new(NodeLength),
+            ColorSchemeId::default(),
+        );
+        assert_ne!(new_id, id);
+        assert_eq!(sources.registry.meta(id).name, "node_length");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,524 @@
+//! Read coverage from alignments to the graph, in the GAF format
+//! produced by GraphAligner and minigraph.
+
+use std::{
+    io::{BufRead, BufReader},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Context};
+use waragraph_core::graph::{Node, PathId, PathIndex};
+
+use crate::color::ColorSchemeId;
+
+use super::{
+    project_node_values, DataSourceId, DataSourceMeta, GraphDataCache,
+    ValueDomain,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GafFilter {
+    /// Alignments with a lower mapping quality are skipped. A MAPQ of
+    /// 255 (missing) always passes.
+    pub min_mapq: u8,
+    /// Alignments with a lower identity are skipped. The identity is
+    /// taken from the `id:f` tag if present, otherwise it's computed
+    /// as residue matches / alignment block length.
+    pub min_identity: f32,
+}
+
+impl Default for GafFilter {
+    fn default() -> Self {
+        Self {
+            min_mapq: 0,
+            min_identity: 0.0,
+        }
+    }
+}
+
+/// Per-node coverage accumulated from a GAF file.
+pub struct GafCoverage {
+    /// Number of aligned bases covering each node
+    pub node_bp: Vec<u64>,
+    /// Number of times an alignment passes through each node
+    pub node_reads: Vec<u32>,
+    /// Depth at each base, indexed by pangenome position; only
+    /// computed if requested, as it's the size of the graph sequence
+    pub base_depth: Option<Vec<u32>>,
+
+    pub alignments_used: usize,
+    pub alignments_filtered: usize,
+}
+
+impl GafCoverage {
+    /// Accumulates the coverage from the GAF file at `gaf_path`,
+    /// including the depth at each base if `per_base` is set
+    pub fn from_gaf(
+        graph: &PathIndex,
+        filter: GafFilter,
+        per_base: bool,
+        gaf_path: impl AsRef<Path>,
+    ) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(gaf_path.as_ref())?;
+        Self::from_reader(graph, filter, per_base, BufReader::new(file))
+    }
+
+    pub fn from_reader(
+        graph: &PathIndex,
+        filter: GafFilter,
+        per_base: bool,
+        reader: impl BufRead,
+    ) -> anyhow::Result<Self> {
+        let mut result = Self {
+            node_bp: vec![0; graph.node_count],
+            node_reads: vec![0; graph.node_count],
+            base_depth: per_base
+                .then(|| vec![0; graph.pangenome_len().0 as usize]),
+            alignments_used: 0,
+            alignments_filtered: 0,
+        };
+
+        // reused between records
+        let mut overlaps: Vec<(Node, Range<u64>)> = Vec::new();
+
+        for (line_ix, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let record = GafRecord::parse(&line)
+                .with_context(|| format!("GAF line {}", line_ix + 1))?;
+
+            if record.mapq < filter.min_mapq
+                || record.identity < filter.min_identity
+            {
+                result.alignments_filtered += 1;
+                continue;
+            }
+
+            overlaps.clear();
+            record
+                .node_overlaps(graph, &mut overlaps)
+                .with_context(|| format!("GAF line {}", line_ix + 1))?;
+
+            for (node, range) in overlaps.iter() {
+                result.node_bp[node.ix()] += range.end - range.start;
+                result.node_reads[node.ix()] += 1;
+
+                if let Some(depth) = result.base_depth.as_mut() {
+                    let offset = graph.node_offset(*node).0;
+                    let start = (offset + range.start) as usize;
+                    let end = (offset + range.end) as usize;
+                    depth[start..end].iter_mut().for_each(|d| *d += 1);
+                }
+            }
+
+            result.alignments_used += 1;
+        }
+
+        Ok(result)
+    }
+
+    /// Mean per-base depth of each node, i.e. the number of aligned
+    /// bases on the node divided by the node length
+    pub fn mean_depth(&self, graph: &PathIndex) -> Vec<f32> {
+        self.node_bp
+            .iter()
+            .enumerate()
+            .map(|(ix, &bp)| {
+                let len = graph.node_length(Node::from(ix)).0.max(1);
+                bp as f32 / len as f32
+            })
+            .collect()
+    }
+
+    pub fn read_count(&self) -> Vec<f32> {
+        self.node_reads.iter().map(|&n| n as f32).collect()
+    }
+
+    /// Highest per-base depth on each node, if the per-base depth was
+    /// computed
+    pub fn max_depth(&self, graph: &PathIndex) -> Option<Vec<f32>> {
+        let depth = self.base_depth.as_ref()?;
+
+        let result = (0..graph.node_count)
+            .map(|ix| {
+                let range = graph.node_pangenome_range(Node::from(ix));
+                let range = range.start.0 as usize..range.end.0 as usize;
+                depth[range].iter().max().copied().unwrap_or_default() as f32
+            })
+            .collect();
+
+        Some(result)
+    }
+}
+
+struct GafRecord<'a> {
+    path: &'a str,
+    path_range: std::ops::Range<u64>,
+    mapq: u8,
+    identity: f32,
+}
+
+impl<'a> GafRecord<'a> {
+    fn parse(line: &'a str) -> anyhow::Result<Self> {
+        let fields = line.split('\t').collect::<Vec<_>>();
+
+        if fields.len() < 12 {
+            anyhow::bail!(
+                "expected at least 12 columns, found {}",
+                fields.len()
+            );
+        }
+
+        let parse_u64 = |ix: usize| {
+            fields[ix]
+                .parse::<u64>()
+                .map_err(|e| anyhow!("error parsing column {}: {e:?}", ix + 1))
+        };
+
+        let path = fields[5];
+        let path_start = parse_u64(7)?;
+        let path_end = parse_u64(8)?;
+        let matches = parse_u64(9)?;
+        let block_len = parse_u64(10)?;
+        let mapq = fields[11].parse::<u8>().unwrap_or(255);
+
+        let identity = fields[12..]
+            .iter()
+            .find_map(|tag| tag.strip_prefix("id:f:")?.parse::<f32>().ok())
+            .unwrap_or_else(|| {
+                if block_len == 0 {
+                    0.0
+                } else {
+                    matches as f32 / block_len as f32
+                }
+            });
+
+        Ok(Self {
+            path,
+            path_range: path_start..path_end,
+            mapq,
+            identity,
+        })
+    }
+
+    /// Fills `out` with the nodes covered by the alignment, together
+    /// with the aligned range on each node, in the node's forward
+    /// orientation.
+    ///
+    /// The path column can either be an oriented segment walk
+    /// (`>12<13>14`), or a stable sequence name, in which case the
+    /// alignment coordinates are projected onto the graph path with
+    /// that name.
+    fn node_overlaps(
+        &self,
+        graph: &PathIndex,
+        out: &mut Vec<(Node, Range<u64>)>,
+    ) -> anyhow::Result<()> {
+        let range = &self.path_range;
+
+        if self.path.starts_with(['>', '<']) {
+            let mut offset = 0u64;
+
+            let steps = self.path.match_indices(['>', '<']).map(|(ix, o)| {
+                let name = self.path[ix + 1..]
+                    .split(['>', '<'])
+                    .next()
+                    .unwrap_or_default();
+                (name, o == "<")
+            });
+
+            for (segment, reverse) in steps {
+                let node = parse_segment(graph, segment)?;
+                let len = graph.node_length(node).0;
+
+                if let Some(local) =
+                    node_local_range(offset, len, reverse, range)
+                {
+                    out.push((node, local));
+                }
+
+                offset += len;
+                if offset >= range.end {
+                    break;
+                }
+            }
+        } else if let Some(path) = graph.path_names.get_by_right(self.path) {
+            let Some(steps) = graph.path_step_range_iter(*path, range.clone())
+            else {
+                return Ok(());
+            };
+            let offsets = &graph.path_step_offsets[path.ix()];
+
+            for (step_ix, step) in steps {
+                let Some(offset) = offsets.select(step_ix as u64) else {
+                    continue;
+                };
+
+                let node = step.node();
+                let len = graph.node_length(node).0;
+
+                if let Some(local) =
+                    node_local_range(offset, len, step.is_reverse(), range)
+                {
+                    out.push((node, local));
+                }
+            }
+        } else {
+            // a single segment given by name
+            let node = parse_segment(graph, self.path)?;
+            let len = graph.node_length(node).0;
+            if let Some(local) = node_local_range(0, len, false, range) {
+                out.push((node, local));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The part of `range` covered by a node step of length `len` at
+/// `offset` along the path, in the node's forward orientation
+fn node_local_range(
+    offset: u64,
+    len: u64,
+    reverse: bool,
+    range: &Range<u64>,
+) -> Option<Range<u64>> {
+    let start = offset.max(range.start) - offset;
+    let end = (offset + len).min(range.end).saturating_sub(offset);
+
+    if start >= end {
+        return None;
+    }
+
+    if reverse {
+        Some(len - end..len - start)
+    } else {
+        Some(start..end)
+    }
+}
+
+fn parse_segment(graph: &PathIndex, name: &str) -> anyhow::Result<Node> {
+    let seg_id = name
+        .parse::<u32>()
+        .map_err(|_| anyhow!("unknown segment or path `{name}`"))?;
+
+    let (min_id, max_id) = graph.segment_id_range;
+    if seg_id < min_id || seg_id > max_id {
+        anyhow::bail!("segment `{name}` is not in the graph");
+    }
+
+    Ok(Node::from(seg_id - min_id))
+}
+
+/// Lazily loads and caches the coverage from a GAF file, so that the
+/// data sources derived from the same file share a single parse.
+struct GafCoverageSource {
+    graph: Arc<PathIndex>,
+    gaf_path: PathBuf,
+    filter: GafFilter,
+    per_base: bool,
+
+    coverage: Mutex<Option<Arc<GafCoverage>>>,
+}
+
+impl GafCoverageSource {
+    fn get(&self) -> anyhow::Result<Arc<GafCoverage>> {
+        let mut coverage = self.coverage.lock().unwrap();
+
+        if let Some(coverage) = coverage.as_ref() {
+            return Ok(coverage.clone());
+        }
+
+        let t0 = std::time::Instant::now();
+        let loaded = Arc::new(GafCoverage::from_gaf(
+            &self.graph,
+            self.filter,
+            self.per_base,
+            &self.gaf_path,
+        )?);
+
+        log::warn!(
+            "loaded coverage from {:?} in {:.2} s: {} alignments used, {} filtered",
+            self.gaf_path,
+            t0.elapsed().as_secs_f32(),
+            loaded.alignments_used,
+            loaded.alignments_filtered
+        );
+
+        *coverage = Some(loaded.clone());
+
+        Ok(loaded)
+    }
+}
+
+/// Registers coverage from the GAF file at `gaf_path` as data sources
+/// in `cache`, using the name `name` for the mean per-base depth,
+/// and `{name}_reads` for the number of alignments touching each node.
+/// If `per_base` is set, the depth at each base is also computed, and
+/// the highest depth on each node is registered as `{name}_max`.
+///
+/// All are available as graph-level sources, and as path-level
+/// sources by projecting the node values onto each path. Returns the
+/// IDs of the depth, read count and (if used) max depth sources, in
+/// that order.
+///
+/// The file is only parsed once any of the sources is first used.
+pub fn register_gaf_coverage(
+    cache: &GraphDataCache,
+    name: &str,
+    gaf_path: impl AsRef<Path>,
+    filter: GafFilter,
+    per_base: bool,
+    color_scheme: ColorSchemeId,
+) -> Vec<DataSourceId> {
+    let graph = cache.graph.clone();
+    let gaf_path = gaf_path.as_ref();
+
+    let source = Arc::new(GafCoverageSource {
+        graph: graph.clone(),
+        gaf_path: gaf_path.to_path_buf(),
+        filter,
+        per_base,
+        coverage: Mutex::new(None),
+    });
+
+    type NodeValuesFn = Arc<
+        dyn Fn(&GafCoverage) -> anyhow::Result<Vec<f32>>
+            + Send
+            + Sync
+            + 'static,
+    >;
+
+    let mean_depth: NodeValuesFn = {
+        let graph = graph.clone();
+        Arc::new(move |cov: &GafCoverage| Ok(cov.mean_depth(&graph)))
+    };
+    let read_count: NodeValuesFn =
+        Arc::new(|cov: &GafCoverage| Ok(cov.read_count()));
+
+    let depth_meta = DataSourceMeta {
+        name: name.to_string(),
+        description: format!("Mean read depth from {gaf_path:?}"),
+        unit: Some("bp/bp".to_string()),
+        value_domain: ValueDomain::Count,
+        default_color_scheme: color_scheme,
+    };
+
+    let reads_meta = DataSourceMeta {
+        name: format!("{name}_reads"),
+        description: format!("Number of alignments from {gaf_path:?}"),
+        unit: Some("reads".to_string()),
+        value_domain: ValueDomain::Count,
+        default_color_scheme: color_scheme,
+    };
+
+    let mut sources = vec![(depth_meta, mean_depth), (reads_meta, read_count)];
+
+    if per_base {
+        let max_meta = DataSourceMeta {
+            name: format!("{name}_max"),
+            description: format!("Max read depth from {gaf_path:?}"),
+            unit: Some("reads".to_string()),
+            value_domain: ValueDomain::Count,
+            default_color_scheme: color_scheme,
+        };
+
+        let max_depth: NodeValuesFn = {
+            let graph = graph.clone();
+            Arc::new(move |cov: &GafCoverage| {
+                cov.max_depth(&graph)
+                    .ok_or_else(|| anyhow!("per-base depth not computed"))
+            })
+        };
+
+        sources.push((max_meta, max_depth));
+    }
+
+    sources
+        .into_iter()
+        .map(|(meta, values)| {
+            {
+                let source = source.clone();
+                let values = values.clone();
+                cache.register_graph_f32(
+                    meta.clone(),
+                    Arc::new(move || values(&*source.get()?)),
+                );
+            }
+
+            let source = source.clone();
+            let graph = graph.clone();
+            cache.register_path_f32(
+                meta,
+                Arc::new(move |path: PathId| {
+                    let node_values = values(&*source.get()?)?;
+                    Ok(project_node_values(&graph, path, &node_values))
+                }),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gaf_node_coverage() -> anyhow::Result<()> {
+        // segments 1: 4bp, 2: 6bp, 3: 2bp; paths p1: 1+,2+,3+ and
+        // p2: 3+,2-
+        let gfa =
+            concat!(env!("CARGO_MANIFEST_DIR"), "/../test/data/gaf_test.gfa");
+        let graph = PathIndex::from_gfa(gfa)?;
+
+        let gaf = [
+            // node 1 bases 2..4, node 2 bases 0..3
+            "r1\t5\t0\t5\t+\t>1>2\t10\t2\t7\t5\t5\t60",
+            // node 1 base 3, all of nodes 2 and 3
+            "r2\t9\t0\t9\t+\tp1\t12\t3\t12\t9\t9\t60",
+            // all of node 3, then node 2 reversed, so its last 2 bases
+            "r3\t4\t0\t4\t+\tp2\t8\t0\t4\t4\t4\t60",
+            // filtered by MAPQ
+            "r4\t5\t0\t5\t+\t>1>2\t10\t2\t7\t5\t5\t5",
+            // filtered by identity tag
+            "r5\t2\t0\t2\t+\t>3\t2\t0\t2\t2\t2\t60\tid:f:0.5",
+        ]
+        .join("\n");
+
+        let filter = GafFilter {
+            min_mapq: 10,
+            min_identity: 0.9,
+        };
+
+        let coverage =
+            GafCoverage::from_reader(&graph, filter, true, gaf.as_bytes())?;
+
+        assert_eq!(coverage.alignments_used, 3);
+        assert_eq!(coverage.alignments_filtered, 2);
+
+        assert_eq!(coverage.node_bp, [2 + 1, 3 + 6 + 2, 2 + 2]);
+        assert_eq!(coverage.node_reads, [2, 3, 2]);
+
+        #[rustfmt::skip]
+        let depth = [
+            0, 0, 1, 2,
+            2, 2, 2, 1, 2, 2,
+            2, 2,
+        ];
+        assert_eq!(coverage.base_depth.as_deref(), Some(depth.as_slice()));
+        assert_eq!(coverage.max_depth(&graph), Some(vec![2.0, 2.0, 2.0]));
+
+        let node_only =
+            GafCoverage::from_reader(&graph, filter, false, gaf.as_bytes())?;
+        assert_eq!(node_only.node_bp, coverage.node_bp);
+        assert!(node_only.base_depth.is_none());
+
+        Ok(())
+    }
+}
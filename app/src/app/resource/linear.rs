@@ -0,0 +1,487 @@
+//! Signal tracks defined on linear references (BAM coverage and
+//! bedGraph intervals), projected onto the nodes of graph paths.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{anyhow, Context};
+use waragraph_core::graph::{Node, PathId, PathIndex};
+
+use crate::color::ColorSchemeId;
+
+use super::{
+    path_range_node_overlaps, DataSourceId, DataSourceMeta, GraphDataCache,
+    ValueDomain,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinearSignalFile {
+    /// Per-base alignment depth, computed from the alignments in a BAM
+    Bam(PathBuf),
+    /// `chrom start end value` intervals
+    BedGraph(PathBuf),
+}
+
+impl LinearSignalFile {
+    pub fn from_path(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let ext = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+
+        match ext.as_deref() {
+            Some("bam") => Ok(Self::Bam(path.to_path_buf())),
+            Some("bedgraph" | "bdg" | "bg") => {
+                Ok(Self::BedGraph(path.to_path_buf()))
+            }
+            _ => anyhow::bail!(
+                "Unknown signal file extension `{:?}`",
+                path.as_os_str()
+            ),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        match self {
+            LinearSignalFile::Bam(path) => path,
+            LinearSignalFile::BedGraph(path) => path,
+        }
+    }
+}
+
+/// Signal values for each path the signal file was mapped to, in the
+/// layout used by the path data sources (one value per unique node on
+/// the path, ordered by node ID).
+///
+/// The value for a node is the mean signal over all of its bases,
+/// across all of its visits by the path; bases without any signal
+/// count as zero.
+#[derive(Default)]
+pub struct PathSignal {
+    pub path_values: HashMap<PathId, Vec<f32>>,
+    /// Reference sequence names that didn't correspond to any path
+    pub unmapped_refs: Vec<String>,
+}
+
+/// Accumulates the per-node sums of signal values, for a single path
+struct PathSignalSums {
+    sums: Vec<f64>,
+}
+
+impl PathSignalSums {
+    fn new(graph: &PathIndex, path: PathId) -> Self {
+        let node_count = graph.path_node_sets[path.ix()].len() as usize;
+        Self {
+            sums: vec![0.0; node_count],
+        }
+    }
+
+    fn add_overlaps(
+        &mut self,
+        graph: &PathIndex,
+        path: PathId,
+        overlaps: &[(Node, u64)],
+        value: f64,
+    ) {
+        let node_set = &graph.path_node_sets[path.ix()];
+        for &(node, len) in overlaps {
+            // the path data is ordered by node, so the node's index
+            // is its rank in the path node set
+            let ix = node_set.rank(node.into()) as usize - 1;
+            self.sums[ix] += value * len as f64;
+        }
+    }
+
+    fn finish(self, graph: &PathIndex, path: PathId) -> Vec<f32> {
+        let node_set = &graph.path_node_sets[path.ix()];
+
+        node_set
+            .iter()
+            .zip(self.sums)
+            .map(|(node, sum)| {
+                let node = Node::from(node);
+                let len = graph.node_length(node).0.max(1);
+                let visits = graph
+                    .node_path_steps(node, path)
+                    .map(|steps| steps.count())
+                    .unwrap_or(1)
+                    .max(1);
+
+                (sum / (len as f64 * visits as f64)) as f32
+            })
+            .collect()
+    }
+}
+
+impl PathSignal {
+    /// Load the signal in `file`, using `path_name_map` to map the
+    /// reference sequence names used in the file to path names in
+    /// the graph.
+    pub fn load(
+        graph: &PathIndex,
+        path_name_map: impl Fn(&str) -> String,
+        file: &LinearSignalFile,
+    ) -> anyhow::Result<Self> {
+        let mut sums: HashMap<PathId, PathSignalSums> = HashMap::default();
+        let mut unmapped_refs = Vec::new();
+
+        let mut ref_paths: HashMap<String, Option<PathId>> = HashMap::default();
+
+        let mut lookup_path = |ref_name: &str| -> Option<PathId> {
+            if let Some(path) = ref_paths.get(ref_name) {
+                return *path;
+            }
+
+            let path_name = path_name_map(ref_name);
+            let path = graph.path_names.get_by_right(&path_name).copied();
+
+            if path.is_none() {
+                unmapped_refs.push(ref_name.to_string());
+            }
+
+            ref_paths.insert(ref_name.to_string(), path);
+            path
+        };
+
+        // reused between records
+        let mut overlaps: Vec<(Node, u64)> = Vec::new();
+
+        let mut add_signal =
+            |path: PathId, range: std::ops::Range<u64>, value: f64| {
+                overlaps.clear();
+                path_range_node_overlaps(graph, path, range, &mut overlaps);
+
+                sums.entry(path)
+                    .or_insert_with(|| PathSignalSums::new(graph, path))
+                    .add_overlaps(graph, path, &overlaps, value);
+            };
+
+        match file {
+            LinearSignalFile::Bam(bam_path) => {
+                let mut reader = std::fs::File::open(bam_path)
+                    .map(noodles::bam::Reader::new)?;
+                reader.read_header()?;
+                let ref_seqs = reader.read_reference_sequences()?;
+
+                let ref_seq_paths = ref_seqs
+                    .keys()
+                    .map(|name| lookup_path(name))
+                    .collect::<Vec<_>>();
+
+                for record in reader.records() {
+                    let record = record?;
+
+                    let flags = record.flags();
+                    if flags.is_unmapped()
+                        || flags.is_secondary()
+                        || flags.is_qc_fail()
+                        || flags.is_duplicate()
+                    {
+                        continue;
+                    }
+
+                    let Some(path) =
+                        record.reference_sequence_id().and_then(|id| {
+                            ref_seq_paths.get(id).copied().flatten()
+                        })
+                    else {
+                        continue;
+                    };
+
+                    let Some(start) = record.alignment_start() else {
+                        continue;
+                    };
+
+                    // BAM positions are 1-based
+                    let start = usize::from(start) as u64 - 1;
+
+                    for range in cigar_aligned_ranges(start, record.cigar()) {
+                        add_signal(path, range, 1.0);
+                    }
+                }
+            }
+            LinearSignalFile::BedGraph(bg_path) => {
+                let reader =
+                    std::fs::File::open(bg_path).map(BufReader::new)?;
+
+                for (line_ix, line) in reader.lines().enumerate() {
+                    let line = line?;
+
+                    let Some((ref_name, range, value)) =
+                        parse_bedgraph_line(&line).with_context(|| {
+                            format!("bedGraph line {}", line_ix + 1)
+                        })?
+                    else {
+                        continue;
+                    };
+
+                    if let Some(path) = lookup_path(ref_name) {
+                        add_signal(path, range, value);
+                    }
+                }
+            }
+        }
+
+        let path_values = sums
+            .into_iter()
+            .map(|(path, sums)| (path, sums.finish(graph, path)))
+            .collect();
+
+        Ok(Self {
+            path_values,
+            unmapped_refs,
+        })
+    }
+}
+
+/// The reference ranges of the bases in an alignment that are aligned
+/// to the reference, i.e. excluding insertions, clips, deletions and
+/// skipped regions, with `start` the 0-based start of the alignment
+fn cigar_aligned_ranges(
+    start: u64,
+    cigar: &noodles::sam::record::Cigar,
+) -> Vec<std::ops::Range<u64>> {
+    use noodles::sam::record::cigar::op::Kind;
+
+    let mut ranges = Vec::new();
+    let mut pos = start;
+
+    for op in cigar.iter() {
+        let len = op.len() as u64;
+        match op.kind() {
+            Kind::Match | Kind::SequenceMatch | Kind::SequenceMismatch => {
+                ranges.push(pos..pos + len);
+                pos += len;
+            }
+            Kind::Deletion | Kind::Skip => {
+                pos += len;
+            }
+            _ => (),
+        }
+    }
+
+    ranges
+}
+
+/// Parses a bedGraph data line; empty, comment, `track` and `browser`
+/// lines are skipped with `None`
+fn parse_bedgraph_line(
+    line: &str,
+) -> anyhow::Result<Option<(&str, std::ops::Range<u64>, f64)>> {
+    if line.trim().is_empty()
+        || line.starts_with('#')
+        || line.starts_with("track")
+        || line.starts_with("browser")
+    {
+        return Ok(None);
+    }
+
+    let mut fields = line.split_whitespace();
+
+    let mut next_field = |name: &str| {
+        fields
+            .next()
+            .ok_or_else(|| anyhow!("missing field `{name}`"))
+    };
+
+    let ref_name = next_field("chrom")?;
+    let start = next_field("start")?.parse::<u64>()?;
+    let end = next_field("end")?.parse::<u64>()?;
+    let value = next_field("value")?.parse::<f64>()?;
+
+    if end < start {
+        anyhow::bail!("interval end {end} is before its start {start}");
+    }
+
+    Ok(Some((ref_name, start..end, value)))
+}
+
+/// Lazily loads and caches the signal from a file, so that it's only
+/// parsed once, no matter how many paths use it.
+struct PathSignalSource {
+    graph: Arc<PathIndex>,
+    file: LinearSignalFile,
+    path_name_map: Box<dyn Fn(&str) -> String + Send + Sync + 'static>,
+
+    signal: Mutex<Option<Arc<PathSignal>>>,
+}
+
+impl PathSignalSource {
+    fn get(&self) -> anyhow::Result<Arc<PathSignal>> {
+        let mut signal = self.signal.lock().unwrap();
+
+        if let Some(signal) = signal.as_ref() {
+            return Ok(signal.clone());
+        }
+
+        let t0 = std::time::Instant::now();
+        let loaded = Arc::new(PathSignal::load(
+            &self.graph,
+            &self.path_name_map,
+            &self.file,
+        )?);
+
+        log::warn!(
+            "loaded signal from {:?} onto {} paths in {:.2} s",
+            self.file.path(),
+            loaded.path_values.len(),
+            t0.elapsed().as_secs_f32(),
+        );
+
+        if !loaded.unmapped_refs.is_empty() {
+            log::warn!(
+                "{} reference sequences in {:?} did not match any path: {:?}",
+                loaded.unmapped_refs.len(),
+                self.file.path(),
+                loaded.unmapped_refs,
+            );
+        }
+
+        *signal = Some(loaded.clone());
+
+        Ok(loaded)
+    }
+}
+
+/// Registers the signal in `file` as the path data source `name` in
+/// `cache`. The reference sequence names in the file are mapped to
+/// path names using `path_name_map`; paths without any signal get
+/// zero for all nodes.
+///
+/// The file is only parsed once the data source is first used.
+pub fn register_path_signal(
+    cache: &GraphDataCache,
+    name: &str,
+    file: LinearSignalFile,
+    path_name_map: impl Fn(&str) -> String + Send + Sync + 'static,
+    color_scheme: ColorSchemeId,
+) -> DataSourceId {
+    let graph = cache.graph.clone();
+
+    let (description, unit, value_domain) = match &file {
+        LinearSignalFile::Bam(path) => (
+            format!("Mean alignment depth from {path:?}"),
+            Some("bp/bp".to_string()),
+            ValueDomain::Count,
+        ),
+        LinearSignalFile::BedGraph(path) => (
+            format!("Mean signal from {path:?}"),
+            None,
+            ValueDomain::Continuous,
+        ),
+    };
+
+    let meta = DataSourceMeta {
+        name: name.to_string(),
+        description,
+        unit,
+        value_domain,
+        default_color_scheme: color_scheme,
+    };
+
+    let source = Arc::new(PathSignalSource {
+        graph: graph.clone(),
+        file,
+        path_name_map: Box::new(path_name_map),
+        signal: Mutex::new(None),
+    });
+
+    cache.register_path_f32(
+        meta,
+        Arc::new(move |path: PathId| {
+            let signal = source.get()?;
+
+            if let Some(values) = signal.path_values.get(&path) {
+                Ok(values.clone())
+            } else {
+                let node_count = graph.path_node_sets[path.ix()].len();
+                Ok(vec![0.0; node_count as usize])
+            }
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::{test_graph, TempFile};
+
+    #[test]
+    fn bedgraph_lines() {
+        for skipped in ["", "track type=bedGraph", "browser position", "# x"] {
+            assert!(parse_bedgraph_line(skipped).unwrap().is_none());
+        }
+
+        let (name, range, value) =
+            parse_bedgraph_line("chr1\t10\t20\t1.5").unwrap().unwrap();
+        assert_eq!((name, range, value), ("chr1", 10..20, 1.5));
+
+        for bad in [
+            "chr1 10 20",
+            "chr1 x 20 1.0",
+            "chr1 10 20 y",
+            "chr1 20 10 1",
+        ] {
+            assert!(parse_bedgraph_line(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn cigar_projection() {
+        let cigar = "2S3M2I1D2=4N1X".parse().unwrap();
+        let ranges = cigar_aligned_ranges(10, &cigar);
+        assert_eq!(ranges, vec![10..13, 14..16, 20..21]);
+    }
+
+    #[test]
+    fn bedgraph_node_means() -> anyhow::Result<()> {
+        let graph = test_graph();
+
+        let path = *graph.path_names.left_values().next().unwrap();
+        let path_name = graph.path_names.get_by_left(&path).unwrap();
+
+        // the first two steps of the path, which only visits them once
+        let steps = &graph.path_steps[path.ix()];
+        let [a, b] = [steps[0].node(), steps[1].node()];
+        let visits = |node| graph.node_path_steps(node, path).unwrap().count();
+        assert_eq!((visits(a), visits(b)), (1, 1));
+
+        let a_len = graph.node_length(a).0;
+        let b_len = graph.node_length(b).0;
+
+        // `a` is covered with 2.0, and half of `b` with 4.0
+        let bedgraph = format!(
+            "track type=bedGraph\n\
+             {path_name}\t0\t{a_len}\t2.0\n\
+             {path_name}\t{a_len}\t{}\t4.0\n\
+             unknown\t0\t10\t1.0\n",
+            a_len + b_len / 2,
+        );
+
+        let file_path = TempFile::new("bedgraph", bedgraph)?;
+
+        let file = LinearSignalFile::from_path(&file_path)?;
+        let signal = PathSignal::load(&graph, |name| name.to_string(), &file)?;
+
+        assert_eq!(signal.unmapped_refs, vec!["unknown".to_string()]);
+
+        let values = &signal.path_values[&path];
+        let value = |node: Node| {
+            let node_set = &graph.path_node_sets[path.ix()];
+            values[node_set.rank(node.into()) as usize - 1]
+        };
+
+        let b_mean = 4.0 * (b_len / 2) as f32 / b_len as f32;
+
+        assert_eq!(value(a), 2.0);
+        assert!((value(b) - b_mean).abs() < 1e-5);
+        assert_eq!(value(steps[2].node()), 0.0);
+
+        Ok(())
+    }
+}
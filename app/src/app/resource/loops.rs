@@ -0,0 +1,187 @@
+//! Data sources that show where a path loops back into nodes it has
+//! already visited (e.g. tandem repeats and copy-number variation),
+//! as opposed to nodes that are merely shared by many paths.
+
+use std::sync::Arc;
+
+use waragraph_core::graph::{Node, PathId, PathIndex};
+
+use crate::color::ColorSchemeId;
+
+use super::{DataSourceMeta, GraphDataSources, ValueDomain};
+
+/// How a path traverses a node, based on the indices of the steps
+/// that visit it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Traversal {
+    /// The node is visited at most once
+    Single = 0,
+    /// The node is visited multiple times, in consecutive steps
+    Contiguous = 1,
+    /// The path leaves the node and comes back to it later
+    Loop = 2,
+}
+
+impl Traversal {
+    pub const COUNT: u32 = 3;
+
+    /// Classifies the visits to a node, given the (sorted) indices of
+    /// the path steps on it
+    pub fn from_step_indices(steps: &[usize]) -> Self {
+        if steps.len() < 2 {
+            Traversal::Single
+        } else if steps.windows(2).all(|w| w[1] == w[0] + 1) {
+            Traversal::Contiguous
+        } else {
+            Traversal::Loop
+        }
+    }
+}
+
+/// The length, in bp, of the part of `path` from the start of the
+/// first step to the end of the last step in `steps`, which must be
+/// steps on `node`. Nodes visited at most once have a span of 0.
+pub fn loop_span(
+    graph: &PathIndex,
+    path: PathId,
+    node: Node,
+    steps: &[usize],
+) -> u64 {
+    let (Some(&first), Some(&last)) = (steps.first(), steps.last()) else {
+        return 0;
+    };
+
+    if first == last {
+        return 0;
+    }
+
+    let offsets = &graph.path_step_offsets[path.ix()];
+    let start = offsets.select(first as u64).unwrap_or_default();
+    let end = offsets.select(last as u64).unwrap_or_default();
+    let (_, len) = graph.node_offset_length(node);
+
+    end + len.0 - start
+}
+
+#[derive(Clone, Copy)]
+enum LoopMetric {
+    Traversal,
+    Span,
+}
+
+impl LoopMetric {
+    fn value(
+        &self,
+        graph: &PathIndex,
+        path: PathId,
+        node: Node,
+        steps: &[usize],
+    ) -> f32 {
+        match self {
+            LoopMetric::Traversal => {
+                Traversal::from_step_indices(steps) as u32 as f32
+            }
+            LoopMetric::Span => loop_span(graph, path, node, steps) as f32,
+        }
+    }
+
+    fn node_value(&self, graph: &PathIndex, path: PathId, node: Node) -> f32 {
+        let steps = graph
+            .node_path_steps(node, path)
+            .map(|steps| steps.collect::<Vec<_>>())
+            .unwrap_or_default();
+        self.value(graph, path, node, &steps)
+    }
+
+    fn path_data(&self, graph: &PathIndex, path: PathId) -> Vec<f32> {
+        graph.path_node_sets[path.ix()]
+            .iter()
+            .map(|node| self.node_value(graph, path, Node::from(node)))
+            .collect()
+    }
+
+    /// The graph-level data is the maximum over all paths
+    fn graph_data(&self, graph: &PathIndex) -> Vec<f32> {
+        (0..graph.node_count)
+            .map(|ix| {
+                let node = Node::from(ix);
+                graph
+                    .paths_on_node(node)
+                    .into_iter()
+                    .flatten()
+                    .map(|path| self.node_value(graph, path, node))
+                    .fold(0.0, f32::max)
+            })
+            .collect()
+    }
+}
+
+/// Registers the "path_loops" and "loop_span" sources, as both graph
+/// and path data sources
+pub(super) fn register_loop_sources(
+    sources: &mut GraphDataSources,
+    graph: &Arc<PathIndex>,
+    loop_classes: ColorSchemeId,
+    spectral: ColorSchemeId,
+) {
+    let metrics = [
+        (
+            LoopMetric::Traversal,
+            DataSourceMeta {
+                name: "path_loops".to_string(),
+                description: "Whether the path visits each node once (0), \
+                              several times in a row (1), or again after \
+                              leaving it, i.e. in a loop (2)"
+                    .to_string(),
+                unit: None,
+                value_domain: ValueDomain::Classes(Traversal::COUNT),
+                default_color_scheme: loop_classes,
+            },
+        ),
+        (
+            LoopMetric::Span,
+            DataSourceMeta {
+                name: "loop_span".to_string(),
+                description: "Length of the path from the first visit to \
+                              each node to the end of the last visit; 0 for \
+                              nodes visited once"
+                    .to_string(),
+                unit: Some("bp".to_string()),
+                value_domain: ValueDomain::Count,
+                default_color_scheme: spectral,
+            },
+        ),
+    ];
+
+    for (metric, meta) in metrics {
+        let id = sources.registry.register(meta);
+
+        {
+            let graph = graph.clone();
+            let ctor = move || Ok(metric.graph_data(&graph));
+            sources.graph_f32.insert(id, Arc::new(ctor));
+        }
+
+        {
+            let graph = graph.clone();
+            let ctor = move |path: PathId| Ok(metric.path_data(&graph, path));
+            sources.path_f32.insert(id, Arc::new(ctor));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn traversal_classes() {
+        assert_eq!(Traversal::from_step_indices(&[]), Traversal::Single);
+        assert_eq!(Traversal::from_step_indices(&[4]), Traversal::Single);
+        assert_eq!(
+            Traversal::from_step_indices(&[4, 5, 6]),
+            Traversal::Contiguous
+        );
+        assert_eq!(Traversal::from_step_indices(&[4, 5, 9]), Traversal::Loop);
+    }
+}
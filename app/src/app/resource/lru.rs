@@ -0,0 +1,241 @@
+use std::{
+    collections::BTreeMap,
+    hash::Hash,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use egui::epaint::ahash::HashMap;
+
+/// The (approximate) number of bytes a cached value uses
+pub trait ByteSize {
+    fn byte_size(&self) -> usize;
+}
+
+impl<T: ByteSize> ByteSize for std::sync::Arc<T> {
+    fn byte_size(&self) -> usize {
+        self.as_ref().byte_size()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+
+    pub entries: usize,
+    pub used_bytes: usize,
+    pub budget_bytes: usize,
+}
+
+impl CacheStats {
+    pub fn hit_rate(&self) -> f32 {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            0.0
+        } else {
+            self.hits as f32 / total as f32
+        }
+    }
+}
+
+struct LruEntry<V> {
+    value: V,
+    size: usize,
+    last_used: AtomicU64,
+    // the key of the entry in the recency queue
+    queued_at: u64,
+}
+
+/// A map that evicts the least recently used entries once the total
+/// size of its values exceeds the memory budget.
+///
+/// Lookups only need a shared reference, so that readers don't have
+/// to wait on each other; recency is tracked using a logical clock.
+/// Since lookups can't reorder the recency queue, entries that have
+/// been used since they were queued are moved to the back of the
+/// queue when they reach the front during eviction.
+pub(super) struct LruCache<K, V> {
+    entries: HashMap<K, LruEntry<V>>,
+    // keys by the tick they were queued at, oldest first
+    queue: BTreeMap<u64, K>,
+
+    used_bytes: usize,
+    budget_bytes: usize,
+
+    clock: AtomicU64,
+
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: u64,
+}
+
+impl<K, V> LruCache<K, V>
+where
+    K: Clone + Eq + Hash,
+    V: ByteSize,
+{
+    pub(super) fn new(budget_bytes: usize) -> Self {
+        Self {
+            entries: HashMap::default(),
+            queue: BTreeMap::new(),
+            used_bytes: 0,
+            budget_bytes,
+            clock: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: 0,
+        }
+    }
+
+    fn tick(&self) -> u64 {
+        self.clock.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Returns the value for `key`, marking it as the most recently
+    /// used entry, and counting the lookup as a hit or miss.
+    pub(super) fn get(&self, key: &K) -> Option<&V> {
+        if let Some(entry) = self.entries.get(key) {
+            entry.last_used.store(self.tick(), Ordering::Relaxed);
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            Some(&entry.value)
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            None
+        }
+    }
+
+    /// Returns the value for `key`, without affecting its recency or
+    /// the hit/miss statistics
+    pub(super) fn peek(&self, key: &K) -> Option<&V> {
+        self.entries.get(key).map(|entry| &entry.value)
+    }
+
+    /// Insert a value, evicting older entries if the budget is
+    /// exceeded. The new entry is never evicted by its own insertion,
+    /// even if it's larger than the entire budget.
+    pub(super) fn insert(&mut self, key: K, value: V) {
+        let size = value.byte_size();
+        let tick = self.tick();
+
+        let entry = LruEntry {
+            value,
+            size,
+            last_used: AtomicU64::new(tick),
+            queued_at: tick,
+        };
+
+        if let Some(old) = self.entries.insert(key.clone(), entry) {
+            self.used_bytes -= old.size;
+            self.queue.remove(&old.queued_at);
+        }
+        self.used_bytes += size;
+        self.queue.insert(tick, key.clone());
+
+        self.evict_to_budget(Some(&key));
+    }
+
+    pub(super) fn retain(&mut self, mut f: impl FnMut(&K) -> bool) {
+        let mut removed = 0;
+        self.entries.retain(|k, entry| {
+            let keep = f(k);
+            if !keep {
+                removed += entry.size;
+            }
+            keep
+        });
+        self.used_bytes -= removed;
+
+        let entries = &self.entries;
+        self.queue.retain(|_, k| entries.contains_key(k));
+    }
+
+    pub(super) fn set_budget(&mut self, budget_bytes: usize) {
+        self.budget_bytes = budget_bytes;
+        self.evict_to_budget(None);
+    }
+
+    pub(super) fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            evictions: self.evictions,
+            entries: self.entries.len(),
+            used_bytes: self.used_bytes,
+            budget_bytes: self.budget_bytes,
+        }
+    }
+
+    fn evict_to_budget(&mut self, keep: Option<&K>) {
+        while self.used_bytes > self.budget_bytes {
+            let Some((queued_at, key)) = self.queue.pop_first() else {
+                break;
+            };
+
+            let entry = self.entries.get_mut(&key).unwrap();
+            let last_used = entry.last_used.load(Ordering::Relaxed);
+
+            // the kept entry is the newest, so everything else is gone
+            if Some(&key) == keep {
+                self.queue.insert(queued_at, key);
+                break;
+            }
+
+            // used since it was queued, so requeue it at its last use
+            if last_used != queued_at {
+                entry.queued_at = last_used;
+                self.queue.insert(last_used, key);
+                continue;
+            }
+
+            self.used_bytes -= entry.size;
+            self.entries.remove(&key);
+            self.evictions += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl ByteSize for Vec<u8> {
+        fn byte_size(&self) -> usize {
+            self.len()
+        }
+    }
+
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let mut cache: LruCache<u32, Vec<u8>> = LruCache::new(30);
+
+        cache.insert(0, vec![0; 10]);
+        cache.insert(1, vec![0; 10]);
+        cache.insert(2, vec![0; 10]);
+
+        // touch the oldest entry, so that 1 is evicted next
+        assert!(cache.get(&0).is_some());
+
+        cache.insert(3, vec![0; 10]);
+
+        assert!(cache.get(&1).is_none());
+        assert!(cache.get(&0).is_some());
+        assert!(cache.get(&2).is_some());
+        assert!(cache.get(&3).is_some());
+
+        let stats = cache.stats();
+        assert_eq!(stats.used_bytes, 30);
+        assert_eq!(stats.entries, 3);
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.hits, 4);
+        assert_eq!(stats.misses, 1);
+
+        // entries larger than the budget are kept until the next insert
+        cache.insert(4, vec![0; 50]);
+        assert_eq!(cache.stats().entries, 1);
+        assert!(cache.get(&4).is_some());
+
+        cache.set_budget(0);
+        assert_eq!(cache.stats().used_bytes, 0);
+    }
+}
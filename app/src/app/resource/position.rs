@@ -0,0 +1,182 @@
+//! Data sources describing where along each path a node lies, so that
+//! rearrangements show up as discontinuities in the color gradient.
+//!
+//! Nodes that a path visits multiple times get the mean over all of
+//! its visits; the graph-level data is the mean over the visits by all
+//! paths.
+
+use std::{collections::BTreeMap, sync::Arc};
+
+use waragraph_core::graph::{Node, PathId, PathIndex};
+
+use crate::color::ColorSchemeId;
+
+use super::{DataSourceMeta, GraphDataSources, ValueDomain};
+
+/// Calls `f` with the node, step index, and normalized position (the
+/// bp offset of the step divided by the path length) of each step on
+/// `path`
+pub fn for_each_path_step(
+    graph: &PathIndex,
+    path: PathId,
+    mut f: impl FnMut(Node, usize, f32),
+) {
+    let steps = &graph.path_steps[path.ix()];
+    let offsets = &graph.path_step_offsets[path.ix()];
+
+    let Some(last) = steps.last() else {
+        return;
+    };
+
+    let last_offset = offsets.max().unwrap_or_default();
+    let (_, last_len) = graph.node_offset_length(last.node());
+    let path_len = (last_offset + last_len.0).max(1) as f32;
+
+    for ((ix, step), offset) in steps.iter().enumerate().zip(offsets.iter()) {
+        f(step.node(), ix, offset as f32 / path_len);
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+struct Mean {
+    sum: f64,
+    count: u32,
+}
+
+impl Mean {
+    fn add(&mut self, v: f32) {
+        self.sum += v as f64;
+        self.count += 1;
+    }
+
+    fn get(&self) -> f32 {
+        if self.count == 0 {
+            0.0
+        } else {
+            (self.sum / self.count as f64) as f32
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum PositionMetric {
+    Position,
+    StepIndex,
+}
+
+impl PositionMetric {
+    fn value(&self, step_ix: usize, pos: f32) -> f32 {
+        match self {
+            PositionMetric::Position => pos,
+            PositionMetric::StepIndex => step_ix as f32,
+        }
+    }
+
+    fn path_data(&self, graph: &PathIndex, path: PathId) -> Vec<f32> {
+        // iterating the map in node order matches the path data layout
+        let mut node_means: BTreeMap<Node, Mean> = BTreeMap::default();
+
+        for_each_path_step(graph, path, |node, ix, pos| {
+            node_means.entry(node).or_default().add(self.value(ix, pos));
+        });
+
+        node_means.into_values().map(|m| m.get()).collect()
+    }
+
+    fn graph_data(&self, graph: &PathIndex) -> Vec<f32> {
+        let mut node_means = vec![Mean::default(); graph.node_count];
+
+        for &path in graph.path_names.left_values() {
+            for_each_path_step(graph, path, |node, ix, pos| {
+                node_means[node.ix()].add(self.value(ix, pos));
+            });
+        }
+
+        node_means.into_iter().map(|m| m.get()).collect()
+    }
+}
+
+/// Registers the "path_position" and "step_index" sources, as both
+/// graph and path data sources
+pub(super) fn register_position_sources(
+    sources: &mut GraphDataSources,
+    graph: &Arc<PathIndex>,
+    gradient: ColorSchemeId,
+) {
+    let metrics = [
+        (
+            PositionMetric::Position,
+            DataSourceMeta {
+                name: "path_position".to_string(),
+                description: "Offset of each node along the path, divided \
+                              by the path length"
+                    .to_string(),
+                unit: None,
+                value_domain: ValueDomain::Fraction,
+                default_color_scheme: gradient,
+            },
+        ),
+        (
+            PositionMetric::StepIndex,
+            DataSourceMeta {
+                name: "step_index".to_string(),
+                description: "Index of the path step on each node".to_string(),
+                unit: Some("steps".to_string()),
+                value_domain: ValueDomain::Count,
+                default_color_scheme: gradient,
+            },
+        ),
+    ];
+
+    for (metric, meta) in metrics {
+        let id = sources.registry.register(meta);
+
+        {
+            let graph = graph.clone();
+            let ctor = move || Ok(metric.graph_data(&graph));
+            sources.graph_f32.insert(id, Arc::new(ctor));
+        }
+
+        {
+            let graph = graph.clone();
+            let ctor = move |path: PathId| Ok(metric.path_data(&graph, path));
+            sources.path_f32.insert(id, Arc::new(ctor));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_graph;
+
+    #[test]
+    fn path_positions() -> anyhow::Result<()> {
+        let graph = test_graph();
+
+        for &path in graph.path_names.left_values() {
+            let mut steps = 0;
+            let mut prev_pos = -1.0;
+
+            for_each_path_step(&graph, path, |_node, ix, pos| {
+                assert_eq!(ix, steps);
+                assert!(pos > prev_pos && pos < 1.0);
+                steps += 1;
+                prev_pos = pos;
+            });
+
+            assert_eq!(steps, graph.path_steps[path.ix()].len());
+
+            let data = PositionMetric::Position.path_data(&graph, path);
+            assert_eq!(
+                data.len() as u64,
+                graph.path_node_sets[path.ix()].len()
+            );
+        }
+
+        let step_ix = PositionMetric::StepIndex.graph_data(&graph);
+        assert_eq!(step_ix.len(), graph.node_count);
+
+        Ok(())
+    }
+}
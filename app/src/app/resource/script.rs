@@ -0,0 +1,550 @@
+//! Data sources defined as rhai expressions over other data sources,
+//! e.g. `depth / path_count`, or `if strand > 0.5 { 1 } else { 0 }`.
+//!
+//! The expression is evaluated once per node. Any registered data
+//! source can be used as a variable holding the node's value, along
+//! with:
+//!
+//! - `node`: the node index
+//! - `len`: the node length, in bp
+//! - `seq`: the node sequence, as a string
+//! - `node_count`, `path_count`
+//! - `path`: the path index, when evaluated as a path data source
+//!
+//! The sequence metrics in [`super::sequence`] are also available as
+//! functions taking a sequence, e.g. `gc_fraction(seq)`.
+
+use std::{
+    collections::BTreeSet,
+    path::Path,
+    sync::{Arc, Weak},
+};
+
+use anyhow::{anyhow, Context};
+use rhai::{ImmutableString, INT};
+use waragraph_core::graph::{Node, PathId, PathIndex};
+
+use crate::{
+    app::{
+        settings_menu::{
+            SettingsUiContext, SettingsUiResponse, SettingsWidget,
+        },
+        SharedState,
+    },
+    color::ColorSchemeId,
+};
+
+use super::{
+    sequence, DataSourceId, DataSourceMeta, FStats, GraphData, GraphDataCache,
+    GraphPathData, ValueDomain,
+};
+
+type SequenceFn = fn(&[u8]) -> f32;
+
+fn script_engine() -> rhai::Engine {
+    let mut engine = rhai::Engine::new();
+
+    let seq_fns: [(&str, SequenceFn); 5] = [
+        ("gc_fraction", sequence::gc_fraction),
+        ("ambiguous_fraction", sequence::ambiguous_fraction),
+        ("longest_homopolymer", sequence::longest_homopolymer),
+        ("cpg_density", sequence::cpg_density),
+        ("dust_score", sequence::dust_score),
+    ];
+
+    for (name, f) in seq_fns {
+        engine.register_fn(name, move |seq: ImmutableString| f(seq.as_bytes()));
+    }
+
+    engine
+}
+
+/// The words in `expr` that could be variable names
+fn identifiers(expr: &str) -> BTreeSet<&str> {
+    expr.split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .filter(|s| is_identifier(s))
+        .collect()
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .map(|c| c.is_ascii_alphabetic() || c == '_')
+        .unwrap_or(false)
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The data used for one of the variables in a script
+enum Input {
+    Graph(Arc<GraphData<f32, FStats>>),
+    Path(Arc<GraphPathData<f32, FStats>>),
+}
+
+impl Input {
+    /// `ix` is the index of the node in the data being computed, i.e.
+    /// the node index for graph data, or the index among the unique
+    /// nodes of the path for path data
+    fn value(&self, ix: usize, node: Node) -> f32 {
+        match self {
+            Input::Graph(data) => data.node_data[node.ix()],
+            Input::Path(data) => data.path_data[ix],
+        }
+    }
+}
+
+struct CompiledScript {
+    graph: Arc<PathIndex>,
+    ast: rhai::AST,
+    /// The data sources used in the script, bound to variables with
+    /// the same names
+    input_names: Vec<String>,
+    input_ids: Vec<DataSourceId>,
+    uses_seq: bool,
+}
+
+impl CompiledScript {
+    fn compile(
+        cache: &GraphDataCache,
+        name: &str,
+        expr: &str,
+    ) -> anyhow::Result<Self> {
+        let ast = script_engine()
+            .compile(expr)
+            .map_err(|e| anyhow!("error compiling `{name}`: {e}"))?;
+
+        let words = identifiers(expr);
+
+        // a source can't refer to itself
+        let (input_names, input_ids) = words
+            .iter()
+            .filter(|&&word| word != name)
+            .filter_map(|&word| {
+                let id = cache.data_source_id(word).ok()?;
+                (cache.has_graph_data(id) || cache.has_path_data(id))
+                    .then(|| (word.to_string(), id))
+            })
+            .unzip();
+
+        Ok(Self {
+            graph: cache.graph.clone(),
+            ast,
+            input_names,
+            input_ids,
+            uses_seq: words.contains("seq"),
+        })
+    }
+
+    fn eval(
+        &self,
+        nodes: impl Iterator<Item = (usize, Node)>,
+        inputs: &[Input],
+        path: Option<PathId>,
+    ) -> anyhow::Result<Vec<f32>> {
+        let graph = &self.graph;
+        let engine = script_engine();
+
+        let mut scope = rhai::Scope::new();
+        scope.push_constant("node_count", graph.node_count as INT);
+        scope.push_constant("path_count", graph.path_names.len() as INT);
+        if let Some(path) = path {
+            scope.push_constant("path", path.ix() as INT);
+        }
+
+        let base_len = scope.len();
+
+        nodes
+            .map(|(ix, node)| {
+                scope.rewind(base_len);
+
+                scope.push("node", node.ix() as INT);
+                scope.push("len", graph.node_length(node).0 as INT);
+
+                for (name, input) in self.input_names.iter().zip(inputs) {
+                    scope.push(name.as_str(), input.value(ix, node));
+                }
+
+                if self.uses_seq {
+                    let seq = graph.node_sequence(node);
+                    let seq = String::from_utf8_lossy(seq).into_owned();
+                    scope.push("seq", seq);
+                }
+
+                let value = engine
+                    .eval_ast_with_scope::<rhai::Dynamic>(&mut scope, &self.ast)
+                    .map_err(|e| anyhow!("error at node {}: {e}", node.ix()))?;
+
+                value
+                    .as_float()
+                    .or_else(|_| value.as_int().map(|v| v as f32))
+                    .or_else(|_| value.as_bool().map(|v| v as u8 as f32))
+                    .map_err(|ty| {
+                        anyhow!("script returned `{ty}`, expected a number")
+                    })
+            })
+            .collect()
+    }
+}
+
+// the descriptions of script sources, which tell them apart from the
+// other data sources
+const SCRIPT_DESCRIPTION_PREFIX: &str = "Script: ";
+
+/// Registers the rhai expression `expr` as the graph and path data
+/// source `name`. The expression is compiled right away, but only
+/// evaluated when the data is first used, as with other data sources.
+///
+/// Only data sources that exist when the script is registered can be
+/// used in the expression. A script can replace an earlier script with
+/// the same name, but not any other kind of data source, and not with
+/// an expression using sources computed from the script, which would
+/// make it depend on itself. The cached data of the sources computed
+/// from a replaced script is dropped.
+pub fn register_script_source(
+    cache: &Arc<GraphDataCache>,
+    name: &str,
+    expr: &str,
+    color_scheme: ColorSchemeId,
+) -> anyhow::Result<DataSourceId> {
+    if !is_identifier(name) {
+        anyhow::bail!("`{name}` is not a valid data source name");
+    }
+
+    let existing = cache.data_source_id(name).ok();
+
+    if let Some(id) = existing {
+        let meta = cache.data_source_meta(id);
+        if !meta.description.starts_with(SCRIPT_DESCRIPTION_PREFIX) {
+            anyhow::bail!("`{name}` is already used by another data source");
+        }
+    }
+
+    let script = Arc::new(CompiledScript::compile(cache, name, expr)?);
+
+    if let Some(id) = existing {
+        let cycle = script
+            .input_ids
+            .iter()
+            .find(|&&input| cache.depends_on(input, id));
+
+        if let Some(&input) = cycle {
+            let input = &cache.data_source_meta(input).name;
+            anyhow::bail!("`{name}` can't use `{input}`, which uses `{name}`");
+        }
+    }
+
+    let meta = DataSourceMeta {
+        name: name.to_string(),
+        description: format!("{SCRIPT_DESCRIPTION_PREFIX}{expr}"),
+        unit: None,
+        value_domain: ValueDomain::Continuous,
+        default_color_scheme: color_scheme,
+    };
+
+    let input_ids = script.input_ids.clone();
+
+    // the sources are owned by the cache, so they can't keep it alive
+    let weak_cache: Weak<GraphDataCache> = Arc::downgrade(cache);
+
+    {
+        let script = script.clone();
+        let weak_cache = weak_cache.clone();
+
+        cache.register_graph_f32(
+            meta.clone(),
+            Arc::new(move || {
+                let cache = weak_cache
+                    .upgrade()
+                    .ok_or_else(|| anyhow!("data cache has been dropped"))?;
+
+                let inputs = script
+                    .input_names
+                    .iter()
+                    .zip(&script.input_ids)
+                    .map(|(name, id)| {
+                        let data =
+                            cache.fetch_graph_data_blocking(*id).ok_or_else(
+                                || anyhow!("`{name}` has no graph data"),
+                            )?;
+                        Ok(Input::Graph(data))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+
+                let nodes =
+                    (0..script.graph.node_count).map(|ix| (ix, Node::from(ix)));
+
+                script.eval(nodes, &inputs, None)
+            }),
+        );
+    }
+
+    let id = cache.register_path_f32(
+        meta,
+        Arc::new(move |path: PathId| {
+            let cache = weak_cache
+                .upgrade()
+                .ok_or_else(|| anyhow!("data cache has been dropped"))?;
+
+            // use the path data if the source has any, otherwise the
+            // graph data for the path's nodes
+            let inputs = script
+                .input_names
+                .iter()
+                .zip(&script.input_ids)
+                .map(|(name, id)| {
+                    let input = if cache.has_path_data(*id) {
+                        cache
+                            .fetch_path_data_blocking(*id, path)
+                            .map(Input::Path)
+                    } else {
+                        cache.fetch_graph_data_blocking(*id).map(Input::Graph)
+                    };
+                    input.ok_or_else(|| anyhow!("error loading `{name}`"))
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+
+            let nodes = script.graph.path_node_sets[path.ix()]
+                .iter()
+                .enumerate()
+                .map(|(ix, node)| (ix, Node::from(node)));
+
+            script.eval(nodes, &inputs, Some(path))
+        }),
+    );
+
+    cache.set_source_inputs(id, input_ids);
+
+    Ok(id)
+}
+
+/// Registers each of the scripted data sources in the file at
+/// `script_path`, which contains one `name = expression` definition
+/// per line. Empty lines and lines starting with `#` are ignored.
+pub fn load_script_file(
+    cache: &Arc<GraphDataCache>,
+    script_path: impl AsRef<Path>,
+    color_scheme: ColorSchemeId,
+) -> anyhow::Result<Vec<DataSourceId>> {
+    let script_path = script_path.as_ref();
+    let text = std::fs::read_to_string(script_path)?;
+
+    let mut ids = Vec::new();
+
+    for (line_ix, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let id = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected `name = expression`"))
+            .and_then(|(name, expr)| {
+                register_script_source(
+                    cache,
+                    name.trim(),
+                    expr.trim(),
+                    color_scheme,
+                )
+            })
+            .with_context(|| {
+                format!("{:?} line {}", script_path, line_ix + 1)
+            })?;
+
+        ids.push(id);
+    }
+
+    Ok(ids)
+}
+
+/// Settings widget for defining new scripted data sources
+pub struct ScriptSourceWidget {
+    shared: SharedState,
+
+    name: String,
+    expr: String,
+
+    // the result of the last registration attempt
+    status: Option<Result<String, String>>,
+}
+
+impl ScriptSourceWidget {
+    pub fn new(shared: SharedState) -> Self {
+        Self {
+            shared,
+            name: String::new(),
+            expr: String::new(),
+            status: None,
+        }
+    }
+}
+
+impl SettingsWidget for ScriptSourceWidget {
+    fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        _settings_ctx: &SettingsUiContext,
+    ) -> SettingsUiResponse {
+        let resp = ui.vertical(|ui| {
+            ui.horizontal(|ui| {
+                ui.label("Name");
+                ui.text_edit_singleline(&mut self.name);
+            });
+
+            ui.label("Expression");
+            ui.add(
+                egui::TextEdit::multiline(&mut self.expr)
+                    .code_editor()
+                    .desired_rows(3),
+            );
+
+            if ui.button("Register").clicked() {
+                let color_scheme = self
+                    .shared
+                    .colors
+                    .blocking_read()
+                    .get_color_scheme_id("spectral")
+                    .unwrap();
+
+                let result = register_script_source(
+                    &self.shared.graph_data_cache,
+                    self.name.trim(),
+                    self.expr.trim(),
+                    color_scheme,
+                );
+
+                self.status = Some(
+                    result
+                        .map(|_| format!("Registered `{}`", self.name.trim()))
+                        .map_err(|e| format!("{e:?}")),
+                );
+            }
+
+            match &self.status {
+                Some(Ok(msg)) => {
+                    ui.label(msg);
+                }
+                Some(Err(msg)) => {
+                    ui.colored_label(egui::Color32::RED, msg);
+                }
+                None => (),
+            }
+
+            ui.separator();
+
+            ui.label("Available data sources:");
+            let data_cache = &self.shared.graph_data_cache;
+            let names = data_cache
+                .path_data_sources()
+                .into_iter()
+                .chain(data_cache.graph_data_sources())
+                .map(|id| data_cache.data_source_meta(id).name.clone())
+                .collect::<BTreeSet<_>>();
+
+            ui.horizontal_wrapped(|ui| {
+                for name in names {
+                    ui.monospace(name);
+                }
+            });
+        });
+
+        SettingsUiResponse {
+            response: resp.response,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::test_graph;
+
+    #[test]
+    fn script_eval() -> anyhow::Result<()> {
+        assert_eq!(
+            identifiers("if strand > 0.5 { depth / 2 } else { 0 }"),
+            BTreeSet::from(["depth", "else", "if", "strand"])
+        );
+
+        let graph = test_graph();
+
+        let expr = "if depth > 1.0 { depth / path_count } else { len > 10 }";
+        let script = CompiledScript {
+            graph: graph.clone(),
+            ast: script_engine().compile(expr)?,
+            input_names: vec!["depth".to_string()],
+            input_ids: Vec::new(),
+            uses_seq: false,
+        };
+
+        let depth = [2.0, 1.0, 0.0];
+        let input = Input::Graph(Arc::new(GraphData {
+            node_data: depth.to_vec(),
+            stats: FStats::from_items(depth.into_iter()),
+        }));
+
+        let nodes = (0..3usize).map(|ix| (ix, Node::from(ix)));
+        let values = script.eval(nodes, &[input], None)?;
+
+        let path_count = graph.path_names.len() as f32;
+        let long = |ix: usize| graph.node_length(Node::from(ix)).0 > 10;
+
+        assert_eq!(values[0], 2.0 / path_count);
+        assert_eq!(values[1], long(1) as u8 as f32);
+        assert_eq!(values[2], long(2) as u8 as f32);
+
+        let script = CompiledScript {
+            graph: graph.clone(),
+            ast: script_engine().compile("gc_fraction(seq)")?,
+            input_names: Vec::new(),
+            input_ids: Vec::new(),
+            uses_seq: true,
+        };
+        let values =
+            script.eval([(0, Node::from(0usize))].into_iter(), &[], None)?;
+        assert_eq!(
+            values[0],
+            sequence::gc_fraction(graph.node_sequence(Node::from(0usize)))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn script_redefinition() -> anyhow::Result<()> {
+        let graph = test_graph();
+        let cache =
+            Arc::new(GraphDataCache::with_sources(&graph, Default::default()));
+
+        let node_count = graph.node_count;
+        cache.register_graph_f32(
+            DataSourceMeta {
+                name: "ones".to_string(),
+                description: String::new(),
+                unit: None,
+                value_domain: ValueDomain::Continuous,
+                default_color_scheme: ColorSchemeId::default(),
+            },
+            Arc::new(move || Ok(vec![1.0; node_count])),
+        );
+
+        let scheme = ColorSchemeId::default();
+        let a = register_script_source(&cache, "a", "ones + 1", scheme)?;
+        let b = register_script_source(&cache, "b", "a * 2", scheme)?;
+
+        let b_value = |cache: &GraphDataCache| {
+            cache.fetch_graph_data_blocking(b).unwrap().node_data[0]
+        };
+        assert_eq!(b_value(&cache), 4.0);
+
+        // `b` is computed from `a`, so `a` can't use `b`
+        assert!(cache.depends_on(b, a));
+        assert!(register_script_source(&cache, "a", "b", scheme).is_err());
+
+        // replacing `a` drops the cached data of `b`
+        register_script_source(&cache, "a", "ones + 2", scheme)?;
+        assert_eq!(b_value(&cache), 6.0);
+
+        Ok(())
+    }
+}
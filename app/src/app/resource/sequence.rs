@@ -0,0 +1,268 @@
+//! Node data sources derived from the node sequences, useful for
+//! spotting assembly artifacts and repeats.
+
+use std::sync::Arc;
+
+use waragraph_core::graph::{Node, PathId, PathIndex};
+
+use crate::color::ColorSchemeId;
+
+use super::{DataSourceMeta, GraphDataSources, ValueDomain};
+
+/// Fraction of G and C among the unambiguous (ACGT) bases
+pub fn gc_fraction(seq: &[u8]) -> f32 {
+    let mut gc = 0usize;
+    let mut acgt = 0usize;
+
+    for &b in seq {
+        match b.to_ascii_uppercase() {
+            b'G' | b'C' => {
+                gc += 1;
+                acgt += 1;
+            }
+            b'A' | b'T' => acgt += 1,
+            _ => (),
+        }
+    }
+
+    if acgt == 0 {
+        0.0
+    } else {
+        gc as f32 / acgt as f32
+    }
+}
+
+/// Fraction of bases that are N or another ambiguous base
+pub fn ambiguous_fraction(seq: &[u8]) -> f32 {
+    if seq.is_empty() {
+        return 0.0;
+    }
+
+    let ambiguous = seq
+        .iter()
+        .filter(|b| {
+            !matches!(b.to_ascii_uppercase(), b'A' | b'C' | b'G' | b'T')
+        })
+        .count();
+
+    ambiguous as f32 / seq.len() as f32
+}
+
+/// Length of the longest run of a single base
+pub fn longest_homopolymer(seq: &[u8]) -> f32 {
+    let mut longest = 0usize;
+    let mut run = 0usize;
+    let mut prev = None;
+
+    for &b in seq {
+        let b = b.to_ascii_uppercase();
+        if prev == Some(b) {
+            run += 1;
+        } else {
+            run = 1;
+            prev = Some(b);
+        }
+        longest = longest.max(run);
+    }
+
+    longest as f32
+}
+
+/// Number of CpG dinucleotides per base
+pub fn cpg_density(seq: &[u8]) -> f32 {
+    if seq.len() < 2 {
+        return 0.0;
+    }
+
+    let cpg = seq
+        .windows(2)
+        .filter(|w| w.eq_ignore_ascii_case(b"CG"))
+        .count();
+
+    cpg as f32 / seq.len() as f32
+}
+
+const DUST_WINDOW: usize = 64;
+
+/// DUST low-complexity score, i.e. the maximum over windows of 64 bp
+/// of sum(c_t * (c_t - 1) / 2) / (l - 1), where c_t is the count of
+/// triplet t in the window, and l the number of triplets.
+///
+/// Random sequence scores close to zero, while e.g. a homopolymer
+/// scores l / 2 = 31 for a full window.
+pub fn dust_score(seq: &[u8]) -> f32 {
+    if seq.len() < 4 {
+        return 0.0;
+    }
+
+    let triplet = |w: &[u8]| -> Option<usize> {
+        let mut t = 0;
+        for &b in w {
+            let v = match b.to_ascii_uppercase() {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' => 3,
+                _ => return None,
+            };
+            t = (t << 2) | v;
+        }
+        Some(t)
+    };
+
+    let window_score = |window: &[u8]| -> f32 {
+        let mut counts = [0u32; 64];
+        let mut triplets = 0u32;
+
+        for w in window.windows(3) {
+            if let Some(t) = triplet(w) {
+                counts[t] += 1;
+                triplets += 1;
+            }
+        }
+
+        if triplets < 2 {
+            return 0.0;
+        }
+
+        let sum: u32 =
+            counts.iter().map(|&c| c * c.saturating_sub(1) / 2).sum();
+        sum as f32 / (triplets - 1) as f32
+    };
+
+    if seq.len() <= DUST_WINDOW {
+        return window_score(seq);
+    }
+
+    let step = DUST_WINDOW / 2;
+    let last_start = seq.len() - DUST_WINDOW;
+
+    (0..=last_start)
+        .step_by(step)
+        .chain(std::iter::once(last_start))
+        .map(|start| window_score(&seq[start..start + DUST_WINDOW]))
+        .fold(0.0, f32::max)
+}
+
+struct SequenceMetric {
+    name: &'static str,
+    description: &'static str,
+    unit: Option<&'static str>,
+    value_domain: ValueDomain,
+    color_scheme: ColorSchemeId,
+    metric: fn(&[u8]) -> f32,
+}
+
+/// Registers the sequence-derived sources as both graph and path data
+/// sources; the path data for a node is the same as the graph data.
+pub(super) fn register_sequence_sources(
+    sources: &mut GraphDataSources,
+    graph: &Arc<PathIndex>,
+    spectral: ColorSchemeId,
+    black_red: ColorSchemeId,
+) {
+    let metrics = [
+        SequenceMetric {
+            name: "gc_content",
+            description: "Fraction of G and C among the unambiguous bases \
+                          of each node",
+            unit: None,
+            value_domain: ValueDomain::Fraction,
+            color_scheme: spectral,
+            metric: gc_fraction,
+        },
+        SequenceMetric {
+            name: "ambiguous_bases",
+            description: "Fraction of N or other ambiguous bases in each node",
+            unit: None,
+            value_domain: ValueDomain::Fraction,
+            color_scheme: black_red,
+            metric: ambiguous_fraction,
+        },
+        SequenceMetric {
+            name: "longest_homopolymer",
+            description: "Length of the longest single-base run in each node",
+            unit: Some("bp"),
+            value_domain: ValueDomain::Count,
+            color_scheme: spectral,
+            metric: longest_homopolymer,
+        },
+        SequenceMetric {
+            name: "low_complexity",
+            description: "DUST low-complexity score of each node (highest \
+                          over 64 bp windows)",
+            unit: None,
+            value_domain: ValueDomain::Continuous,
+            color_scheme: spectral,
+            metric: dust_score,
+        },
+        SequenceMetric {
+            name: "cpg_density",
+            description: "CpG dinucleotides per base in each node",
+            unit: Some("CpG/bp"),
+            value_domain: ValueDomain::Continuous,
+            color_scheme: spectral,
+            metric: cpg_density,
+        },
+    ];
+
+    for seq_metric in metrics {
+        let id = sources.registry.register(DataSourceMeta {
+            name: seq_metric.name.to_string(),
+            description: seq_metric.description.to_string(),
+            unit: seq_metric.unit.map(String::from),
+            value_domain: seq_metric.value_domain,
+            default_color_scheme: seq_metric.color_scheme,
+        });
+
+        let metric = seq_metric.metric;
+
+        {
+            let graph = graph.clone();
+            let ctor = move || {
+                Ok((0..graph.node_count)
+                    .map(|ix| metric(graph.node_sequence(Node::from(ix))))
+                    .collect())
+            };
+            sources.graph_f32.insert(id, Arc::new(ctor));
+        }
+
+        {
+            let graph = graph.clone();
+            let ctor = move |path: PathId| {
+                Ok(graph.path_node_sets[path.ix()]
+                    .iter()
+                    .map(|node| metric(graph.node_sequence(Node::from(node))))
+                    .collect())
+            };
+            sources.path_f32.insert(id, Arc::new(ctor));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequence_metrics() {
+        assert_eq!(gc_fraction(b"ACGTNNgc"), 4.0 / 6.0);
+        assert_eq!(gc_fraction(b"NNNN"), 0.0);
+
+        assert_eq!(ambiguous_fraction(b"ACGTNNRY"), 0.5);
+
+        assert_eq!(longest_homopolymer(b"ACaaaAGT"), 4.0);
+        assert_eq!(longest_homopolymer(b""), 0.0);
+
+        assert_eq!(cpg_density(b"CGCGAT"), 2.0 / 6.0);
+
+        let homopolymer = [b'A'; 64];
+        assert_eq!(dust_score(&homopolymer), 31.0);
+
+        let dinucleotide = b"AC".repeat(32);
+        assert!(dust_score(&dinucleotide) > 10.0);
+
+        let mixed = b"ACGTTGCAAGCTTCGAGATCCTAGGCATCGATGCCATGGTACAGTCTGAACTTGACGGTATCCAG";
+        assert!(dust_score(mixed) < 1.0);
+    }
+}
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+
+use egui::epaint::ahash::HashMap;
+use waragraph_core::graph::{PathId, PathIndex};
+
+use crate::color::ColorSchemeId;
+
+/// Handle to a data source registered in a `GraphDataCache`.
+///
+/// Data source IDs can only be created by registering a data source,
+/// so everything that's keyed on them (samplers, viz. modes, color
+/// schemes, slots) refers to a source that exists; string names are
+/// only resolved at registration time, where unknown names are
+/// errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DataSourceId(u32);
+
+impl std::fmt::Display for DataSourceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "DataSource#{}", self.0)
+    }
+}
+
+/// The kind of values a data source produces, used to pick sensible
+/// defaults (e.g. the color map value range) when visualizing it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueDomain {
+    /// Arbitrary real values
+    Continuous,
+    /// Non-negative counts, such as depth
+    Count,
+    /// Values in [0, 1]
+    Fraction,
+    /// Values that identify categories, rather than quantities
+    Categorical,
+    /// A fixed number of categories, labeled 0, 1, .., n-1, meant to
+    /// be used with a color scheme with (at least) n colors
+    Classes(u32),
+}
+
+impl ValueDomain {
+    /// The value range that's always correct for this domain, if any
+    pub fn fixed_range(&self) -> Option<[f32; 2]> {
+        match self {
+            ValueDomain::Fraction => Some([0.0, 1.0]),
+            ValueDomain::Classes(n) => Some([0.0, n.saturating_sub(1) as f32]),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ValueDomain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueDomain::Continuous => write!(f, "continuous"),
+            ValueDomain::Count => write!(f, "count"),
+            ValueDomain::Fraction => write!(f, "fraction"),
+            ValueDomain::Categorical => write!(f, "categorical"),
+            ValueDomain::Classes(n) => write!(f, "{n} classes"),
+        }
+    }
+}
+
+/// A node data source that can be plugged in at runtime, e.g. by
+/// crates that use waragraph as a library to add their own tracks,
+/// using `SharedState::register_data_source`.
+///
+/// Graph data has one value per node in the graph, while path data
+/// has one value per unique node on the path, ordered by node ID.
+pub trait DataSource: Send + Sync + 'static {
+    /// The name of the source, which must be unique; registering a
+    /// source with the name of an existing source replaces it
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str {
+        ""
+    }
+
+    fn unit(&self) -> Option<&str> {
+        None
+    }
+
+    fn value_domain(&self) -> ValueDomain;
+
+    /// The name of the color scheme to use by default, which falls
+    /// back to "spectral" if there's no such scheme
+    fn color_scheme(&self) -> &str {
+        "spectral"
+    }
+
+    fn graph_f32(&self, graph: &PathIndex) -> anyhow::Result<Vec<f32>>;
+
+    /// Whether the source provides path data, through `path_f32`
+    fn has_path_data(&self) -> bool {
+        false
+    }
+
+    fn path_f32(
+        &self,
+        _graph: &PathIndex,
+        _path: PathId,
+    ) -> anyhow::Result<Vec<f32>> {
+        anyhow::bail!("Data source `{}` has no path data", self.name())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DataSourceMeta {
+    pub name: String,
+    pub description: String,
+    pub unit: Option<String>,
+    pub value_domain: ValueDomain,
+    pub default_color_scheme: ColorSchemeId,
+}
+
+/// Maps data source names to IDs, and IDs to their metadata
+#[derive(Default)]
+pub(super) struct DataSourceRegistry {
+    name_ids: HashMap<String, DataSourceId>,
+    meta: Vec<Arc<DataSourceMeta>>,
+}
+
+impl DataSourceRegistry {
+    /// Registers the metadata, returning the ID for the data source.
+    ///
+    /// Re-registering a name replaces its metadata, but keeps the ID.
+    pub(super) fn register(&mut self, meta: DataSourceMeta) -> DataSourceId {
+        if let Some(&id) = self.name_ids.get(&meta.name) {
+            self.meta[id.0 as usize] = Arc::new(meta);
+            return id;
+        }
+
+        let id = DataSourceId(self.meta.len() as u32);
+        self.name_ids.insert(meta.name.clone(), id);
+        self.meta.push(Arc::new(meta));
+        id
+    }
+
+    /// Removes the name of the data source, so that it's no longer
+    /// listed, and registering the name again gives a new ID. The
+    /// metadata is kept, as the old ID may still be in use.
+    pub(super) fn unregister(&mut self, id: DataSourceId) {
+        let name = &self.meta[id.0 as usize].name;
+
+        if self.name_ids.get(name) == Some(&id) {
+            self.name_ids.remove(name);
+        }
+    }
+
+    pub(super) fn id(&self, name: &str) -> Option<DataSourceId> {
+        self.name_ids.get(name).copied()
+    }
+
+    /// The IDs of the registered data sources, in no particular order
+    pub(super) fn ids(&self) -> impl Iterator<Item = DataSourceId> + '_ {
+        self.name_ids.values().copied()
+    }
+
+    pub(super) fn meta(&self, id: DataSourceId) -> &Arc<DataSourceMeta> {
+        // IDs can only be created by registering a source, so this
+        // can only fail if an ID from another registry is used
+        &self.meta[id.0 as usize]
+    }
+}
@@ -41,7 +41,7 @@ pub struct SettingsWindow {
 
     active_tab: Option<String>,
 
-    ctx: SettingsUiContext,
+    ctx: Arc<SettingsUiContext>,
 }
 
 impl SettingsWindow {
@@ -52,10 +52,22 @@ impl SettingsWindow {
         Self {
             tabs: BTreeMap::default(),
             active_tab: None,
-            ctx: SettingsUiContext::new(tokio_handle, app_msg_send),
+            ctx: Arc::new(SettingsUiContext::new(tokio_handle, app_msg_send)),
         }
     }
 
+    /// The context passed to the settings widgets, which can also be
+    /// used to open file dialogs in the viewer windows
+    pub fn ui_context(&self) -> Arc<SettingsUiContext> {
+        self.ctx.clone()
+    }
+
+    /// Shows the file dialogs opened in the window with the egui
+    /// context `ctx`, see `SettingsUiContext::with_window_file_dialog_oneshot`
+    pub fn show_window_file_dialogs(&self, ctx: &egui::Context) {
+        self.ctx.process_file_dialogs(ctx, Some(ctx));
+    }
+
     pub fn register_widget(
         &mut self,
         tab_name: &str,
@@ -90,7 +102,7 @@ impl SettingsWindow {
     }
 
     pub fn show(&mut self, ctx: &egui::Context) {
-        self.process_file_dialogs(ctx);
+        self.ctx.process_file_dialogs(ctx, None);
 
         self.validate_active_tab();
 
@@ -150,21 +162,37 @@ impl SettingsWindow {
     }
 }
 
-impl SettingsWindow {
-    fn process_file_dialogs(&mut self, ctx: &egui::Context) {
-        let mut lock = self.ctx.file_dialogs.lock();
+impl SettingsUiContext {
+    // shows the dialogs opened in `window`, or in the settings window
+    // if `None`
+    fn process_file_dialogs(
+        &self,
+        ctx: &egui::Context,
+        window: Option<&egui::Context>,
+    ) {
+        let mut lock = self.file_dialogs.lock();
 
         let mut done = Vec::new();
 
         for (id, dialog) in lock.iter_mut() {
-            if dialog.dialog.show(ctx).selected() {
+            if dialog.window.as_ref() != window {
+                continue;
+            }
+
+            // cancelled dialogs are also removed, closing the
+            // oneshot channel
+            if !dialog.dialog.show(ctx).visible() {
                 done.push(*id);
             }
         }
 
         for id in done {
             if let Some(dialog) = lock.remove(&id) {
-                let path = dialog.dialog.path();
+                let path = dialog
+                    .dialog
+                    .selected()
+                    .then(|| dialog.dialog.path())
+                    .flatten();
                 (dialog.callback)(path);
             }
         }
@@ -173,6 +201,8 @@ impl SettingsWindow {
 
 struct FileDialogState {
     dialog: egui_file::FileDialog,
+    // the window the dialog is shown in, if not the settings window
+    window: Option<egui::Context>,
     callback: Box<dyn FnOnce(Option<PathBuf>) + Send + Sync + 'static>,
 }
 
@@ -207,6 +237,27 @@ impl SettingsUiContext {
         &self,
         id: egui::Id,
         dialog: egui_file::FileDialog,
+    ) -> oneshot::Receiver<PathBuf> {
+        self.add_file_dialog(id, dialog, None)
+    }
+
+    /// Like `with_file_dialog_oneshot`, but the dialog is shown in the
+    /// window with the egui context `ctx`, rather than the settings
+    /// window, for opening files from the viewers' own GUIs
+    pub fn with_window_file_dialog_oneshot(
+        &self,
+        ctx: &egui::Context,
+        id: egui::Id,
+        dialog: egui_file::FileDialog,
+    ) -> oneshot::Receiver<PathBuf> {
+        self.add_file_dialog(id, dialog, Some(ctx.clone()))
+    }
+
+    fn add_file_dialog(
+        &self,
+        id: egui::Id,
+        dialog: egui_file::FileDialog,
+        window: Option<egui::Context>,
     ) -> oneshot::Receiver<PathBuf> {
         let (send, recv) = oneshot::channel::<PathBuf>();
 
@@ -218,6 +269,7 @@ impl SettingsUiContext {
 
         let state = FileDialogState {
             dialog,
+            window,
             callback: Box::new(f),
         };
 
@@ -11,7 +11,7 @@ pub mod util;
 pub mod widget;
 
 #[derive(
-    Clone, Copy, PartialEq, PartialOrd, bytemuck::Pod, bytemuck::Zeroable,
+    Debug, Clone, Copy, PartialEq, PartialOrd, bytemuck::Pod, bytemuck::Zeroable,
 )]
 #[repr(C)]
 pub struct ColorMap {
@@ -19,7 +19,7 @@ pub struct ColorMap {
     pub color_range: [f32; 2],
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ColorSchemeId(usize);
 
 pub struct ColorStore {
@@ -79,6 +79,72 @@ fn create_nearest_sampler(device: &wgpu::Device) -> wgpu::Sampler {
     device.create_sampler(&sampler_desc)
 }
 
+fn create_color_texture(
+    state: &raving_wgpu::State,
+    label: &str,
+    colors: &[[f32; 4]],
+) -> (wgpu::Texture, wgpu::TextureView) {
+    let dimension = wgpu::TextureDimension::D1;
+    let format = wgpu::TextureFormat::Rgba8Unorm;
+
+    let usage =
+        wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST;
+
+    let pixel_data: Vec<_> = colors
+        .iter()
+        .map(|&[r, g, b, a]| {
+            [
+                (r * 255.0) as u8,
+                (g * 255.0) as u8,
+                (b * 255.0) as u8,
+                (a * 255.0) as u8,
+            ]
+        })
+        .collect();
+
+    let width = colors.len() as u32;
+
+    let size = wgpu::Extent3d {
+        width,
+        height: 1,
+        depth_or_array_layers: 1,
+    };
+
+    let texture_label = format!("Texture - {label}");
+
+    let texture_desc = wgpu::TextureDescriptor {
+        label: Some(&texture_label),
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension,
+        format,
+        usage,
+        view_formats: &[],
+    };
+
+    let texture = state.device.create_texture_with_data(
+        &state.queue,
+        &texture_desc,
+        bytemuck::cast_slice(&pixel_data),
+    );
+
+    let view_label = format!("Texture View - {label}");
+
+    let view = texture.create_view(&wgpu::TextureViewDescriptor {
+        label: Some(&view_label),
+        format: Some(format),
+        dimension: Some(wgpu::TextureViewDimension::D1),
+        aspect: wgpu::TextureAspect::All,
+        base_mip_level: 0,
+        mip_level_count: None,
+        base_array_layer: 0,
+        array_layer_count: None,
+    });
+
+    (texture, view)
+}
+
 impl ColorStore {
     pub fn get_color_scheme_id(&self, name: &str) -> Option<ColorSchemeId> {
         self.scheme_name_map.get_by_left(name).copied()
@@ -94,6 +160,15 @@ impl ColorStore {
         &self.color_schemes[id.0]
     }
 
+    /// The names and IDs of all color schemes, sorted by name
+    pub fn color_schemes(
+        &self,
+    ) -> impl Iterator<Item = (&str, ColorSchemeId)> + '_ {
+        self.scheme_name_map
+            .iter()
+            .map(|(name, id)| (name.as_str(), *id))
+    }
+
     pub fn init(state: &raving_wgpu::State) -> Self {
         let linear_sampler = Arc::new(create_linear_sampler(&state.device));
         let nearest_sampler = Arc::new(create_nearest_sampler(&state.device));
@@ -132,7 +207,7 @@ impl ColorStore {
 
         spectral.extend(spectral_colors);
 
-        result.add_color_scheme("spectral", spectral);
+        result.add_color_scheme(state, "spectral", spectral);
 
         let black_red = (0..8).map(|i: i32| {
             // for i = 8 this is 255, which is what we want
@@ -140,82 +215,70 @@ impl ColorStore {
             rgba(r as u8, 0, 0)
         });
 
-        result.add_color_scheme("black_red", black_red);
+        result.add_color_scheme(state, "black_red", black_red);
+
+        // a perceptually uniform gradient, for data where the order of
+        // the values matters, such as positions along paths
+        let viridis = [
+            rgba(68, 1, 84),
+            rgba(72, 40, 120),
+            rgba(62, 74, 137),
+            rgba(49, 104, 142),
+            rgba(38, 130, 142),
+            rgba(31, 158, 137),
+            rgba(53, 183, 121),
+            rgba(109, 205, 89),
+            rgba(180, 222, 44),
+            rgba(253, 231, 37),
+        ];
+
+        result.add_color_scheme(state, "viridis", viridis);
+
+        // for the `path_loops` data source: nodes visited once, visited
+        // several times in a row, and revisited after a loop
+        let loop_classes =
+            [rgba(210, 210, 210), rgba(49, 130, 189), rgba(222, 45, 38)];
+
+        result.add_color_scheme(state, "loop_classes", loop_classes);
 
         result
     }
 
-    pub fn create_color_scheme_texture(
+    fn create_color_scheme_texture(
         &mut self,
         state: &raving_wgpu::State,
-        scheme_name: &str,
+        scheme_id: ColorSchemeId,
     ) {
-        // create texture & texture view
-        let scheme_id = *self.scheme_name_map.get_by_left(scheme_name).unwrap();
-
+        let scheme_name = self.get_scheme_name(scheme_id);
         let color_scheme = &self.color_schemes[scheme_id.0];
 
-        let dimension = wgpu::TextureDimension::D1;
-        let format = wgpu::TextureFormat::Rgba8Unorm;
-
-        let label = format!("Texture - Color Scheme {scheme_name}");
+        let texture_view = create_color_texture(
+            state,
+            &format!("Color Scheme {scheme_name}"),
+            &color_scheme.colors,
+        );
 
-        let usage = wgpu::TextureUsages::TEXTURE_BINDING
-            | wgpu::TextureUsages::COPY_DST;
+        self.scheme_textures
+            .insert(scheme_id, Arc::new(texture_view));
+    }
 
-        let pixel_data: Vec<_> = color_scheme
-            .colors
+    /// Creates a texture with the colors of each of the `schemes`, in
+    /// order, so that several color schemes can be used in a single
+    /// draw. Returns the texture and its width.
+    pub fn create_color_scheme_atlas(
+        &self,
+        state: &raving_wgpu::State,
+        schemes: &[ColorSchemeId],
+    ) -> (Arc<(wgpu::Texture, wgpu::TextureView)>, u32) {
+        let colors = schemes
             .iter()
-            .map(|&[r, g, b, a]| {
-                [
-                    (r * 255.0) as u8,
-                    (g * 255.0) as u8,
-                    (b * 255.0) as u8,
-                    (a * 255.0) as u8,
-                ]
-            })
-            .collect();
-
-        let width = color_scheme.colors.len() as u32;
-
-        let size = wgpu::Extent3d {
-            width,
-            height: 1,
-            depth_or_array_layers: 1,
-        };
+            .flat_map(|id| self.color_schemes[id.0].colors.iter().copied())
+            .collect::<Vec<_>>();
 
-        let texture_desc = wgpu::TextureDescriptor {
-            label: Some(&label),
-            size,
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension,
-            format,
-            usage,
-            view_formats: &[],
-        };
+        let texture_view =
+            create_color_texture(state, "Color Scheme Atlas", &colors);
 
-        let texture = state.device.create_texture_with_data(
-            &state.queue,
-            &texture_desc,
-            bytemuck::cast_slice(&pixel_data),
-        );
-
-        let label = format!("Texture View - Color Scheme {scheme_name}");
-
-        let view = texture.create_view(&wgpu::TextureViewDescriptor {
-            label: Some(&label),
-            format: Some(format),
-            dimension: Some(wgpu::TextureViewDimension::D1),
-            aspect: wgpu::TextureAspect::All,
-            base_mip_level: 0,
-            mip_level_count: None,
-            base_array_layer: 0,
-            array_layer_count: None,
-        });
-
-        self.scheme_textures
-            .insert(scheme_id, Arc::new((texture, view)));
+        (Arc::new(texture_view), colors.len() as u32)
     }
 
     pub fn get_color_scheme_texture(
@@ -269,8 +332,10 @@ impl ColorStore {
         Ok(())
     }
 
+    /// Adds a color scheme, and creates its texture
     pub fn add_color_scheme(
         &mut self,
+        state: &raving_wgpu::State,
         name: &str,
         colors: impl IntoIterator<Item = [f32; 4]>,
     ) -> ColorSchemeId {
@@ -284,6 +349,8 @@ impl ColorStore {
         self.scheme_name_map.insert(name.to_string(), id);
         self.color_schemes.push(scheme);
 
+        self.create_color_scheme_texture(state, id);
+
         id
     }
 }
@@ -15,7 +15,7 @@ pub struct ColorMapWidgetShared {
     colors: Arc<RwLock<ColorStore>>,
 
     id: egui::Id,
-    data_stats: FStats, // TODO: use stats to add reset value range button
+    data_stats: Option<FStats>,
     data_mode: String,
     scheme_id: ColorSchemeId,
     color_map: Arc<AtomicCell<super::ColorMap>>,
@@ -25,7 +25,7 @@ impl ColorMapWidgetShared {
     pub fn new(
         colors: Arc<RwLock<ColorStore>>,
         id: Id,
-        data_stats: FStats,
+        data_stats: Option<FStats>,
         data_mode: &str,
         scheme_id: ColorSchemeId,
         color_map: Arc<AtomicCell<ColorMap>>,
@@ -42,25 +42,20 @@ impl ColorMapWidgetShared {
         result
     }
 
+    /// Update the data mode and the stats used for the percentile
+    /// range; the color map itself is left to the owner
     pub fn update(
         &mut self,
-        data_stats: impl Fn(&str) -> Option<FStats>,
+        data_stats: Option<FStats>,
         data_mode: &str,
         scheme_id: ColorSchemeId,
     ) {
-        let colors = self.colors.blocking_read();
+        self.data_stats = data_stats;
+        self.scheme_id = scheme_id;
 
         if self.data_mode != data_mode {
-            let mut color_map = self.color_map.load();
-            if let Some(stats) = data_stats(data_mode) {
-                color_map.value_range = [stats.min, stats.max];
-                color_map.color_range = [0.0, 1.0];
-            }
-            self.color_map.store(color_map);
-
             self.data_mode = data_mode.to_string();
         }
-        self.scheme_id = scheme_id;
     }
 }
 
@@ -88,6 +83,7 @@ impl SettingsWidget for ColorMapWidgetShared {
         let mut color_map = self.color_map.load();
         let widget = ColorMapWidget {
             id: self.id,
+            data_stats: self.data_stats.as_ref(),
             color_map: &mut color_map,
         };
         let response = widget.show(ui);
@@ -99,15 +95,28 @@ impl SettingsWidget for ColorMapWidgetShared {
 
 pub struct ColorMapWidget<'a> {
     id: egui::Id,
+    data_stats: Option<&'a FStats>,
     color_map: &'a mut super::ColorMap,
 }
 
-#[derive(Default, Clone)]
+#[derive(Clone)]
 pub struct ColorMapWidgetState {
     // TODO: maybe store the WindowId here for reference, since the
     // egui contexts are paired with windows
     texture_handle: Arc<Mutex<Option<(ColorSchemeId, egui::TextureHandle)>>>,
     // data_mode: String,
+    /// The percentiles used by the "Auto range" button
+    auto_percentiles: [f32; 2],
+}
+
+impl Default for ColorMapWidgetState {
+    fn default() -> Self {
+        let [lo, hi] = FStats::AUTO_RANGE_QUANTILES;
+        Self {
+            texture_handle: Arc::default(),
+            auto_percentiles: [lo * 100.0, hi * 100.0],
+        }
+    }
 }
 
 impl ColorMapWidgetState {
@@ -165,13 +174,12 @@ impl<'a> ColorMapWidget<'a> {
     pub fn new(
         ctx: &Context,
         id: Id,
-        data_stats: impl Fn(&str) -> Option<FStats>,
-        data_mode: &str,
+        data_stats: Option<&'a FStats>,
         scheme_name: &str,
         color_scheme: &ColorScheme,
         color_map: &'a mut ColorMap,
     ) -> Self {
-        let mut state = ColorMapWidgetState::load(ctx, id).unwrap_or_default();
+        let state = ColorMapWidgetState::load(ctx, id).unwrap_or_default();
 
         // just upload the state here/on creation -- no need to
         // try to do it as part of show(), which will be limited
@@ -180,13 +188,21 @@ impl<'a> ColorMapWidget<'a> {
 
         state.store(ctx, id);
 
-        Self { id, color_map }
+        Self {
+            id,
+            data_stats,
+            color_map,
+        }
     }
 
     pub fn show(self, ui: &mut Ui) -> Response {
-        let state =
+        let mut state =
             ColorMapWidgetState::load(ui.ctx(), self.id).unwrap_or_default();
 
+        let stats_resp = self.data_stats.map(|stats| {
+            Self::show_auto_range(&mut state, stats, self.color_map, ui)
+        });
+
         // allocate space, then insert the sliders "on top of" the image... kind of.
         // probably good enough to make them aligned, at least
 
@@ -284,7 +300,53 @@ impl<'a> ColorMapWidget<'a> {
             },
         );
 
-        resp.response
+        state.store(ui.ctx(), self.id);
+
+        match stats_resp {
+            Some(stats_resp) => stats_resp.union(resp.response),
+            None => resp.response,
+        }
+    }
+
+    /// Shows the data summary, and a button that sets the value range
+    /// to the range between two percentiles of the data
+    fn show_auto_range(
+        state: &mut ColorMapWidgetState,
+        stats: &FStats,
+        color_map: &mut ColorMap,
+        ui: &mut Ui,
+    ) -> Response {
+        ui.vertical(|ui| {
+            ui.label(format!(
+                "Data: {} values, min {:.3}, max {:.3}, mean {:.3}, std. dev. {:.3}",
+                stats.count, stats.min, stats.max, stats.mean, stats.std_dev
+            ));
+
+            ui.horizontal(|ui| {
+                let [lo, hi] = &mut state.auto_percentiles;
+
+                ui.label("Percentiles");
+                ui.add(
+                    egui::DragValue::new(lo)
+                        .clamp_range(0.0..=100.0)
+                        .speed(0.1)
+                        .suffix("%"),
+                );
+                ui.add(
+                    egui::DragValue::new(hi)
+                        .clamp_range(0.0..=100.0)
+                        .speed(0.1)
+                        .suffix("%"),
+                );
+
+                if ui.button("Auto range").clicked() {
+                    let (lo, hi) = (lo.min(*hi), lo.max(*hi));
+                    color_map.value_range =
+                        stats.quantile_range([lo / 100.0, hi / 100.0]);
+                }
+            });
+        })
+        .response
     }
 
     fn show_color_scheme_image(
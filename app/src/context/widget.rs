@@ -149,13 +149,11 @@ impl ContextInspector {
             move |ui: &mut egui::Ui,
                   meta: &ContextMeta,
                   (path, annot_id): &(PathId, GlobalAnnotationId)| {
-                let annot_text = {
-                    let annots = annotations.blocking_read();
-                    let set =
-                        annots.annotation_sets.get(&annot_id.set_id).unwrap();
-                    let annot = set.get(annot_id.annot_id).unwrap();
-                    annot.label.to_string()
-                };
+                let annot_text = annotations
+                    .blocking_read()
+                    .get(*annot_id)
+                    .map(|annot| annot.label.to_string())
+                    .unwrap_or_else(|| "<removed>".to_string());
 
                 let path_name = graph
                     .path_names
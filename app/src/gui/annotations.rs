@@ -1,18 +1,39 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::PathBuf;
 use std::sync::Arc;
 
+use anyhow::Context;
 use egui::scroll_area::ScrollAreaOutput;
-use tokio::sync::RwLock;
+use tokio::sync::{oneshot, RwLock};
+use waragraph_core::graph::{Bp, PathId, PathIndex};
 
+use crate::app::settings_menu::{
+    SettingsUiContext, SettingsUiResponse, SettingsWidget,
+};
+use crate::app::SharedState;
+
+use crate::annotations::load::{
+    AnnotationFormat, AnnotationSource, LoadOptions,
+};
+use crate::annotations::path_names::{load_rename_table, PathNameMap};
 use crate::annotations::{
-    Annotation, AnnotationId, AnnotationStore, GlobalAnnotationId,
+    Annotation, AnnotationId, AnnotationSet, AnnotationSetId, AnnotationStore,
+    AnnotationTarget, GlobalAnnotationId,
 };
 
 pub(crate) struct AnnotationListWidget {
     annotation_store: Arc<RwLock<AnnotationStore>>,
     filter_string: String,
 
-    list: Vec<GlobalAnnotationId>,
+    /// Annotations whose child features are listed below them
+    expanded: HashSet<GlobalAnnotationId>,
+
+    /// The listed annotations, with their depth in the feature
+    /// hierarchy
+    list: Vec<(GlobalAnnotationId, usize)>,
+
+    store_generation: u64,
 }
 
 impl AnnotationListWidget {
@@ -20,7 +41,9 @@ impl AnnotationListWidget {
         let mut result = Self {
             annotation_store,
             filter_string: String::new(),
+            expanded: HashSet::default(),
             list: Vec::new(),
+            store_generation: 0,
         };
 
         result.recreate_list();
@@ -28,22 +51,77 @@ impl AnnotationListWidget {
         result
     }
 
+    /// Lists the top-level annotations, or every annotation matching
+    /// the filter if there is one, followed by the children of the
+    /// expanded annotations
     fn recreate_list(&mut self) {
-        let annotations = self.annotation_store.blocking_read();
+        let store = self.annotation_store.clone();
+        let annotations = store.blocking_read();
 
-        self.list.clear();
-        for (set_id, set) in annotations.annotation_sets.iter() {
-            for (a_id, annot) in set.annotations.iter().enumerate() {
-                if annot.label.contains(&self.filter_string) {
+        let filtering = !self.filter_string.is_empty();
+        let matches = |annot: &Annotation| {
+            (annot.parent.is_none() || filtering)
+                && annot.label.contains(&self.filter_string)
+        };
+
+        // a matching annotation is already listed below its parent if
+        // an ancestor matches, and every annotation between them is
+        // expanded
+        let listed_below_parent =
+            |set_id, set: &AnnotationSet, annot: &Annotation| {
+                let mut parent = annot.parent;
+                while let Some(p_id) = parent {
                     let global_id = GlobalAnnotationId {
-                        set_id: *set_id,
-                        annot_id: AnnotationId(a_id),
+                        set_id,
+                        annot_id: p_id,
+                    };
+                    let Some(p_annot) = set.get(p_id) else {
+                        return false;
                     };
+                    if !self.expanded.contains(&global_id) {
+                        return false;
+                    }
+                    if matches(p_annot) {
+                        return true;
+                    }
+                    parent = p_annot.parent;
+                }
+                false
+            };
 
-                    self.list.push(global_id);
+        let mut roots = Vec::new();
+        for (set_id, set) in annotations.annotation_sets.iter() {
+            for (a_id, annot) in set.annotations.iter().enumerate() {
+                if matches(annot) && !listed_below_parent(*set_id, set, annot) {
+                    roots.push((*set_id, AnnotationId(a_id)));
                 }
             }
         }
+
+        self.store_generation = annotations.generation();
+
+        self.list.clear();
+        for (set_id, annot_id) in roots {
+            let set = &annotations.annotation_sets[&set_id];
+            self.push_with_children(set_id, set, annot_id, 0);
+        }
+    }
+
+    fn push_with_children(
+        &mut self,
+        set_id: AnnotationSetId,
+        set: &AnnotationSet,
+        annot_id: AnnotationId,
+        depth: usize,
+    ) {
+        let global_id = GlobalAnnotationId { set_id, annot_id };
+        self.list.push((global_id, depth));
+
+        if self.expanded.contains(&global_id) {
+            for &child in set.children(annot_id) {
+                self.push_with_children(set_id, set, child, depth + 1);
+            }
+        }
     }
 
     pub fn show(
@@ -58,17 +136,15 @@ impl AnnotationListWidget {
         let row_height = ui.text_style_height(&egui::TextStyle::Body);
         let total_rows = self.list.len();
 
-        let annotation_sets = self
-            .annotation_store
-            .blocking_read()
-            .annotation_sets
-            .clone();
+        let (annotation_sets, store_generation) = {
+            let store = self.annotation_store.blocking_read();
+            (store.annotation_sets.clone(), store.generation())
+        };
 
         let filter_entry = ui.text_edit_singleline(&mut self.filter_string);
 
-        if filter_entry.changed() {
-            self.recreate_list();
-        }
+        let mut list_changed =
+            filter_entry.changed() || store_generation != self.store_generation;
 
         egui::ScrollArea::vertical().max_height(500.0).show_rows(
             ui,
@@ -76,16 +152,1247 @@ impl AnnotationListWidget {
             total_rows,
             |ui, range| {
                 for ix in range {
-                    let annot = self.list.get(ix).and_then(|annot_id| {
-                        let set = annotation_sets.get(&annot_id.set_id)?;
-                        Some((*annot_id, set.get(annot_id.annot_id)?))
-                    });
+                    let annot =
+                        self.list.get(ix).and_then(|(annot_id, depth)| {
+                            let set = annotation_sets.get(&annot_id.set_id)?;
+                            let has_children =
+                                !set.children(annot_id.annot_id).is_empty();
+                            Some((
+                                *annot_id,
+                                *depth,
+                                has_children,
+                                set.get(annot_id.annot_id)?,
+                            ))
+                        });
+
+                    let Some((id, depth, has_children, annotation)) = annot
+                    else {
+                        continue;
+                    };
+
+                    ui.horizontal(|ui| {
+                        ui.add_space(depth as f32 * row_height);
+
+                        if has_children {
+                            let expanded = self.expanded.contains(&id);
+                            let icon = if expanded { "⏷" } else { "⏵" };
+
+                            if ui.small_button(icon).clicked() {
+                                if expanded {
+                                    self.expanded.remove(&id);
+                                } else {
+                                    self.expanded.insert(id);
+                                }
+                                list_changed = true;
+                            }
+                        }
 
-                    if let Some((id, annotation)) = annot {
                         show_annotation(ui, id, annotation);
-                    }
+                    });
                 }
             },
         );
+
+        if list_changed {
+            self.recreate_list();
+        }
+    }
+}
+
+/// Where the annotation is, as `path:start-end` for annotations on
+/// paths, `pangenome:start-end`, or the number of nodes in a node set
+pub(crate) fn location_text(graph: &PathIndex, annot: &Annotation) -> String {
+    let (start, end) = (annot.range.start.0, annot.range.end.0);
+
+    match &annot.target {
+        AnnotationTarget::Path(path) => {
+            let path_name = graph
+                .path_names
+                .get_by_left(path)
+                .map(|s| s.as_str())
+                .unwrap_or_default();
+            format!("{path_name}:{start}-{end}")
+        }
+        AnnotationTarget::Pangenome => format!("pangenome:{start}-{end}"),
+        AnnotationTarget::Nodes(nodes) => format!("{} nodes", nodes.len()),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SearchMode {
+    Substring,
+    Regex,
+}
+
+/// Builds the regex used to match annotation labels; in substring
+/// mode the query is matched literally
+pub(crate) fn label_matcher(
+    query: &str,
+    mode: SearchMode,
+    case_insensitive: bool,
+) -> Result<regex::Regex, regex::Error> {
+    let pattern = match mode {
+        SearchMode::Substring => regex::escape(query),
+        SearchMode::Regex => query.to_string(),
+    };
+
+    regex::RegexBuilder::new(&pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+}
+
+/// Searches the labels of the annotations in every set in the store,
+/// listing the hits with their set, path, and range
+pub(crate) struct AnnotationSearchWidget {
+    graph: Arc<PathIndex>,
+    annotation_store: Arc<RwLock<AnnotationStore>>,
+
+    query: String,
+    mode: SearchMode,
+    case_insensitive: bool,
+
+    hits: Vec<GlobalAnnotationId>,
+    error: Option<String>,
+
+    // the store generation the hits were found in
+    store_generation: u64,
+}
+
+impl AnnotationSearchWidget {
+    pub fn new(
+        graph: Arc<PathIndex>,
+        annotation_store: Arc<RwLock<AnnotationStore>>,
+    ) -> Self {
+        Self {
+            graph,
+            annotation_store,
+            query: String::new(),
+            mode: SearchMode::Substring,
+            case_insensitive: true,
+            hits: Vec::new(),
+            error: None,
+            store_generation: 0,
+        }
+    }
+
+    fn search(&mut self) {
+        self.hits.clear();
+        self.error = None;
+
+        let store = self.annotation_store.blocking_read();
+        self.store_generation = store.generation();
+
+        if self.query.is_empty() {
+            return;
+        }
+
+        let matcher = match label_matcher(
+            &self.query,
+            self.mode,
+            self.case_insensitive,
+        ) {
+            Ok(matcher) => matcher,
+            Err(err) => {
+                self.error = Some(err.to_string());
+                return;
+            }
+        };
+
+        for (set_id, set) in store.annotation_sets.iter() {
+            for (a_id, annot) in set.annotations.iter().enumerate() {
+                if matcher.is_match(&annot.label) {
+                    self.hits.push(GlobalAnnotationId {
+                        set_id: *set_id,
+                        annot_id: AnnotationId(a_id),
+                    });
+                }
+            }
+        }
+    }
+
+    /// Returns the hit that was clicked, if any
+    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<GlobalAnnotationId> {
+        let mut changed = false;
+
+        ui.horizontal(|ui| {
+            let query = ui.text_edit_singleline(&mut self.query);
+            changed |= query.changed();
+        });
+
+        ui.horizontal(|ui| {
+            changed |= ui
+                .radio_value(&mut self.mode, SearchMode::Substring, "Substring")
+                .changed();
+            changed |= ui
+                .radio_value(&mut self.mode, SearchMode::Regex, "Regex")
+                .changed();
+            changed |= ui
+                .checkbox(&mut self.case_insensitive, "Ignore case")
+                .changed();
+        });
+
+        let store = self.annotation_store.clone();
+
+        if changed
+            || self.store_generation != store.blocking_read().generation()
+        {
+            self.search();
+        }
+
+        if let Some(err) = &self.error {
+            ui.colored_label(ui.visuals().error_fg_color, err);
+            return None;
+        }
+
+        if !self.query.is_empty() {
+            ui.label(format!("{} hits", self.hits.len()));
+        }
+
+        let store = store.blocking_read();
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Body)
+            + ui.spacing().item_spacing.y;
+
+        let mut clicked = None;
+
+        egui::ScrollArea::vertical()
+            .id_source("annotation-search-hits")
+            .max_height(300.0)
+            .show_rows(ui, row_height, self.hits.len(), |ui, range| {
+                for &id in &self.hits[range] {
+                    let Some(set) = store.annotation_sets.get(&id.set_id)
+                    else {
+                        continue;
+                    };
+                    let Some(annot) = set.get(id.annot_id) else {
+                        continue;
+                    };
+
+                    let location = location_text(&self.graph, annot);
+
+                    ui.horizontal(|ui| {
+                        let label = ui.add(
+                            egui::Label::new(annot.label.as_str())
+                                .sense(egui::Sense::click()),
+                        );
+
+                        ui.weak(format!("{} | {location}", set.name));
+
+                        if label.clicked() {
+                            clicked = Some(id);
+                        }
+                    });
+                }
+            });
+
+        clicked
+    }
+}
+
+/// Annotations created interactively, kept in their own set in the
+/// store, where they can be renamed, recolored, and deleted, and saved
+/// to a BED file
+pub(crate) struct UserAnnotationsWidget {
+    graph: Arc<PathIndex>,
+    annotation_store: Arc<RwLock<AnnotationStore>>,
+    settings_ctx: Arc<SettingsUiContext>,
+
+    set_id: Option<AnnotationSetId>,
+
+    /// The annotation being created, before it's added to the set
+    pending: Option<Annotation>,
+
+    // labels being edited, applied when the text field loses focus
+    label_edits: HashMap<AnnotationId, String>,
+
+    save_path_recv: Option<oneshot::Receiver<PathBuf>>,
+    save_status: Option<Result<String, String>>,
+}
+
+enum UserAnnotationEdit {
+    Label(AnnotationId, String),
+    Color(AnnotationId, egui::Color32),
+    Delete(AnnotationId),
+}
+
+impl UserAnnotationsWidget {
+    pub const SET_NAME: &'static str = "User annotations";
+
+    pub fn new(
+        graph: Arc<PathIndex>,
+        annotation_store: Arc<RwLock<AnnotationStore>>,
+        settings_ctx: Arc<SettingsUiContext>,
+    ) -> Self {
+        Self {
+            graph,
+            annotation_store,
+            settings_ctx,
+            set_id: None,
+            pending: None,
+            label_edits: HashMap::default(),
+            save_path_recv: None,
+            save_status: None,
+        }
+    }
+
+    pub fn has_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// Starts creating an annotation covering `range` on `path`, to be
+    /// labelled and added in the widget
+    pub fn begin_annotation(&mut self, path: PathId, range: Range<Bp>) {
+        let path_name = self
+            .graph
+            .path_names
+            .get_by_left(&path)
+            .map(|s| s.as_str())
+            .unwrap_or_default();
+
+        let label = format!("{path_name}:{}-{}", range.start.0, range.end.0);
+        let [r, g, b] = crate::color::util::hashed_rgb(&label);
+        let color = egui::Color32::from_rgb(r, g, b);
+
+        self.pending =
+            Some(Annotation::new(path, range, Arc::new(label), Some(color)));
+    }
+
+    /// Rebuilds the user annotation set after applying `f` to its
+    /// annotations, creating the set if it doesn't exist
+    fn update_set(&mut self, f: impl FnOnce(&mut Vec<Annotation>)) {
+        let mut store = self.annotation_store.blocking_write();
+
+        let set_id = self
+            .set_id
+            .filter(|id| store.annotation_sets.contains_key(id));
+
+        let mut annotations = set_id
+            .and_then(|id| store.annotation_sets.get(&id))
+            .map(|set| set.annotations.clone())
+            .unwrap_or_default();
+
+        f(&mut annotations);
+
+        let set = AnnotationSet::new(
+            &self.graph,
+            Self::SET_NAME.to_string(),
+            annotations,
+            HashMap::new(),
+        );
+
+        if let Some(set_id) = set_id {
+            store.replace_set(set_id, set);
+        } else {
+            self.set_id = Some(store.insert_set(set));
+        }
+    }
+
+    fn show_pending(&mut self, ui: &mut egui::Ui) {
+        let Some(pending) = self.pending.as_mut() else {
+            return;
+        };
+
+        let mut add = false;
+        let mut cancel = false;
+
+        ui.group(|ui| {
+            ui.label(format!(
+                "New annotation on {}",
+                location_text(&self.graph, pending)
+            ));
+
+            ui.horizontal(|ui| {
+                let mut color = pending.color.unwrap_or(egui::Color32::GRAY);
+                egui::color_picker::color_edit_button_srgba(
+                    ui,
+                    &mut color,
+                    egui::color_picker::Alpha::Opaque,
+                );
+                pending.color = Some(color);
+
+                let label = Arc::make_mut(&mut pending.label);
+                let text = ui.text_edit_singleline(label);
+
+                add = text.lost_focus()
+                    && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            });
+
+            ui.horizontal(|ui| {
+                add |= ui.button("Add").clicked();
+                cancel = ui.button("Cancel").clicked();
+            });
+        });
+
+        if cancel {
+            self.pending = None;
+        } else if add {
+            if let Some(annot) = self.pending.take() {
+                self.update_set(|annotations| annotations.push(annot));
+            }
+        }
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui) {
+        self.show_pending(ui);
+
+        if self.pending.is_none() {
+            ui.weak("Shift + drag on a path to create an annotation");
+        }
+
+        let store = self.annotation_store.clone();
+        let store = store.blocking_read();
+
+        let set = self.set_id.and_then(|id| store.annotation_sets.get(&id));
+
+        let mut edits = Vec::new();
+
+        for (ix, annot) in set
+            .iter()
+            .flat_map(|set| set.annotations.iter())
+            .enumerate()
+        {
+            let annot_id = AnnotationId(ix);
+
+            ui.horizontal(|ui| {
+                let mut color = annot.color.unwrap_or(egui::Color32::GRAY);
+                let color_button = egui::color_picker::color_edit_button_srgba(
+                    ui,
+                    &mut color,
+                    egui::color_picker::Alpha::Opaque,
+                );
+
+                if color_button.changed() {
+                    edits.push(UserAnnotationEdit::Color(annot_id, color));
+                }
+
+                let label = self
+                    .label_edits
+                    .entry(annot_id)
+                    .or_insert_with(|| annot.label.to_string());
+
+                let text = ui.add(
+                    egui::TextEdit::singleline(label).desired_width(120.0),
+                );
+
+                if text.lost_focus() {
+                    if let Some(label) = self.label_edits.remove(&annot_id) {
+                        if label != *annot.label {
+                            edits.push(UserAnnotationEdit::Label(
+                                annot_id, label,
+                            ));
+                        }
+                    }
+                } else if !text.has_focus() {
+                    // keep the field in sync with the set
+                    self.label_edits.remove(&annot_id);
+                }
+
+                ui.weak(format!(
+                    "{}-{}",
+                    annot.range.start.0, annot.range.end.0
+                ));
+
+                if ui.small_button("🗑").on_hover_text("Delete").clicked() {
+                    edits.push(UserAnnotationEdit::Delete(annot_id));
+                }
+            });
+        }
+
+        let save_path = match self.save_path_recv.as_mut().map(|r| r.try_recv())
+        {
+            Some(Ok(path)) => {
+                self.save_path_recv = None;
+                Some(path)
+            }
+            Some(Err(oneshot::error::TryRecvError::Closed)) => {
+                self.save_path_recv = None;
+                None
+            }
+            _ => None,
+        };
+
+        if let Some((set, path)) = set.zip(save_path) {
+            let result = set
+                .write_bed(&self.graph, &path)
+                .map(|_| format!("Saved to {}", path.display()))
+                .map_err(|err| {
+                    log::error!("Error saving annotations: {err:?}");
+                    format!("Error saving annotations: {err}")
+                });
+            self.save_status = Some(result);
+        }
+
+        let has_annotations =
+            set.is_some_and(|set| !set.annotations.is_empty());
+
+        if has_annotations {
+            let save_button = ui.add_enabled(
+                self.save_path_recv.is_none(),
+                egui::Button::new("Save BED..."),
+            );
+
+            if save_button.clicked() {
+                let mut dialog = egui_file::FileDialog::save_file(None)
+                    .default_filename("annotations.bed");
+                dialog.open();
+
+                let id = egui::Id::new("UserAnnotationsWidget");
+                let recv = self.settings_ctx.with_window_file_dialog_oneshot(
+                    ui.ctx(),
+                    id,
+                    dialog,
+                );
+                self.save_path_recv = Some(recv);
+            }
+
+            match &self.save_status {
+                Some(Ok(msg)) => {
+                    ui.label(msg);
+                }
+                Some(Err(msg)) => {
+                    ui.colored_label(ui.visuals().error_fg_color, msg);
+                }
+                None => (),
+            }
+        }
+
+        std::mem::drop(store);
+
+        if !edits.is_empty() {
+            if edits
+                .iter()
+                .any(|edit| matches!(edit, UserAnnotationEdit::Delete(_)))
+            {
+                // the IDs of the later annotations change
+                self.label_edits.clear();
+            }
+
+            self.update_set(|annotations| {
+                let mut deleted = HashSet::new();
+
+                for edit in edits {
+                    match edit {
+                        UserAnnotationEdit::Label(id, label) => {
+                            annotations[id.0].label = Arc::new(label);
+                        }
+                        UserAnnotationEdit::Color(id, color) => {
+                            annotations[id.0].color = Some(color);
+                        }
+                        UserAnnotationEdit::Delete(id) => {
+                            deleted.insert(id.0);
+                        }
+                    }
+                }
+
+                let mut ix = 0;
+                annotations.retain(|_| {
+                    ix += 1;
+                    !deleted.contains(&(ix - 1))
+                });
+            });
+        }
+    }
+}
+
+/// Builds a path name mapping from the text fields of the settings
+/// widgets, where empty fields are unused
+fn path_name_map(
+    template: &str,
+    regex: &str,
+    replacement: &str,
+) -> anyhow::Result<PathNameMap> {
+    fn non_empty(s: &str) -> Option<&str> {
+        (!s.trim().is_empty()).then_some(s.trim())
+    }
+
+    let mut path_names = PathNameMap::default();
+    path_names.set_template(non_empty(template))?;
+    path_names.set_rewrite(non_empty(regex).map(|re| (re, replacement)))?;
+
+    Ok(path_names)
+}
+
+/// Settings for how the sequence names in annotation files are mapped
+/// to path names, with the number of records that matched a path in
+/// each loaded set, and the sequence names that didn't
+pub(crate) struct PathNamesWidget {
+    shared: SharedState,
+
+    template: String,
+    regex: String,
+    replacement: String,
+
+    renames_path: String,
+    renames: HashMap<String, String>,
+
+    // the number of reloaded sets, and the errors of the sets that
+    // couldn't be reloaded
+    reload_recv: Option<oneshot::Receiver<(usize, Vec<String>)>>,
+
+    status: Option<Result<String, String>>,
+}
+
+impl PathNamesWidget {
+    pub fn new(shared: SharedState) -> Self {
+        let (template, (regex, replacement), renames) = {
+            let options = shared.annotation_options.blocking_read();
+            let path_names = &options.path_names;
+            (
+                path_names.template().unwrap_or_default().to_string(),
+                path_names
+                    .rewrite()
+                    .map(|(r, s)| (r.to_string(), s.to_string()))
+                    .unwrap_or_default(),
+                path_names.renames.clone(),
+            )
+        };
+
+        Self {
+            shared,
+            template,
+            regex,
+            replacement,
+            renames_path: String::new(),
+            renames,
+            reload_recv: None,
+            status: None,
+        }
+    }
+
+    /// Applies the path name mapping, and starts reloading the
+    /// annotation sets in the background, see `reload_sets`
+    fn apply_and_reload(
+        &mut self,
+        tokio_handle: &tokio::runtime::Handle,
+    ) -> anyhow::Result<()> {
+        let mut path_names =
+            path_name_map(&self.template, &self.regex, &self.replacement)?;
+        path_names.renames = self.renames.clone();
+
+        let options = {
+            let mut options = self.shared.annotation_options.blocking_write();
+            options.path_names = path_names;
+            options.clone()
+        };
+
+        let (send, recv) = oneshot::channel();
+        let shared = self.shared.clone();
+
+        tokio_handle.spawn_blocking(move || {
+            let _ = send.send(Self::reload_sets(&shared, &options));
+        });
+
+        self.reload_recv = Some(recv);
+
+        Ok(())
+    }
+
+    /// Reloads the annotation sets that were loaded from files, and
+    /// projects the sets that were projected from them again. Sets
+    /// that fail to load are kept as they are; returns the number of
+    /// sets that were reloaded, and the errors of the others
+    fn reload_sets(
+        shared: &SharedState,
+        options: &LoadOptions,
+    ) -> (usize, Vec<String>) {
+        let sets = shared
+            .annotations
+            .blocking_read()
+            .annotation_sets
+            .iter()
+            .map(|(set_id, set)| (*set_id, set.clone()))
+            .collect::<Vec<_>>();
+
+        let mut reloaded_sets = HashMap::new();
+        let mut errors = Vec::new();
+
+        let replace = |set_id, set: Arc<AnnotationSet>| {
+            shared
+                .annotations
+                .blocking_write()
+                .replace_set(set_id, set.clone());
+            shared.register_annotation_density(set_id, &set);
+        };
+
+        for (set_id, set) in &sets {
+            let Some(source) = set.source.clone() else {
+                continue;
+            };
+
+            match AnnotationSet::load(&shared.graph, options, source) {
+                Ok(new_set) => {
+                    let new_set = Arc::new(new_set);
+                    replace(*set_id, new_set.clone());
+                    reloaded_sets.insert(*set_id, new_set);
+                }
+                Err(err) => {
+                    log::error!("Error reloading {}: {err:?}", set.name);
+                    errors.push(format!("{}: {err}", set.name));
+                }
+            }
+        }
+
+        let mut reloaded = reloaded_sets.len();
+
+        for (set_id, set) in &sets {
+            let Some(origin) =
+                set.projected_from.and_then(|id| reloaded_sets.get(&id))
+            else {
+                continue;
+            };
+
+            let mut projected = origin.project_to_paths(&shared.graph);
+            projected.projected_from = set.projected_from;
+            replace(*set_id, Arc::new(projected));
+            reloaded += 1;
+        }
+
+        (reloaded, errors)
+    }
+}
+
+impl SettingsWidget for PathNamesWidget {
+    fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        settings_ctx: &SettingsUiContext,
+    ) -> SettingsUiResponse {
+        if let Some(recv) = self.reload_recv.as_mut() {
+            match recv.try_recv() {
+                Ok((count, errors)) => {
+                    let msg = format!("Reloaded {count} annotation sets");
+
+                    self.status = Some(if errors.is_empty() {
+                        Ok(msg)
+                    } else {
+                        Err(format!(
+                            "{msg}, failed to reload:\n{}",
+                            errors.join("\n")
+                        ))
+                    });
+                    self.reload_recv = None;
+                }
+                Err(oneshot::error::TryRecvError::Closed) => {
+                    self.reload_recv = None;
+                }
+                Err(oneshot::error::TryRecvError::Empty) => (),
+            }
+        }
+
+        let resp = ui.vertical(|ui| {
+            egui::Grid::new("path-names-grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("Rename table");
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.renames_path);
+
+                        if ui.button("Load").clicked() {
+                            match load_rename_table(self.renames_path.trim()) {
+                                Ok(renames) => {
+                                    self.status = Some(Ok(format!(
+                                        "Loaded {} renames",
+                                        renames.len()
+                                    )));
+                                    self.renames = renames;
+                                }
+                                Err(err) => {
+                                    self.status = Some(Err(format!("{err:?}")));
+                                }
+                            }
+                        }
+
+                        if ui.button("Clear").clicked() {
+                            self.renames.clear();
+                        }
+
+                        ui.label(format!("{} renames", self.renames.len()));
+                    });
+                    ui.end_row();
+
+                    ui.label("Regex");
+                    ui.text_edit_singleline(&mut self.regex);
+                    ui.end_row();
+
+                    ui.label("Replacement");
+                    ui.text_edit_singleline(&mut self.replacement);
+                    ui.end_row();
+
+                    ui.label("Template");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.template)
+                            .hint_text("{name}"),
+                    );
+                    ui.end_row();
+                });
+
+            ui.horizontal(|ui| {
+                let reloading = self.reload_recv.is_some();
+
+                let button = ui.add_enabled(
+                    !reloading,
+                    egui::Button::new("Apply and reload annotations"),
+                );
+
+                if button.clicked() {
+                    if let Err(err) =
+                        self.apply_and_reload(&settings_ctx.tokio_handle)
+                    {
+                        self.status = Some(Err(format!("{err:?}")));
+                    }
+                }
+
+                if reloading {
+                    ui.spinner();
+                }
+            });
+
+            match &self.status {
+                Some(Ok(msg)) => {
+                    ui.label(msg);
+                }
+                Some(Err(msg)) => {
+                    ui.colored_label(egui::Color32::RED, msg);
+                }
+                None => (),
+            }
+
+            ui.separator();
+
+            let store = self.shared.annotations.blocking_read();
+
+            for (set_id, set) in store.annotation_sets.iter() {
+                let Some(report) = set.path_matches.as_ref() else {
+                    continue;
+                };
+
+                let text = format!(
+                    "{}: {} records matched, {} skipped",
+                    set.name,
+                    report.matched,
+                    report.unmatched_records()
+                );
+
+                if report.unmatched.is_empty() {
+                    ui.label(text);
+                    continue;
+                }
+
+                egui::CollapsingHeader::new(text)
+                    .id_source(("path-matches", set_id.0))
+                    .show(ui, |ui| {
+                        for (name, (path_name, count)) in &report.unmatched {
+                            ui.label(format!(
+                                "{name} (as {path_name}): {count} records"
+                            ));
+                        }
+                    });
+            }
+        });
+
+        SettingsUiResponse {
+            response: resp.response,
+        }
+    }
+}
+
+/// Loading and removing annotation files at runtime, optionally with
+/// a path name mapping and GFF3 label attribute used only for that file
+pub(crate) struct AnnotationFilesWidget {
+    shared: SharedState,
+
+    file_path: String,
+    file_path_recv: Option<oneshot::Receiver<PathBuf>>,
+    load_recv: Option<oneshot::Receiver<anyhow::Result<usize>>>,
+    // `None` to detect the format from the file extension
+    format: Option<AnnotationFormat>,
+
+    // per-file options, used instead of the global ones if set
+    custom_path_names: bool,
+    template: String,
+    regex: String,
+    replacement: String,
+    gff_attr: String,
+
+    status: Option<Result<String, String>>,
+}
+
+impl AnnotationFilesWidget {
+    const FORMATS: [AnnotationFormat; 5] = [
+        AnnotationFormat::Bed,
+        AnnotationFormat::Gff,
+        AnnotationFormat::Vcf,
+        AnnotationFormat::PangenomeBed,
+        AnnotationFormat::NodeList,
+    ];
+
+    pub fn new(shared: SharedState) -> Self {
+        Self {
+            shared,
+
+            file_path: String::new(),
+            file_path_recv: None,
+            load_recv: None,
+            format: None,
+
+            custom_path_names: false,
+            template: String::new(),
+            regex: String::new(),
+            replacement: String::new(),
+            gff_attr: String::new(),
+
+            status: None,
+        }
+    }
+
+    fn source(&self) -> anyhow::Result<AnnotationSource> {
+        let path = PathBuf::from(self.file_path.trim());
+
+        let format = self
+            .format
+            .or_else(|| AnnotationFormat::from_path(&path))
+            .with_context(|| {
+                format!("Can't detect the format of {path:?}, choose one")
+            })?;
+
+        let mut source = AnnotationSource::new(path, format);
+
+        let gff_attr = self.gff_attr.trim();
+
+        if self.custom_path_names || !gff_attr.is_empty() {
+            let mut options =
+                self.shared.annotation_options.blocking_read().clone();
+
+            if self.custom_path_names {
+                options.path_names = path_name_map(
+                    &self.template,
+                    &self.regex,
+                    &self.replacement,
+                )?;
+            }
+
+            if !gff_attr.is_empty() {
+                options.gff_attr = Some(gff_attr.to_string());
+            }
+
+            source.options = Some(options);
+        }
+
+        Ok(source)
+    }
+
+    /// Starts loading the file in the background, adding it to the
+    /// store once it's loaded; the number of annotations in the new
+    /// set is sent to `load_recv`
+    fn load(
+        &mut self,
+        tokio_handle: &tokio::runtime::Handle,
+    ) -> anyhow::Result<()> {
+        let source = self.source()?;
+        let path = source.path.clone();
+
+        let options = self.shared.annotation_options.blocking_read().clone();
+        let shared = self.shared.clone();
+
+        let (send, recv) = oneshot::channel();
+
+        tokio_handle.spawn_blocking(move || {
+            let result = AnnotationSet::load(&shared.graph, &options, source)
+                .with_context(|| format!("Error loading {path:?}"))
+                .map(|set| {
+                    let count = set.annotations.len();
+                    let set = Arc::new(set);
+
+                    let set_id = shared
+                        .annotations
+                        .blocking_write()
+                        .insert_set(set.clone());
+                    shared.register_annotation_density(set_id, &set);
+
+                    count
+                });
+
+            let _ = send.send(result);
+        });
+
+        self.load_recv = Some(recv);
+
+        Ok(())
+    }
+
+    fn show_loaded_sets(&self, ui: &mut egui::Ui) -> Option<AnnotationSetId> {
+        let store = self.shared.annotations.blocking_read();
+
+        if store.annotation_sets.is_empty() {
+            ui.label("No annotations loaded");
+            return None;
+        }
+
+        let mut to_remove = None;
+
+        egui::Grid::new("annotation-files-grid")
+            .num_columns(4)
+            .striped(true)
+            .show(ui, |ui| {
+                for (set_id, set) in store.annotation_sets.iter() {
+                    ui.label(set.name.as_str());
+
+                    match &set.source {
+                        Some(source) => {
+                            ui.label(source.format.name())
+                                .on_hover_text(source.path.to_string_lossy());
+                        }
+                        None => {
+                            ui.label("");
+                        }
+                    }
+
+                    ui.label(format!("{} annotations", set.annotations.len()));
+
+                    if ui.button("Remove").clicked() {
+                        to_remove = Some(*set_id);
+                    }
+
+                    ui.end_row();
+                }
+            });
+
+        to_remove
+    }
+}
+
+impl SettingsWidget for AnnotationFilesWidget {
+    fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        settings_ctx: &SettingsUiContext,
+    ) -> SettingsUiResponse {
+        let id = egui::Id::new("AnnotationFilesWidget");
+
+        if let Some(recv) = self.file_path_recv.as_mut() {
+            match recv.try_recv() {
+                Ok(path) => {
+                    self.file_path = path.to_string_lossy().to_string();
+                    self.file_path_recv = None;
+                }
+                Err(oneshot::error::TryRecvError::Closed) => {
+                    self.file_path_recv = None;
+                }
+                Err(oneshot::error::TryRecvError::Empty) => (),
+            }
+        }
+
+        if let Some(recv) = self.load_recv.as_mut() {
+            match recv.try_recv() {
+                Ok(result) => {
+                    self.status = Some(
+                        result
+                            .map(|count| format!("Loaded {count} annotations"))
+                            .map_err(|err| format!("{err:?}")),
+                    );
+                    self.load_recv = None;
+                }
+                Err(oneshot::error::TryRecvError::Closed) => {
+                    self.load_recv = None;
+                }
+                Err(oneshot::error::TryRecvError::Empty) => (),
+            }
+        }
+
+        let resp = ui.vertical(|ui| {
+            if let Some(set_id) = self.show_loaded_sets(ui) {
+                let removed =
+                    self.shared.annotations.blocking_write().remove_set(set_id);
+                self.shared.unregister_annotation_density(set_id);
+
+                if let Some(set) = removed {
+                    self.status = Some(Ok(format!("Removed {}", set.name)));
+                }
+            }
+
+            ui.separator();
+
+            egui::Grid::new("annotation-file-load-grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label("File");
+                    ui.horizontal(|ui| {
+                        ui.add_enabled(
+                            self.file_path_recv.is_none(),
+                            egui::TextEdit::singleline(&mut self.file_path),
+                        );
+
+                        if ui.button("Choose").clicked() {
+                            let mut files =
+                                egui_file::FileDialog::open_file(None);
+                            files.open();
+
+                            let recv = settings_ctx
+                                .with_file_dialog_oneshot(id, files);
+                            self.file_path_recv = Some(recv);
+                        }
+                    });
+                    ui.end_row();
+
+                    ui.label("Format");
+                    egui::ComboBox::from_id_source(id.with("format"))
+                        .selected_text(
+                            self.format
+                                .map(|f| f.name())
+                                .unwrap_or("From extension"),
+                        )
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut self.format,
+                                None,
+                                "From extension",
+                            );
+                            for format in Self::FORMATS {
+                                ui.selectable_value(
+                                    &mut self.format,
+                                    Some(format),
+                                    format.name(),
+                                );
+                            }
+                        });
+                    ui.end_row();
+
+                    ui.label("GFF3 label attribute");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.gff_attr)
+                            .hint_text("Global setting"),
+                    );
+                    ui.end_row();
+
+                    ui.label("Path names");
+                    ui.checkbox(
+                        &mut self.custom_path_names,
+                        "Use a mapping for this file",
+                    );
+                    ui.end_row();
+
+                    if self.custom_path_names {
+                        ui.label("Regex");
+                        ui.text_edit_singleline(&mut self.regex);
+                        ui.end_row();
+
+                        ui.label("Replacement");
+                        ui.text_edit_singleline(&mut self.replacement);
+                        ui.end_row();
+
+                        ui.label("Template");
+                        ui.add(
+                            egui::TextEdit::singleline(&mut self.template)
+                                .hint_text("{name}"),
+                        );
+                        ui.end_row();
+                    }
+                });
+
+            let can_load = !self.file_path.trim().is_empty()
+                && self.file_path_recv.is_none()
+                && self.load_recv.is_none();
+
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(can_load, egui::Button::new("Load"))
+                    .clicked()
+                {
+                    if let Err(err) = self.load(&settings_ctx.tokio_handle) {
+                        self.status = Some(Err(format!("{err:?}")));
+                    }
+                }
+
+                if self.load_recv.is_some() {
+                    ui.spinner();
+                }
+            });
+
+            match &self.status {
+                Some(Ok(msg)) => {
+                    ui.label(msg);
+                }
+                Some(Err(msg)) => {
+                    ui.colored_label(egui::Color32::RED, msg);
+                }
+                None => (),
+            }
+        });
+
+        SettingsUiResponse {
+            response: resp.response,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annotation_label_matching() {
+        let matcher =
+            label_matcher("brca", SearchMode::Substring, true).unwrap();
+        assert!(matcher.is_match("BRCA2"));
+        assert!(!matcher.is_match("TP53"));
+
+        let matcher =
+            label_matcher("brca", SearchMode::Substring, false).unwrap();
+        assert!(!matcher.is_match("BRCA2"));
+
+        // regex metacharacters are literal in substring mode
+        let matcher =
+            label_matcher("a.1", SearchMode::Substring, true).unwrap();
+        assert!(matcher.is_match("HLA.1"));
+        assert!(!matcher.is_match("HLA-1"));
+
+        let matcher =
+            label_matcher("^brca[12]$", SearchMode::Regex, true).unwrap();
+        assert!(matcher.is_match("BRCA1"));
+        assert!(!matcher.is_match("BRCA12"));
+
+        assert!(label_matcher("(", SearchMode::Regex, true).is_err());
+    }
+
+    #[test]
+    fn annotation_list_filter_expanded() {
+        let graph = crate::test_util::test_graph();
+        let path = *graph.path_names.left_values().next().unwrap();
+
+        let annot = |label: &str, parent| Annotation {
+            parent,
+            ..Annotation::new(
+                path,
+                Bp(0)..Bp(10),
+                Arc::new(label.to_string()),
+                None,
+            )
+        };
+
+        let gene = AnnotationId(0);
+        let transcript = AnnotationId(1);
+        let set = AnnotationSet::new(
+            &graph,
+            "genes".to_string(),
+            vec![annot("gene-a", None), annot("gene-a.1", Some(gene))],
+            HashMap::from([(gene, vec![transcript])]),
+        );
+
+        let mut store = AnnotationStore::default();
+        let set_id = store.insert_set(set);
+
+        let mut widget =
+            AnnotationListWidget::new(Arc::new(RwLock::new(store)));
+        let global = |annot_id| GlobalAnnotationId { set_id, annot_id };
+
+        widget.filter_string = "gene-a".to_string();
+        widget.recreate_list();
+        assert_eq!(widget.list, [(global(gene), 0), (global(transcript), 0)]);
+
+        // once the gene is expanded, the transcript is only listed
+        // below it
+        widget.expanded.insert(global(gene));
+        widget.recreate_list();
+        assert_eq!(widget.list, [(global(gene), 0), (global(transcript), 1)]);
     }
 }
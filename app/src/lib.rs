@@ -17,3 +17,6 @@ pub mod gui;
 pub mod list;
 
 pub mod util;
+
+#[cfg(test)]
+pub(crate) mod test_util;
@@ -13,7 +13,45 @@ pub fn main() -> Result<()> {
     if args.is_err() {
         let name = std::env::args().next().unwrap();
         println!("Usage: {name} <gfa> [tsv]");
-        println!("4-column BED file can be provided using the --bed flag");
+        println!(
+            "BED (BED3 to BED12) files can be provided using the --bed flag"
+        );
+        println!("GFF3 files can be provided using the --gff flag;");
+        println!("  --gff-attr sets the attribute used for labels (default");
+        println!("  Name), and --gff-types gene,mRNA,... only loads the");
+        println!("  features of those types; records without the label");
+        println!("  attribute are skipped with --gff-skip-unlabeled");
+        println!("VCF files, plain or bgzipped, can be provided using");
+        println!("  the --vcf flag; with --vcf-pansn-samples, only variants");
+        println!(
+            "  carried by samples that are PanSN path prefixes are loaded"
+        );
+        println!("Sequence names in annotation files are mapped to path");
+        println!("  names with --path-renames <two-column table>, or else");
+        println!("  with --path-regex <regex> --path-replace <text> (empty");
+        println!("  by default) followed by --path-template, e.g.");
+        println!("  --path-template 'SGDref#1#{{name}}'");
+        println!("With --project-annotations, annotations are also shown");
+        println!("  on all other paths, through the nodes they share");
+        println!("Graph regions can be annotated with BED files in");
+        println!("  pangenome coordinates using --pangenome-bed, and with");
+        println!("  node lists using --nodes; each line of a node list is");
+        println!("  <label> TAB <segment IDs, e.g. 5,7-9> [TAB #RRGGBB]");
+        println!("Each of --bed, --gff and --vcf can be given more than");
+        println!("  once, and annotation files can also be loaded and");
+        println!("  removed in the Annotations settings tab");
+        println!("GAF alignments can be provided using the --gaf flag,");
+        println!("  filtered with --gaf-min-mapq and --gaf-min-identity");
+        println!("  --gaf-per-base also computes the depth at each base,");
+        println!("  shown as the max depth of each node");
+        println!("BAM and bedGraph signal tracks can be provided using");
+        println!("  --signal <file>, or --signal <file>=<path name> to map");
+        println!("  the entire file onto a single path");
+        println!("The memory used for cached path data can be limited");
+        println!("  with --data-cache-mb <megabytes> (default 1024)");
+        println!("Data sources defined as rhai expressions can be loaded");
+        println!("  from files with `name = expression` lines using");
+        println!("  the --data-script flag");
         std::process::exit(0);
     }
 
@@ -0,0 +1,58 @@
+//! Fixtures shared by the tests
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+};
+
+use waragraph_core::graph::PathIndex;
+
+pub const GFA_PATH: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../test/data/",
+    "A-3105.fa.353ea42.34ee7b1.1576367.smooth.fix.gfa"
+);
+
+/// The test graph, loaded once and shared by all tests
+pub fn test_graph() -> Arc<PathIndex> {
+    static GRAPH: OnceLock<Arc<PathIndex>> = OnceLock::new();
+    GRAPH
+        .get_or_init(|| Arc::new(PathIndex::from_gfa(GFA_PATH).unwrap()))
+        .clone()
+}
+
+/// A file in the temporary directory, removed when dropped, so that
+/// it's also cleaned up when a test fails
+pub struct TempFile(PathBuf);
+
+impl TempFile {
+    /// Writes `contents` to a new file with the extension `ext`
+    pub fn new(ext: &str, contents: impl AsRef<[u8]>) -> std::io::Result<Self> {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let name = format!(
+            "waragraph-test-{}-{}.{ext}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        );
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, contents)?;
+
+        Ok(Self(path))
+    }
+}
+
+impl AsRef<Path> for TempFile {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
@@ -1,8 +1,11 @@
 use crate::annotations::GlobalAnnotationId;
+use crate::app::resource::{DataSourceId, FStats};
 use crate::app::settings_menu::SettingsWindow;
 use crate::app::{AppWindow, SharedState};
+use crate::color::widget::ColorMapWidgetShared;
 use crate::color::ColorMap;
 use crate::context::{ContextQuery, ContextState};
+use crate::gui::annotations::{AnnotationSearchWidget, UserAnnotationsWidget};
 use crate::gui::{GridEntry, RowEntry, RowGridLayout};
 use crate::list::ListView;
 use crate::viewer_1d::annotations::AnnotSlot;
@@ -30,6 +33,7 @@ use waragraph_core::graph::PathIndex;
 use self::cache::{SlotCache, SlotState};
 use self::control::ViewControlWidget;
 use self::render::VizModeConfig;
+use self::tracks::{TrackPalette, VizTrack};
 // use self::util::path_sampled_data_viz_buffer;
 use self::view::View1D;
 use self::widgets::VisualizationModesWidget;
@@ -41,6 +45,7 @@ pub mod control;
 pub mod gui;
 pub mod render;
 pub mod sampler;
+pub mod tracks;
 pub mod util;
 pub mod view;
 pub mod widgets;
@@ -68,25 +73,40 @@ pub struct Viewer1D {
 
     shared: SharedState,
 
-    // active_viz_data_key: String,
-    active_viz_data_key: Arc<RwLock<String>>,
+    /// The tracks shown in each path row, from top to bottom
+    viz_tracks: Arc<RwLock<Vec<VizTrack>>>,
+    // the fallback mode, which is always available
+    path_name_viz: DataSourceId,
     use_linear_sampler: Arc<AtomicCell<bool>>,
 
-    color_mapping: crate::util::Uniform<Arc<AtomicCell<ColorMap>>, 16>,
+    // the color map of the first track, controlled by the widget
+    track_color_map: Arc<AtomicCell<ColorMap>>,
+    color_map_widget: Arc<RwLock<ColorMapWidgetShared>>,
+    // the viz. mode the color map was last reset for
+    color_map_mode: Option<DataSourceId>,
+    viz_data_stats: HashMap<DataSourceId, FStats>,
+
+    track_palette: Option<TrackPalette>,
 
     annotations: annotations::Annots1D,
 
+    /// The path and pangenome positions where a range selection
+    /// started and currently ends, while one is being dragged
+    range_selection: Option<(PathId, Bp, Bp)>,
+
     pub msg_tx: crossbeam::channel::Sender<control::Msg>,
     msg_rx: crossbeam::channel::Receiver<control::Msg>,
 
     cfg: Config,
 
     // NB: very temporary, hopefully; bits are spread all over...
-    viz_mode_config: HashMap<String, VizModeConfig>,
-    viz_samplers: HashMap<String, Arc<dyn sampler::Sampler + 'static>>,
+    viz_mode_config: HashMap<DataSourceId, VizModeConfig>,
+    viz_samplers: HashMap<DataSourceId, Arc<dyn sampler::Sampler + 'static>>,
 
     // NB: also temporary, hopefully
     view_control_widget: ViewControlWidget,
+    annotation_search_widget: AnnotationSearchWidget,
+    user_annotations_widget: UserAnnotationsWidget,
 }
 
 impl Viewer1D {
@@ -172,8 +192,7 @@ impl Viewer1D {
         graph.add_link_from_transient("viz_data_buffer", draw_node, 3);
         graph.add_link_from_transient("sampler", draw_node, 4);
         graph.add_link_from_transient("color_texture", draw_node, 5);
-        graph.add_link_from_transient("color_map", draw_node, 6);
-        graph.add_link_from_transient("transform", draw_node, 7);
+        graph.add_link_from_transient("transform", draw_node, 6);
 
         /*
         graph.add_link_from_transient("color", draw_node, 4);
@@ -193,52 +212,36 @@ impl Viewer1D {
         let path_list_view =
             ListView::new(paths.clone().map(PathId::from), Some(256));
 
-        let active_viz_data_key = "path_name".to_string();
-
         graph.set_node_preprocess_fn(draw_node, move |_ctx, op_state| {
             op_state.vertices = Some(0..6);
             op_state.instances = Some(0..0);
         });
 
-        let color_mapping_val = Arc::new(AtomicCell::new(ColorMap {
+        // the viz. modes for path data sources are created on demand,
+        // by `prepare_viz_mode`
+        let mut viz_samplers = HashMap::default();
+        let mut viz_mode_config = HashMap::default();
+
+        let path_name_viz = util::init_path_name_hash_viz_mode(
+            state,
+            shared,
+            &mut viz_samplers,
+            &mut viz_mode_config,
+        );
+
+        let track_color_map = Arc::new(AtomicCell::new(ColorMap {
             value_range: [0.0, 1.0],
             color_range: [0.0, 1.0],
         }));
 
-        let color_mapping = crate::util::Uniform::new(
-            &state,
-            wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            "Viewer 1D Color Mapping",
-            color_mapping_val.clone(),
-            |color_map| {
-                let data: [u8; 16] = bytemuck::cast(color_map.load());
-                data
-            },
-        )?;
-
-        /*
         let color_map_widget = {
-            let scheme_id =
-                shared.data_color_schemes.get(&active_viz_data_key).unwrap();
-
-            let stats = shared
-                .graph_data_cache
-                .fetch_path_data_blocking(&active_viz_data_key)
-                .as_ref()
-                .unwrap()
-                .global_stats;
-
-            let mut map = color_mapping_val.load();
-            map.value_range = [stats.min, stats.max];
-            color_mapping_val.store(map);
-
             let color_map_widget = ColorMapWidgetShared::new(
                 shared.colors.clone(),
                 "Viewer1D-ColorMapWidget".into(),
-                stats,
-                &active_viz_data_key,
-                *scheme_id,
-                color_mapping_val.clone(),
+                None,
+                &viz_mode_config[&path_name_viz].name,
+                shared.data_color_scheme(path_name_viz),
+                track_color_map.clone(),
             );
 
             let widget = Arc::new(RwLock::new(color_map_widget));
@@ -251,15 +254,16 @@ impl Viewer1D {
 
             widget
         };
-        */
 
-        let active_viz_data_key = Arc::new(RwLock::new(active_viz_data_key));
+        let viz_tracks =
+            Arc::new(RwLock::new(vec![VizTrack::new(path_name_viz)]));
         let use_linear_sampler = Arc::new(AtomicCell::new(false));
 
         {
             let viz_mode_widget = VisualizationModesWidget {
                 shared: shared.clone(),
-                active_viz_data_key: active_viz_data_key.clone(),
+                viz_tracks: viz_tracks.clone(),
+                extra_modes: vec![path_name_viz],
                 use_linear_sampler: use_linear_sampler.clone(),
             };
 
@@ -273,9 +277,13 @@ impl Viewer1D {
         let cfg = {
             let cfg = Config {
                 filter_path_list_by_visibility: Arc::new(true.into()),
+                hidden_annotation_sets: Arc::default(),
             };
 
-            let widget = config::ConfigWidget { cfg: cfg.clone() };
+            let widget = config::ConfigWidget {
+                cfg: cfg.clone(),
+                annotations: shared.annotations.clone(),
+            };
 
             settings_window.register_widget(
                 "1D Viewer",
@@ -286,53 +294,6 @@ impl Viewer1D {
             cfg
         };
 
-        let mut viz_samplers = HashMap::default();
-
-        {
-            let sampler = sampler::PathDataSampler::new(
-                shared.graph.clone(),
-                shared.graph_data_cache.clone(),
-                "depth",
-            );
-
-            viz_samplers.insert(
-                "depth".to_string(),
-                Arc::new(sampler) as Arc<dyn sampler::Sampler + 'static>,
-            );
-        }
-
-        let mut viz_mode_config = {
-            let colors = shared.colors.blocking_read();
-
-            let mut cfg: HashMap<String, VizModeConfig> = HashMap::new();
-
-            let depth = VizModeConfig {
-                name: "depth".to_string(),
-                data_key: "depth".to_string(),
-                color_scheme: colors.get_color_scheme_id("spectral").unwrap(),
-                default_color_map: ColorMap {
-                    value_range: [0.0, 13.0],
-                    color_range: [0.0, 1.0],
-                },
-            };
-
-            let strand = VizModeConfig {
-                name: "strand".to_string(),
-                data_key: "strand".to_string(),
-                color_scheme: colors.get_color_scheme_id("black_red").unwrap(),
-                default_color_map: ColorMap {
-                    value_range: [0.0, 1.0],
-                    color_range: [0.0, 1.0],
-                },
-            };
-
-            for c in [depth, strand] {
-                cfg.insert(c.name.clone(), c);
-            }
-
-            cfg
-        };
-
         log::error!("Initialized in {} seconds", t0.elapsed().as_secs_f32());
 
         let row_count = 512;
@@ -347,18 +308,22 @@ impl Viewer1D {
 
         let annotations = annotations::Annots1D::default();
 
-        util::init_path_name_hash_viz_mode(
-            state,
-            shared,
-            &mut viz_samplers,
-            &mut viz_mode_config,
-        );
-
         let (msg_tx, msg_rx) = crossbeam::channel::unbounded();
 
         let view_control_widget =
             ViewControlWidget::new(shared, msg_tx.clone());
 
+        let annotation_search_widget = AnnotationSearchWidget::new(
+            shared.graph.clone(),
+            shared.annotations.clone(),
+        );
+
+        let user_annotations_widget = UserAnnotationsWidget::new(
+            shared.graph.clone(),
+            shared.annotations.clone(),
+            shared.settings_ctx.clone(),
+        );
+
         Ok(Viewer1D {
             render_graph: graph,
             draw_path_slot: draw_node,
@@ -378,22 +343,30 @@ impl Viewer1D {
             shared: shared.clone(),
 
             annotations,
+            range_selection: None,
 
             msg_tx,
             msg_rx,
 
             view_control_widget,
+            annotation_search_widget,
+            user_annotations_widget,
 
             viz_mode_config,
             viz_samplers,
 
-            active_viz_data_key,
+            viz_tracks,
+            path_name_viz,
             use_linear_sampler,
 
-            color_mapping,
+            track_color_map,
 
             cfg,
-            // color_map_widget,
+            color_map_widget,
+            color_map_mode: None,
+            viz_data_stats: HashMap::default(),
+
+            track_palette: None,
         })
     }
 
@@ -426,6 +399,181 @@ impl Viewer1D {
 
 impl Viewer1D {
     const COLUMN_SEPARATOR_ID: &'static str = "Viewer1D-Column-Separator";
+
+    /// Makes sure there's a sampler and viz. mode config for the
+    /// path data source `data_key`, creating them if needed, so that
+    /// data sources registered at runtime can be visualized.
+    ///
+    /// Returns `false` if the data source has no path data.
+    fn prepare_viz_mode(
+        &mut self,
+        tokio_rt: &tokio::runtime::Handle,
+        data_key: DataSourceId,
+    ) -> bool {
+        let data_cache = &self.shared.graph_data_cache;
+
+        if !self.viz_samplers.contains_key(&data_key) {
+            if !data_cache.has_path_data(data_key) {
+                return false;
+            }
+
+            let sampler = sampler::PathDataSampler::new(
+                self.shared.graph.clone(),
+                data_cache.clone(),
+                data_key,
+            );
+
+            self.viz_samplers.insert(
+                data_key,
+                Arc::new(sampler) as Arc<dyn sampler::Sampler + 'static>,
+            );
+        }
+
+        if !self.viz_mode_config.contains_key(&data_key) {
+            let meta = data_cache.data_source_meta(data_key);
+
+            let fixed_range = meta.value_domain.fixed_range();
+
+            // the stats of the graph-level data, if the source has
+            // any, are used for the color map widget and, if the
+            // domain doesn't have a fixed range, for the default value
+            // range; they're loaded in the background, and the config
+            // is updated once they're ready
+            if data_cache.has_graph_data(data_key) {
+                let data_cache = data_cache.clone();
+                let msg_tx = self.msg_tx.clone();
+
+                tokio_rt.spawn(async move {
+                    match data_cache.fetch_graph_data(data_key).await {
+                        Ok(data) if !data.stats.is_empty() => {
+                            let _ = msg_tx.send(control::Msg::VizDataStats {
+                                data: data_key,
+                                stats: Box::new(data.stats.clone()),
+                            });
+                        }
+                        Ok(_) => (),
+                        Err(e) => log::error!("{e:?}"),
+                    }
+                });
+            }
+
+            let value_range = fixed_range.unwrap_or([0.0, 1.0]);
+
+            let config = VizModeConfig {
+                name: meta.name.clone(),
+                data_key,
+                color_scheme: self.shared.data_color_scheme(data_key),
+                default_color_map: ColorMap {
+                    value_range,
+                    color_range: [0.0, 1.0],
+                },
+            };
+
+            self.viz_mode_config.insert(data_key, config);
+        }
+
+        true
+    }
+
+    /// Prepares the viz. modes of the tracks, removing any tracks that
+    /// can't be shown, and updates the palette and color mappings used
+    /// when sampling the tracks. Returns the tracks to show.
+    fn prepare_tracks(
+        &mut self,
+        state: &raving_wgpu::State,
+        tokio_rt: &tokio::runtime::Handle,
+    ) -> Vec<VizTrack> {
+        let mut tracks = self.viz_tracks.blocking_read().clone();
+        let track_count = tracks.len();
+
+        // slots are keyed by path and data source, so each data source
+        // can only be shown in one track
+        let mut seen = Vec::with_capacity(tracks.len());
+        tracks.retain(|track| {
+            if seen.contains(&track.data_key) {
+                return false;
+            }
+            seen.push(track.data_key);
+
+            if !self.prepare_viz_mode(tokio_rt, track.data_key) {
+                let name = &self
+                    .shared
+                    .graph_data_cache
+                    .data_source_meta(track.data_key)
+                    .name;
+                log::error!("Data source `{name}` can't be visualized");
+                return false;
+            }
+
+            true
+        });
+
+        if tracks.is_empty() {
+            tracks.push(VizTrack::new(self.path_name_viz));
+        }
+
+        if tracks.len() != track_count {
+            *self.viz_tracks.blocking_write() = tracks.clone();
+        }
+
+        let track_scheme = |track: &VizTrack| {
+            track.color_scheme.unwrap_or_else(|| {
+                self.shared.data_color_scheme(track.data_key)
+            })
+        };
+
+        // the color map of the first track is reset to the viz. mode's
+        // default when the mode changes, and is otherwise left to the
+        // color map widget
+        let first = tracks[0];
+        if self.color_map_mode != Some(first.data_key) {
+            if let Some(cfg) = self.viz_mode_config.get(&first.data_key) {
+                self.track_color_map.store(cfg.default_color_map);
+
+                self.color_map_widget.blocking_write().update(
+                    self.viz_data_stats.get(&first.data_key).cloned(),
+                    &cfg.name,
+                    track_scheme(&first),
+                );
+            }
+            self.color_map_mode = Some(first.data_key);
+        }
+
+        let mut schemes = Vec::with_capacity(tracks.len());
+        for track in &tracks {
+            let scheme = track_scheme(track);
+            if !schemes.contains(&scheme) {
+                schemes.push(scheme);
+            }
+        }
+
+        if self.track_palette.as_ref().map(|p| p.schemes()) != Some(&schemes) {
+            let colors = self.shared.colors.blocking_read();
+            self.track_palette =
+                Some(TrackPalette::new(state, &colors, schemes));
+        }
+
+        let palette = self.track_palette.as_ref().unwrap();
+
+        for (ix, track) in tracks.iter().enumerate() {
+            let color_map = if ix == 0 {
+                self.track_color_map.load()
+            } else {
+                self.viz_mode_config[&track.data_key].default_color_map
+            };
+
+            let Some(mapping) =
+                palette.track_mapping(track_scheme(track), color_map)
+            else {
+                continue;
+            };
+
+            self.slot_cache
+                .set_color_map(track.data_key, mapping.shader_color_map());
+        }
+
+        tracks
+    }
 }
 
 impl AppWindow for Viewer1D {
@@ -443,9 +591,27 @@ impl AppWindow for Viewer1D {
                 control::Msg::View(cmd) => {
                     cmd.apply(&self.shared, &mut self.view)
                 }
+                control::Msg::VizDataStats { data, stats } => {
+                    let meta =
+                        self.shared.graph_data_cache.data_source_meta(data);
+                    if meta.value_domain.fixed_range().is_none() {
+                        if let Some(cfg) = self.viz_mode_config.get_mut(&data) {
+                            cfg.default_color_map.value_range =
+                                stats.auto_range();
+                        }
+                    }
+                    self.viz_data_stats.insert(data, *stats);
+
+                    // reset the color map if the mode is already shown
+                    if self.color_map_mode == Some(data) {
+                        self.color_map_mode = None;
+                    }
+                }
             }
         }
 
+        let viz_tracks = self.prepare_tracks(state, tokio_rt);
+
         egui_ctx.begin_frame(&window.window);
 
         let time = egui_ctx.ctx().input(|i| i.time);
@@ -483,6 +649,34 @@ impl AppWindow for Viewer1D {
                 .max_width(screen_rect.width() * 0.5)
                 .show(egui_ctx.ctx(), |ui| {
                     self.view_control_widget.show(ui);
+
+                    ui.separator();
+
+                    egui::CollapsingHeader::new("Search annotations").show(
+                        ui,
+                        |ui| {
+                            if let Some(annot_id) =
+                                self.annotation_search_widget.show(ui)
+                            {
+                                context_state.set(
+                                    "AnnotationSearch",
+                                    ["goto"],
+                                    annot_id,
+                                );
+                            }
+                        },
+                    );
+
+                    ui.separator();
+
+                    let has_pending =
+                        self.user_annotations_widget.has_pending();
+
+                    egui::CollapsingHeader::new("User annotations")
+                        .open(has_pending.then_some(true))
+                        .show(ui, |ui| {
+                            self.user_annotations_widget.show(ui);
+                        });
                 });
 
             let side_panel_rect = side_panel.response.rect;
@@ -500,7 +694,6 @@ impl AppWindow for Viewer1D {
 
         let row_grid_layout = {
             use taffy::prelude::*;
-            let data_id = self.active_viz_data_key.blocking_read().clone();
 
             let mut row_grid_layout: RowGridLayout<gui::SlotElem> =
                 RowGridLayout::new();
@@ -547,9 +740,63 @@ impl AppWindow for Viewer1D {
 
             let should_filter = self.cfg.filter_path_list_by_visibility.load();
 
+            let annotation_store = self.shared.annotations.blocking_read();
+            let hidden_sets = self.cfg.hidden_annotation_sets.blocking_read();
+
+            // drop the annotation slots of sets that have been removed
+            // or replaced; they're rebuilt below, when the paths are
+            // visible
+            self.annotations.sync_with_store(&annotation_store);
+
+            // the annotations that aren't on a path get their own lanes,
+            // below the header
+            let mut pangenome_rows = Vec::new();
+
+            for (set_id, set) in annotation_store.annotation_sets.iter() {
+                if hidden_sets.contains(set_id)
+                    || set.pangenome_annotations.is_empty()
+                {
+                    continue;
+                }
+
+                let slot_id = self
+                    .annotations
+                    .get_pangenome_slot_id(*set_id)
+                    .unwrap_or_else(|| {
+                        let annot_slot = AnnotSlot::new_from_annotations(
+                            &self.shared.graph,
+                            *set_id,
+                            annotations::slot_annotations(
+                                set,
+                                &set.pangenome_annotations,
+                            ),
+                        );
+                        self.annotations.insert_slot(
+                            None,
+                            set.clone(),
+                            annot_slot,
+                        )
+                    });
+
+                pangenome_rows.push(RowEntry {
+                    grid_template_columns: vec![
+                        points(info_col_width),
+                        fr(1.0),
+                    ],
+                    grid_template_rows: vec![points(60.0)],
+                    column_data: vec![GridEntry::new(
+                        [1, 2],
+                        gui::SlotElem::Annotations {
+                            annotation_slot_id: slot_id,
+                        },
+                    )],
+                    ..RowEntry::default()
+                });
+            }
+
             let layout_result = row_grid_layout.fill_from_slice_index(
                 main_view_rect.height(),
-                [header_row],
+                std::iter::once(header_row).chain(pangenome_rows),
                 &self.path_list_view.as_slice(),
                 view_offset,
                 |&(_list_ix, path_id)| {
@@ -569,45 +816,56 @@ impl AppWindow for Viewer1D {
                             points(info_col_width),
                             fr(1.0),
                         ],
-                        grid_template_rows: vec![points(20.0)],
+                        grid_template_rows: viz_tracks
+                            .iter()
+                            .map(|track| points(track.height))
+                            .collect(),
                         column_data: vec![],
                         ..RowEntry::default()
                     };
 
-                    let mut data_row = 1;
+                    let mut data_row: i16 = 1;
 
-                    if let Some(a_slot_id) =
-                        self.annotations.get_path_slot_id(path_id)
-                    {
-                        // println!("adding annot slot");
-                        // if annotation slot is present, change the grid_template_row field
-                        // and append the extra column data
-                        row_entry.grid_template_rows.insert(0, points(50.0));
+                    // each visible annotation set on the path gets a
+                    // lane above the path data
+                    let annot_slots = annotation_store
+                        .get_sets_for_path(path_id)
+                        .filter(|(set_id, _)| !hidden_sets.contains(set_id))
+                        .filter_map(|(set_id, _)| {
+                            self.annotations.get_slot_id(path_id, set_id)
+                        });
+
+                    for a_slot_id in annot_slots {
+                        row_entry
+                            .grid_template_rows
+                            .insert(data_row as usize - 1, points(60.0));
 
                         row_entry.column_data.push(GridEntry::new(
-                            [1, 2],
+                            [data_row, 2],
                             gui::SlotElem::Annotations {
                                 annotation_slot_id: a_slot_id,
                             },
                         ));
 
-                        data_row = 2;
+                        data_row += 1;
                     }
 
-                    // add path name and path data
-                    row_entry.column_data.extend([
-                        GridEntry::new(
-                            [data_row, 1],
-                            gui::SlotElem::PathName { path_id },
-                        ),
-                        GridEntry::new(
-                            [data_row, 2],
+                    // add path name, next to the first track, and the
+                    // path data of each track
+                    row_entry.column_data.push(GridEntry::new(
+                        [data_row, 1],
+                        gui::SlotElem::PathName { path_id },
+                    ));
+
+                    for (ix, track) in viz_tracks.iter().enumerate() {
+                        row_entry.column_data.push(GridEntry::new(
+                            [data_row + ix as i16, 2],
                             gui::SlotElem::PathData {
                                 path_id,
-                                data_id: data_id.clone(),
+                                data_id: track.data_key,
                             },
-                        ),
-                    ]);
+                        ));
+                    }
 
                     Some(row_entry)
                 },
@@ -651,6 +909,28 @@ impl AppWindow for Viewer1D {
             let _ = self.msg_tx.send(Msg::View(ViewCmd::GotoNode { node }));
         }
 
+        // annotations chosen in the search widget of any viewer
+        if let Some(&annot_id) = context_state
+            .query_get_cast::<_, GlobalAnnotationId>(
+                Some("AnnotationSearch"),
+                ["goto"],
+            )
+        {
+            use control::{Msg, ViewCmd};
+            let store = self.shared.annotations.blocking_read();
+
+            if let Some(annot) = store
+                .annotation_sets
+                .get(&annot_id.set_id)
+                .and_then(|set| set.get(annot_id.annot_id))
+            {
+                let _ = self.msg_tx.send(Msg::View(ViewCmd::GotoRange {
+                    path: annot.path(),
+                    range: annot.range.clone(),
+                }));
+            }
+        }
+
         let mut data_slots: HashMap<_, Vec<_>> = HashMap::new();
         let mut viz_slot_rect_map = HashMap::new();
 
@@ -684,25 +964,21 @@ impl AppWindow for Viewer1D {
                         let rect = crate::gui::layout_egui_rect(&layout);
                         path_slot_region = path_slot_region.union(rect);
 
-                        let key = data_id.to_string();
                         data_slots
-                            .entry(key)
+                            .entry(*data_id)
                             .or_default()
                             .push((*path_id, rect));
-                        viz_slot_rect_map
-                            .insert((*path_id, data_id.to_string()), rect);
+                        viz_slot_rect_map.insert((*path_id, *data_id), rect);
 
                         if let Some((path, g_annot_id)) = hovered_annot {
                             if path == path_id {
                                 // draw regions here
-                                let annot_slot_id = self
-                                    .annotations
-                                    .get_path_slot_id(*path_id)
-                                    .unwrap();
-
                                 let regions = self
                                     .annotations
-                                    .get(&annot_slot_id)
+                                    .get_slot_id(*path_id, g_annot_id.set_id)
+                                    .and_then(|slot_id| {
+                                        self.annotations.get(&slot_id)
+                                    })
                                     .and_then(|slot| {
                                         slot.annotation_ranges
                                             .get(&g_annot_id.annot_id)
@@ -715,7 +991,7 @@ impl AppWindow for Viewer1D {
                                     .annotations
                                     .blocking_read()
                                     .get(*g_annot_id)
-                                    .color
+                                    .and_then(|annot| annot.color)
                                     .unwrap_or(egui::Color32::RED);
 
                                 shapes.extend(
@@ -783,16 +1059,25 @@ impl AppWindow for Viewer1D {
 
         egui_ctx.ctx().fonts(|fonts| {
             for (data_key, path_rects) in data_slots {
-                let sampler = self.viz_samplers.get(&data_key).unwrap().clone();
+                let Some(sampler) = self.viz_samplers.get(&data_key).cloned()
+                else {
+                    continue;
+                };
+
                 let result = self.slot_cache.sample_with(
                     state,
                     tokio_rt,
                     &self.view,
-                    data_key.as_str(),
+                    data_key,
                     path_rects.iter().map(|(path, _)| *path),
                     sampler,
                 );
 
+                // the sequence is only drawn in the first track
+                if data_key != viz_tracks[0].data_key {
+                    continue;
+                }
+
                 for (path, rect) in path_rects {
                     let view_range = self.view.range().clone();
 
@@ -839,41 +1124,45 @@ impl AppWindow for Viewer1D {
 
         {
             let annotations = self.shared.annotations.blocking_read();
+            let hidden_sets = self.cfg.hidden_annotation_sets.blocking_read();
 
             for slot_key in viz_slot_rect_map.keys() {
                 let (path, _data_key) = slot_key;
                 let path = *path;
-                // initialize annotation slots if necessary; this part is kinda janky
-
-                let has_annot_slot =
-                    self.annotations.get_path_slot_id(path).is_some();
+                // initialize the annotation slots of the visible sets
+                // on the path if necessary
+                for (set_id, set) in annotations.get_sets_for_path(path) {
+                    if hidden_sets.contains(&set_id)
+                        || self.annotations.get_slot_id(path, set_id).is_some()
+                    {
+                        continue;
+                    }
 
-                // TODO: annotations should be able to be sourced from
-                // any annot. set for a slot, and not only be
-                // initialized once and then forgotten about
-                if let Some((set_id, set)) =
-                    annotations.get_sets_for_path(path).next()
-                {
-                    if !has_annot_slot {
-                        if let Some(annots) = set.path_annotations.get(&path) {
-                            let annot_items = annots
-                                .iter()
-                                .filter_map(|&i| set.annotations.get(i))
-                                .map(|annot| {
-                                    let shape_fn =
-                                        annotations::text_shape(&annot.label);
-                                    (path, annot.range.clone(), shape_fn)
-                                });
-
-                            let annot_slot = AnnotSlot::new_from_path_space(
-                                &self.shared.graph,
-                                set_id,
-                                annot_items,
-                            );
+                    // the annotations on the whole path
+                    let annots = set
+                        .index
+                        .path_overlapping(path, Bp(0)..Bp(u64::MAX))
+                        .map(|(_, annot_id)| annot_id.0)
+                        .collect::<Vec<_>>();
 
-                            self.annotations.insert_slot(path, annot_slot);
-                        }
+                    if annots.is_empty() {
+                        continue;
                     }
+
+                    let annot_items =
+                        annotations::slot_annotations(set, &annots);
+
+                    let annot_slot = AnnotSlot::new_from_annotations(
+                        &self.shared.graph,
+                        set_id,
+                        annot_items,
+                    );
+
+                    self.annotations.insert_slot(
+                        Some(path),
+                        set.clone(),
+                        annot_slot,
+                    );
                 }
 
                 // add spinners
@@ -921,35 +1210,6 @@ impl AppWindow for Viewer1D {
             }
         }
 
-        // NB: disabling the color map widget for the time being
-        /*
-        {
-            let mut color_map_widget = self.color_map_widget.blocking_write();
-
-            let active_viz_data_key = self.active_viz_data_key.blocking_read();
-
-            let data_cache = &self.shared.graph_data_cache;
-            let stats_getter = |key: &str| {
-                let data = data_cache.fetch_path_data_blocking(key)?;
-                Some(data.global_stats)
-            };
-
-            let scheme_id = self
-                .shared
-                .data_color_schemes
-                .get(active_viz_data_key.as_str())
-                .unwrap();
-
-            color_map_widget.update(
-                stats_getter,
-                active_viz_data_key.as_str(),
-                *scheme_id,
-            );
-
-            self.color_mapping.write_buffer(state);
-        }
-        */
-
         // update uniform
         {
             let data = self.slot_cache.get_view_transform(&self.view);
@@ -1033,7 +1293,86 @@ impl AppWindow for Viewer1D {
                     }
                 }
 
-                if path_slots.dragged_by(egui::PointerButton::Primary) {
+                // shift + drag on a path selects a range on it, to
+                // create an annotation from
+                let pointer_pan_pos = |pos: egui::Pos2| {
+                    let left = path_slot_region.left();
+                    let width = path_slot_region.width();
+                    let rel_x = ((pos.x - left) / width).clamp(0.0, 1.0);
+                    Bp(self.view.offset()
+                        + (rel_x * self.view.len() as f32) as u64)
+                };
+
+                let selecting = ui.input(|i| i.modifiers.shift);
+
+                if selecting
+                    && path_slots.drag_started_by(egui::PointerButton::Primary)
+                {
+                    self.range_selection =
+                        path_slots.interact_pointer_pos().and_then(|pos| {
+                            let path = viz_slot_rect_map.iter().find_map(
+                                |((path, _), rect)| {
+                                    rect.contains(pos).then_some(*path)
+                                },
+                            )?;
+                            let pan_pos = pointer_pan_pos(pos);
+                            Some((path, pan_pos, pan_pos))
+                        });
+                }
+
+                if let Some((path, start, end)) = self.range_selection.as_mut()
+                {
+                    if let Some(pos) = path_slots.interact_pointer_pos() {
+                        *end = pointer_pan_pos(pos);
+                    }
+
+                    let range = (*start).min(*end)..(*start).max(*end);
+
+                    for ((slot_path, _), rect) in viz_slot_rect_map.iter() {
+                        if slot_path != path {
+                            continue;
+                        }
+
+                        if let Some(x_range) = self
+                            .view
+                            .map_bp_interval_to_screen_x(&range, &rect.x_range())
+                        {
+                            shapes.push(gui::fill_h_range_of_rect(
+                                egui::Color32::from_white_alpha(60),
+                                *rect,
+                                x_range,
+                            ));
+                        }
+                    }
+                }
+
+                if path_slots.drag_released() {
+                    if let Some((path, start, end)) =
+                        self.range_selection.take()
+                    {
+                        let range = start.min(end)..start.max(end);
+
+                        let path_range =
+                            crate::annotations::index::pangenome_range_path_range(
+                                &self.shared.graph,
+                                path,
+                                &range,
+                                start,
+                            );
+
+                        // a shift-click without dragging selects nothing
+                        if let Some(path_range) =
+                            path_range.filter(|r| r.start < r.end)
+                        {
+                            self.user_annotations_widget
+                                .begin_annotation(path, path_range);
+                        }
+                    }
+                }
+
+                if path_slots.dragged_by(egui::PointerButton::Primary)
+                    && self.range_selection.is_none()
+                {
                     let dx =
                         path_slots.drag_delta().x / path_slot_region.width();
                     self.view.translate_norm_f32(-dx);
@@ -1119,10 +1458,26 @@ impl AppWindow for Viewer1D {
                 }
 
                 for &(slot_id, rect) in annot_slots.iter() {
+                    let set_name = self
+                        .annotations
+                        .slot_set(slot_id)
+                        .map(|set| set.name.clone());
+
                     if let Some(annot_slot) = self.annotations.get_mut(&slot_id)
                     {
                         let painter = ui.painter_at(rect);
 
+                        // label the lane with the annotation set name
+                        if let Some(name) = set_name {
+                            painter.text(
+                                rect.left_top(),
+                                egui::Align2::LEFT_TOP,
+                                name,
+                                egui::FontId::proportional(12.0),
+                                egui::Color32::GRAY,
+                            );
+                        }
+
                         let cursor_pos =
                             ui.input(|input| input.pointer.hover_pos());
                         let interacted =
@@ -1133,14 +1488,25 @@ impl AppWindow for Viewer1D {
                             let global_id =
                                 GlobalAnnotationId { set_id, annot_id };
 
-                            let path = self
+                            // annotations in the pangenome lanes aren't
+                            // highlighted on any particular path
+                            if let Some(path) = self
                                 .annotations
                                 .get_annotation_slot_path(slot_id)
-                                .unwrap();
-
-                            let ctx_data = (path, global_id);
-
-                            context_state.set("Viewer1D", ["hover"], ctx_data);
+                            {
+                                let ctx_data = (path, global_id);
+                                context_state.set(
+                                    "Viewer1D",
+                                    ["hover"],
+                                    ctx_data,
+                                );
+                            } else {
+                                context_state.set(
+                                    "Viewer1D",
+                                    ["hover"],
+                                    global_id,
+                                );
+                            }
                         }
                     }
                 }
@@ -1210,6 +1576,15 @@ impl AppWindow for Viewer1D {
                             ui.label(format!("Node {}", node.ix()));
                             ui.label(format!("Path {path_name}"));
                             ui.label(format!("Pos {} bp", pos.0));
+
+                            let annotations =
+                                self.shared.annotations.blocking_read();
+                            util::path_pos_annotations_tooltip(
+                                &annotations,
+                                *path,
+                                *pos,
+                                ui,
+                            );
                         },
                     );
                 }
@@ -1312,17 +1687,6 @@ impl AppWindow for Viewer1D {
         swapchain_view: &wgpu::TextureView,
         encoder: &mut wgpu::CommandEncoder,
     ) -> anyhow::Result<()> {
-        let data_id = self.active_viz_data_key.blocking_read().clone();
-        let viz_mode_color = self
-            .viz_mode_config
-            .get(&data_id)
-            .unwrap_or_else(|| panic!("Config not found for data {data_id}"));
-
-        self.color_mapping.update_data(|cmap| {
-            cmap.store(viz_mode_color.default_color_map);
-        });
-        self.color_mapping.write_buffer(&state);
-
         let has_vertices = self.slot_cache.vertex_buffer.is_some();
 
         if !has_vertices {
@@ -1387,20 +1751,13 @@ impl AppWindow for Viewer1D {
                 colors.nearest_sampler.clone()
             };
 
-            let data_key = self.active_viz_data_key.blocking_read().clone();
-
-            let id = self
-                .shared
-                .data_color_schemes
-                .blocking_read()
-                .get(&data_key)
-                .copied()
-                .unwrap();
+            let Some(palette) = self.track_palette.as_ref() else {
+                return Ok(());
+            };
 
-            let scheme = colors.get_color_scheme(id);
-            let size = [scheme.colors.len() as u32, 1];
+            let size = [palette.width(), 1];
 
-            (sampler, colors.get_color_scheme_texture(id).unwrap(), size)
+            (sampler, palette.texture.clone(), size)
         };
 
         let texture = &tex.0;
@@ -1428,15 +1785,6 @@ impl AppWindow for Viewer1D {
             },
         );
 
-        transient_res.insert(
-            "color_map".into(),
-            InputResource::Buffer {
-                size: self.color_mapping.buffer_size(),
-                stride: None,
-                buffer: self.color_mapping.buffer(),
-            },
-        );
-
         if let Some(transforms) = self.slot_cache.transform_buffer.as_ref() {
             transient_res.insert(
                 "transform".into(),
@@ -10,7 +10,11 @@ use tokio::{sync::Mutex, task::JoinHandle};
 use ultraviolet::Vec2;
 use waragraph_core::graph::{Bp, PathId, PathIndex};
 
-use crate::annotations::{AnnotationId, AnnotationSetId};
+use crate::annotations::index::target_range_pangenome_ranges;
+use crate::annotations::{
+    AnnotationId, AnnotationSet, AnnotationSetId, AnnotationStore,
+    AnnotationTarget, FeaturePart,
+};
 
 use super::view::View1D;
 
@@ -19,17 +23,32 @@ use super::view::View1D;
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct AnnotSlotId(pub(super) u32);
 
+/// The annotation slots of the 1D viewer, one per path and annotation
+/// set, i.e. each set is shown as its own lane in the path's row, and
+/// one per set for the annotations in it that aren't on a path
 #[derive(Default)]
 pub struct Annots1D {
     slots: HashMap<AnnotSlotId, AnnotSlot>,
     next_slot_id: AnnotSlotId,
 
-    path_annot_slot: BiHashMap<PathId, AnnotSlotId>,
+    path_annot_slot: BiHashMap<(PathId, AnnotationSetId), AnnotSlotId>,
+
+    // the slots of the annotations in each set that aren't on a path
+    pangenome_annot_slot: BiHashMap<AnnotationSetId, AnnotSlotId>,
+
+    // the annotation sets the slots were built from, and the
+    // `AnnotationStore` generation they were last checked against
+    slot_sources: HashMap<AnnotSlotId, Arc<AnnotationSet>>,
+    store_generation: Option<u64>,
 }
 
 impl Annots1D {
-    pub fn get_path_slot_id(&self, path: PathId) -> Option<AnnotSlotId> {
-        let slot = self.path_annot_slot.get_by_left(&path)?;
+    pub fn get_slot_id(
+        &self,
+        path: PathId,
+        set_id: AnnotationSetId,
+    ) -> Option<AnnotSlotId> {
+        let slot = self.path_annot_slot.get_by_left(&(path, set_id))?;
         Some(*slot)
     }
 
@@ -37,22 +56,79 @@ impl Annots1D {
         &self,
         annot_slot: AnnotSlotId,
     ) -> Option<PathId> {
-        let path = self.path_annot_slot.get_by_right(&annot_slot)?;
+        let (path, _set) = self.path_annot_slot.get_by_right(&annot_slot)?;
         Some(*path)
     }
 
+    pub fn get_pangenome_slot_id(
+        &self,
+        set_id: AnnotationSetId,
+    ) -> Option<AnnotSlotId> {
+        self.pangenome_annot_slot.get_by_left(&set_id).copied()
+    }
+
+    /// Inserts the slot for the annotations of `set` on `path`, or for
+    /// those not on a path if `path` is `None`
     pub fn insert_slot(
         &mut self,
-        path: PathId,
+        path: Option<PathId>,
+        set: Arc<AnnotationSet>,
         slot: AnnotSlot,
     ) -> AnnotSlotId {
         let slot_id = self.next_slot_id;
+        if let Some(path) = path {
+            self.path_annot_slot.insert((path, slot.set_id), slot_id);
+        } else {
+            self.pangenome_annot_slot.insert(slot.set_id, slot_id);
+        }
         self.slots.insert(slot_id, slot);
-        self.path_annot_slot.insert(path, slot_id);
+        self.slot_sources.insert(slot_id, set);
         self.next_slot_id = AnnotSlotId(slot_id.0 + 1);
         slot_id
     }
 
+    /// Removes the slots whose annotation set has been removed from,
+    /// or replaced in, the store, so that they're rebuilt on demand
+    pub fn sync_with_store(&mut self, store: &AnnotationStore) {
+        if self.store_generation == Some(store.generation()) {
+            return;
+        }
+        self.store_generation = Some(store.generation());
+
+        let stale = self
+            .path_annot_slot
+            .iter()
+            .map(|((_path, set_id), slot_id)| (set_id, slot_id))
+            .chain(self.pangenome_annot_slot.iter())
+            .filter(|(set_id, slot_id)| {
+                let current = store.annotation_sets.get(set_id);
+                let source = self.slot_sources.get(slot_id);
+                match (current, source) {
+                    (Some(current), Some(source)) => {
+                        !Arc::ptr_eq(current, source)
+                    }
+                    _ => true,
+                }
+            })
+            .map(|(_, slot_id)| *slot_id)
+            .collect::<Vec<_>>();
+
+        for slot_id in stale {
+            self.path_annot_slot.remove_by_right(&slot_id);
+            self.pangenome_annot_slot.remove_by_right(&slot_id);
+            self.slots.remove(&slot_id);
+            self.slot_sources.remove(&slot_id);
+        }
+    }
+
+    /// The annotation set the slot was built from
+    pub fn slot_set(
+        &self,
+        slot_id: AnnotSlotId,
+    ) -> Option<&Arc<AnnotationSet>> {
+        self.slot_sources.get(&slot_id)
+    }
+
     pub fn get(&self, slot_id: &AnnotSlotId) -> Option<&AnnotSlot> {
         self.slots.get(slot_id)
     }
@@ -64,6 +140,13 @@ impl Annots1D {
 
 type AnnotsTreeObj = GeomWithData<Line<(i64, i64)>, AnnotationId>;
 
+type FeatureTreeObj =
+    GeomWithData<Line<(i64, i64)>, (AnnotationId, FeaturePart)>;
+
+/// Height of the strip at the bottom of an annotation slot where the
+/// annotation features (blocks and the lines between them) are drawn
+const FEATURE_TRACK_HEIGHT: f32 = 10.0;
+
 type ShapeFn = Box<dyn Fn(&egui::Painter, egui::Pos2) -> egui::Shape>;
 
 pub fn text_shape<L: ToString>(label: L) -> ShapeFn {
@@ -84,6 +167,45 @@ pub fn text_shape<L: ToString>(label: L) -> ShapeFn {
     })
 }
 
+/// An annotation to display in a slot
+pub struct SlotAnnotation {
+    pub id: AnnotationId,
+    pub target: AnnotationTarget,
+    /// The pangenome-space ranges of the annotation, see
+    /// `AnnotationIndex::pangenome_ranges`
+    pub ranges: Vec<std::ops::Range<Bp>>,
+    /// The parts of the annotation in the coordinates of its target,
+    /// see `Annotation::feature_parts`
+    pub parts: Vec<(std::ops::Range<Bp>, FeaturePart)>,
+    pub color: egui::Color32,
+    pub shape: ShapeFn,
+}
+
+/// The top-level annotations among `indices` in the set, to display
+/// in a slot; child features are drawn as part of their parents'
+/// blocks
+pub fn slot_annotations<'a>(
+    set: &'a AnnotationSet,
+    indices: &'a [usize],
+) -> impl Iterator<Item = SlotAnnotation> + 'a {
+    indices.iter().filter_map(|&i| {
+        let annot = set.annotations.get(i)?;
+
+        if annot.parent.is_some() {
+            return None;
+        }
+
+        Some(SlotAnnotation {
+            id: AnnotationId(i),
+            target: annot.target.clone(),
+            ranges: set.index.pangenome_ranges(AnnotationId(i)).to_vec(),
+            parts: annot.feature_parts(),
+            color: annot.color.unwrap_or(egui::Color32::GRAY),
+            shape: text_shape(&annot.label),
+        })
+    })
+}
+
 // Container for annotations displayed in a single 1D slot,
 // with the annotations "flattened" to the pangenome coordinate
 // space, down from the path-range space
@@ -94,7 +216,10 @@ pub struct AnnotSlot {
     annots: Arc<RTree<AnnotsTreeObj>>,
     pub annotation_ranges: HashMap<AnnotationId, Vec<std::ops::Range<Bp>>>,
 
-    shape_fns: Vec<ShapeFn>,
+    features: RTree<FeatureTreeObj>,
+    colors: HashMap<AnnotationId, egui::Color32>,
+
+    shape_fns: HashMap<AnnotationId, ShapeFn>,
 
     dynamics: Arc<Mutex<AnnotSlotDynamics>>,
 
@@ -440,7 +565,7 @@ impl AnnotSlotDynamics {
                 6.0
             };
 
-            let y0 = screen_rect.bottom() - 8.0;
+            let y0 = screen_rect.bottom() - 8.0 - FEATURE_TRACK_HEIGHT;
             let y = y0 - yd * row_ix as f32;
 
             positions.push((annot_id, Vec2::new(x, y)));
@@ -479,102 +604,62 @@ impl AnnotObj {
 }
 
 impl AnnotSlot {
-    /// Initializes an annotation slot given items in pangenome space.
-    ///
-    pub fn new_from_pangenome_space(
-        set_id: AnnotationSetId,
-        annotations: impl IntoIterator<Item = (std::ops::Range<Bp>, ShapeFn)>,
-    ) -> Self {
-        let mut annot_objs = Vec::new();
-        let mut shape_fns = Vec::new();
-
-        let mut annotation_ranges: HashMap<
-            AnnotationId,
-            Vec<std::ops::Range<Bp>>,
-        > = HashMap::default();
-
-        for (a_id, (range, shape)) in annotations.into_iter().enumerate() {
-            let a_id = AnnotationId(a_id);
-            let geom =
-                Line::new((range.start.0 as i64, 0), (range.end.0 as i64, 0));
-            annot_objs.push(GeomWithData::new(geom, a_id));
-            shape_fns.push(shape);
-
-            annotation_ranges.entry(a_id).or_default().push(range);
-        }
-
-        for ranges in annotation_ranges.values_mut() {
-            ranges.sort_by_key(|r| (r.start, r.end));
-        }
-
-        let annots = RTree::<AnnotsTreeObj>::bulk_load(annot_objs);
-
-        Self {
-            set_id,
-            annots: Arc::new(annots),
-            annotation_ranges,
-            shape_fns,
-            dynamics: Default::default(),
-            task: None,
-            positions: Vec::new(),
-            shape_sizes: Vec::new(),
-        }
-    }
-
-    /// Initializes an annotation slot given items in path space.
-    /// The path ranges to pangenome space, splitting them if
-    /// necessary.
-    pub fn new_from_path_space(
+    /// Initializes an annotation slot given annotations on a path, or
+    /// not on any path. The feature parts are mapped to pangenome
+    /// space, splitting them if necessary.
+    pub fn new_from_annotations(
         graph: &PathIndex,
         set_id: AnnotationSetId,
-        annotations: impl IntoIterator<
-            Item = (PathId, std::ops::Range<Bp>, ShapeFn),
-        >,
+        annotations: impl IntoIterator<Item = SlotAnnotation>,
     ) -> Self {
         let mut annot_objs = Vec::new();
-        let mut shape_fns = Vec::new();
+        let mut feature_objs = Vec::new();
+        let mut shape_fns = HashMap::default();
+        let mut colors = HashMap::default();
 
         let mut annotation_ranges: HashMap<
             AnnotationId,
             Vec<std::ops::Range<Bp>>,
         > = HashMap::default();
 
-        for (a_id, (path, path_range, shape)) in
-            annotations.into_iter().enumerate()
-        {
-            let a_id = AnnotationId(a_id);
-            shape_fns.push(shape);
-            let range_end = path_range.end;
-
-            if let Some(steps) = graph.path_step_range_iter(path, path_range) {
-                for (_path_start, step) in steps {
-                    let (offset, len) = graph.node_offset_length(step.node());
-                    let start = offset.0 as usize;
-                    let len = len.0 as usize;
-                    let end = start + len;
-                    let geom = Line::new((start as i64, 0), (end as i64, 0));
-                    annot_objs.push(GeomWithData::new(geom, a_id));
-
-                    let range = Bp(start as u64)..Bp(end as u64);
-                    annotation_ranges.entry(a_id).or_default().push(range);
-                }
+        for annot in annotations {
+            let a_id = annot.id;
+            shape_fns.insert(a_id, annot.shape);
+            colors.insert(a_id, annot.color);
+
+            for range in annot.ranges {
+                let geom = Line::new(
+                    (range.start.0 as i64, 0),
+                    (range.end.0 as i64, 0),
+                );
+                annot_objs.push(GeomWithData::new(geom, a_id));
+                annotation_ranges.entry(a_id).or_default().push(range);
             }
-        }
-
-        for ranges in annotation_ranges.values_mut() {
-            ranges.sort_by_key(|r| (r.start, r.end));
 
-            // TODO: simplify the stored ranges by iterating the
-            // steps, building up blocks of continuous runs in
-            // pangenome space
+            for (part_range, part) in annot.parts {
+                for range in target_range_pangenome_ranges(
+                    graph,
+                    &annot.target,
+                    &part_range,
+                ) {
+                    let geom = Line::new(
+                        (range.start.0 as i64, 0),
+                        (range.end.0 as i64, 0),
+                    );
+                    feature_objs.push(GeomWithData::new(geom, (a_id, part)));
+                }
+            }
         }
 
         let annots = RTree::<AnnotsTreeObj>::bulk_load(annot_objs);
+        let features = RTree::<FeatureTreeObj>::bulk_load(feature_objs);
 
         Self {
             set_id,
             annots: Arc::new(annots),
             annotation_ranges,
+            features,
+            colors,
             shape_fns,
             dynamics: Default::default(),
             task: None,
@@ -654,9 +739,14 @@ impl AnnotSlot {
             .map(|pos| painter.clip_rect().contains(pos))
             .unwrap_or(false);
 
+        self.draw_features(painter, view);
+
         for &(a_id, pos) in self.positions.iter() {
+            let Some(shape_fn) = self.shape_fns.get(&a_id) else {
+                continue;
+            };
             let pos = mint::Point2::<f32>::from(pos);
-            let shape = self.shape_fns[a_id.0](painter, pos.into());
+            let shape = shape_fn(painter, pos.into());
             let size =
                 mint::Vector2::<f32>::from(shape.visual_bounding_rect().size());
             self.shape_sizes.push((a_id, size.into()));
@@ -678,6 +768,61 @@ impl AnnotSlot {
     }
 }
 
+impl AnnotSlot {
+    /// Draws the annotation blocks as boxes, thinner outside the thick
+    /// range, and the gaps between blocks as lines, along the bottom
+    /// of the slot
+    fn draw_features(&self, painter: &egui::Painter, view: &View1D) {
+        use rstar::AABB;
+
+        let rect = painter.clip_rect();
+        let x_range = rect.x_range();
+        let y_mid = rect.bottom() - FEATURE_TRACK_HEIGHT / 2.0;
+
+        let range = view.range();
+        let aabb =
+            AABB::from_corners((range.start as i64, 0), (range.end as i64, 0));
+
+        for obj in self.features.locate_in_envelope_intersecting(&aabb) {
+            let (a_id, part) = obj.data;
+            let left = Bp(obj.geom().from.0 as u64);
+            let right = Bp(obj.geom().to.0 as u64);
+
+            let Some(xs) =
+                view.map_bp_interval_to_screen_x(&(left..right), &x_range)
+            else {
+                continue;
+            };
+
+            let color = self
+                .colors
+                .get(&a_id)
+                .copied()
+                .unwrap_or(egui::Color32::GRAY);
+
+            let (x0, x1) = xs.into_inner();
+
+            let height = match part {
+                FeaturePart::Thick => FEATURE_TRACK_HEIGHT,
+                FeaturePart::Thin => FEATURE_TRACK_HEIGHT / 2.0,
+                FeaturePart::Gap => {
+                    painter.line_segment(
+                        [egui::pos2(x0, y_mid), egui::pos2(x1, y_mid)],
+                        egui::Stroke::new(1.0, color),
+                    );
+                    continue;
+                }
+            };
+
+            let block = egui::Rect::from_x_y_ranges(
+                x0..=x1.max(x0 + 1.0),
+                (y_mid - height / 2.0)..=(y_mid + height / 2.0),
+            );
+            painter.rect_filled(block, 0.0, color);
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct AnnotObjPos {
     pos_now: Vec2,
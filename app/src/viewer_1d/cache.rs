@@ -10,12 +10,14 @@ use waragraph_core::graph::{Bp, PathId, PathIndex};
 
 use anyhow::Result;
 
-use crate::{app::resource::GraphDataCache, util::BufferDesc};
+use crate::{
+    app::resource::{DataSourceId, GraphDataCache},
+    util::BufferDesc,
+};
 
 use super::view::View1D;
 
-// TODO: still using string-typed keys for data sources and viz. modes! bad!
-pub type SlotKey = (PathId, String);
+pub type SlotKey = (PathId, DataSourceId);
 
 #[derive(
     Clone, Copy, PartialEq, PartialOrd, bytemuck::Zeroable, bytemuck::Pod,
@@ -41,6 +43,7 @@ struct SlotUniform {
     transform: [f32; 2],
     bin_count: u32,
     _pad: u32,
+    color_map: [f32; 4],
 }
 
 type SlotTaskHandle = JoinHandle<Result<([Bp; 2], Vec<u8>, u64)>>;
@@ -84,6 +87,13 @@ pub struct SlotCache {
     slot_id_map: HashMap<SlotKey, usize>,
     slot_id_generation: u64,
 
+    // the slots that were given a rect in the last update; these are
+    // never evicted to make room for other slots
+    visible_slots: HashSet<SlotKey>,
+
+    // the color map of each data source's slots, see `set_color_map`
+    color_maps: HashMap<DataSourceId, [f32; 4]>,
+
     pub data_buffer: BufferDesc,
     rows: usize,
 
@@ -129,6 +139,10 @@ impl SlotCache {
             slot_id_map,
             slot_id_generation: 0,
 
+            visible_slots: HashSet::default(),
+
+            color_maps: HashMap::default(),
+
             data_buffer,
             rows: row_count,
 
@@ -212,7 +226,7 @@ impl SlotCache {
         state: &raving_wgpu::State,
         rt: &tokio::runtime::Handle,
         view: &View1D,
-        data_key: &str,
+        data_key: DataSourceId,
         paths: impl IntoIterator<Item = PathId>,
         sampler: Arc<dyn super::sampler::Sampler + 'static>,
     ) -> Result<()> {
@@ -222,7 +236,7 @@ impl SlotCache {
 
         let slots = paths
             .into_iter()
-            .map(|path| (path, data_key.to_string()))
+            .map(|path| (path, data_key))
             .collect::<Vec<_>>();
 
         let result = self.assign_rows_for_slots(slots.iter(), current_view);
@@ -263,7 +277,7 @@ impl SlotCache {
                 self.bin_count,
                 current_view,
                 slot_key.0,
-                slot_key.clone(),
+                *slot_key,
                 sampler.clone(),
             ));
 
@@ -296,7 +310,7 @@ impl SlotCache {
         state: &raving_wgpu::State,
         rt: &tokio::runtime::Handle,
         view: &View1D,
-        data_key: &str,
+        data_key: DataSourceId,
         paths: impl IntoIterator<Item = PathId>,
     ) -> Result<()> {
         let vl = view.range().start;
@@ -305,7 +319,7 @@ impl SlotCache {
 
         let slots = paths
             .into_iter()
-            .map(|path| (path, data_key.to_string()))
+            .map(|path| (path, data_key))
             .collect::<Vec<_>>();
 
         let result = self.assign_rows_for_slots(slots.iter(), current_view);
@@ -351,7 +365,7 @@ impl SlotCache {
                 path_index,
                 data_cache,
                 bin_count,
-                slot_key.clone(),
+                *slot_key,
                 current_view,
             ));
             state.task_handle = Some(task);
@@ -426,6 +440,9 @@ impl SlotCache {
             }
         }
 
+        self.visible_slots.clear();
+        self.visible_slots.extend(slot_rects.keys().copied());
+
         // create vertices for the slots that contain data
         let mut vertices: Vec<SlotVertex> = Vec::new();
 
@@ -463,11 +480,24 @@ impl SlotCache {
         Ok(())
     }
 
+    /// Sets the color map used by the shader for the slots of
+    /// `data_key`, mapping the values `v` in the slots to the color
+    /// texture coordinates `clamp(v * x + y, z, w)`
+    pub fn set_color_map(&mut self, data_key: DataSourceId, map: [f32; 4]) {
+        self.color_maps.insert(data_key, map);
+    }
+
     fn assign_rows_for_slots<'a>(
         &mut self,
         slots: impl Iterator<Item = &'a SlotKey>,
         current_view: [Bp; 2],
     ) -> std::result::Result<(), SlotCacheError> {
+        // a path row can have several slots, one per track, which
+        // are assigned rows one data source at a time, so both the
+        // slots visible in the last update and the ones being
+        // assigned are kept
+        let slots = slots.collect::<HashSet<_>>();
+
         // evict from cache based on last updated *time*, as in Instant
 
         // let time_since_update = self.last_update
@@ -486,7 +516,10 @@ impl SlotCache {
             .slot_state
             .iter()
             .filter_map(|(slot_key, state)| {
-                if state.task_handle.is_some() {
+                if state.task_handle.is_some()
+                    || self.visible_slots.contains(slot_key)
+                    || slots.contains(slot_key)
+                {
                     return None;
                 }
 
@@ -498,7 +531,7 @@ impl SlotCache {
 
                 let updated_at = state.updated_at?;
                 let time_since = updated_at.elapsed();
-                Some((slot_key.clone(), time_since))
+                Some((*slot_key, time_since))
             })
             .collect();
 
@@ -515,9 +548,9 @@ impl SlotCache {
                 // use the free row, no eviction needed
 
                 let new_state = SlotState::default();
-                self.slot_id_cache[row_id] = Some(slot_key.clone());
-                self.slot_id_map.insert(slot_key.clone(), row_id);
-                self.slot_state.insert(slot_key.clone(), new_state);
+                self.slot_id_cache[row_id] = Some(*slot_key);
+                self.slot_id_map.insert(*slot_key, row_id);
+                self.slot_state.insert(*slot_key, new_state);
 
                 continue;
             }
@@ -536,9 +569,9 @@ impl SlotCache {
                 self.slot_id_map.remove(&old_slot_key);
 
                 let new_state = SlotState::default();
-                self.slot_id_cache[row_id] = Some(slot_key.clone());
-                self.slot_id_map.insert(slot_key.clone(), row_id);
-                self.slot_state.insert(slot_key.clone(), new_state);
+                self.slot_id_cache[row_id] = Some(*slot_key);
+                self.slot_id_map.insert(*slot_key, row_id);
+                self.slot_state.insert(*slot_key, new_state);
 
                 continue;
             }
@@ -645,6 +678,10 @@ impl SlotCache {
                 let transform = Self::view_transform(last_view, current_view);
 
                 data[slot as usize].transform = transform;
+
+                if let Some(map) = key.and_then(|k| self.color_maps.get(&k.1)) {
+                    data[slot as usize].color_map = *map;
+                }
             }
 
             state.queue.write_buffer(
@@ -749,7 +786,7 @@ impl SlotCache {
         key: SlotKey,
         sampler: Arc<dyn super::sampler::Sampler + 'static>,
     ) -> Result<([Bp; 2], Vec<u8>, u64)> {
-        let (path, _data_key) = key.clone();
+        let (path, _data_key) = key;
 
         let sample_vec = sampler
             .sample_range(bin_count, path, view[0]..view[1])
@@ -769,7 +806,7 @@ impl SlotCache {
     ) -> Result<([Bp; 2], Vec<u8>, u64)> {
         use waragraph_core::graph::sampling;
 
-        let (path, data_key) = key.clone();
+        let (path, data_key) = key;
 
         let msg = format!(
             "Fetching data ({}{}), [{}, {}]",
@@ -778,7 +815,7 @@ impl SlotCache {
             view[0].0,
             view[1].0
         );
-        let _ = msg_tx.try_send((key.clone(), msg));
+        let _ = msg_tx.try_send((key, msg));
 
         let t0 = std::time::Instant::now();
 
@@ -798,7 +835,7 @@ impl SlotCache {
         // }
 
         // load data source into cache & get data
-        let data = data_cache.fetch_path_data(&data_key, path).await?;
+        let data = data_cache.fetch_path_data(data_key, path).await?;
 
         let fetch_time = t0.elapsed().as_secs_f32();
 
@@ -809,7 +846,7 @@ impl SlotCache {
             view[0].0,
             view[1].0
         );
-        let _ = msg_tx.try_send((key.clone(), msg));
+        let _ = msg_tx.try_send((key, msg));
 
         // sample data into vector
         let sample_vec = tokio::task::spawn_blocking(move || {
@@ -1,16 +1,23 @@
 use crossbeam::atomic::AtomicCell;
+use std::collections::HashSet;
 use std::sync::Arc;
+use tokio::sync::RwLock;
 
+use crate::annotations::{AnnotationSetId, AnnotationStore};
 use crate::app::settings_menu;
 
 #[derive(Debug, Clone)]
 pub struct Config {
     // use_linear_sampler: Arc<AtomicCell<bool>>,
     pub(super) filter_path_list_by_visibility: Arc<AtomicCell<bool>>,
+
+    /// Annotation sets that aren't shown as lanes in the path rows
+    pub(super) hidden_annotation_sets: Arc<RwLock<HashSet<AnnotationSetId>>>,
 }
 
 pub struct ConfigWidget {
     pub(super) cfg: Config,
+    pub(super) annotations: Arc<RwLock<AnnotationStore>>,
 }
 
 impl settings_menu::SettingsWidget for ConfigWidget {
@@ -20,10 +27,34 @@ impl settings_menu::SettingsWidget for ConfigWidget {
         _settings_ctx: &settings_menu::SettingsUiContext,
     ) -> settings_menu::SettingsUiResponse {
         let mut filter_paths = self.cfg.filter_path_list_by_visibility.load();
-        let response =
+        let mut response =
             ui.checkbox(&mut filter_paths, "Filter paths by visibility");
         self.cfg.filter_path_list_by_visibility.store(filter_paths);
 
+        let annotations = self.annotations.blocking_read();
+
+        if !annotations.annotation_sets.is_empty() {
+            ui.separator();
+            ui.label("Annotation sets");
+
+            let mut hidden = self.cfg.hidden_annotation_sets.blocking_write();
+
+            for (set_id, set) in annotations.annotation_sets.iter() {
+                let mut visible = !hidden.contains(set_id);
+                let checkbox = ui.checkbox(&mut visible, &set.name);
+
+                if checkbox.changed() {
+                    if visible {
+                        hidden.remove(set_id);
+                    } else {
+                        hidden.insert(*set_id);
+                    }
+                }
+
+                response = response.union(checkbox);
+            }
+        }
+
         settings_menu::SettingsUiResponse { response }
     }
 }
@@ -1,11 +1,20 @@
 use waragraph_core::graph::{Bp, Node, PathId};
 
-use crate::app::SharedState;
+use crate::app::{
+    resource::{DataSourceId, FStats},
+    SharedState,
+};
 
 use super::view::View1D;
 
 pub enum Msg {
     View(ViewCmd),
+    /// The stats of the data used by a viz. mode, sent once the data
+    /// has been loaded; used for the default color map value range
+    VizDataStats {
+        data: DataSourceId,
+        stats: Box<FStats>,
+    },
 }
 
 struct ViewMsgParams {
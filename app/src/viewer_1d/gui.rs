@@ -1,6 +1,7 @@
 use taffy::error::TaffyError;
 use waragraph_core::graph::{Bp, PathId};
 
+use crate::app::resource::DataSourceId;
 use crate::gui::FlexLayout;
 
 use super::annotations::AnnotSlotId;
@@ -9,9 +10,16 @@ use super::annotations::AnnotSlotId;
 pub(super) enum SlotElem {
     Empty,
     ViewRange,
-    PathData { path_id: PathId, data_id: String },
-    PathName { path_id: PathId },
-    Annotations { annotation_slot_id: AnnotSlotId },
+    PathData {
+        path_id: PathId,
+        data_id: DataSourceId,
+    },
+    PathName {
+        path_id: PathId,
+    },
+    Annotations {
+        annotation_slot_id: AnnotSlotId,
+    },
     // Annotations { path: PathId, annotation_id: String },
 }
 
@@ -1,3 +1,4 @@
+use crate::app::resource::DataSourceId;
 use crate::app::settings_menu::SettingsWindow;
 use crate::color::{ColorMap, ColorSchemeId};
 use crate::util::BufferDesc;
@@ -13,7 +14,7 @@ use waragraph_core::graph::{PathId, PathIndex};
 #[derive(Clone)]
 pub struct VizModeConfig {
     pub name: String,
-    pub data_key: String,
+    pub data_key: DataSourceId,
     pub color_scheme: ColorSchemeId,
     pub default_color_map: ColorMap,
 }
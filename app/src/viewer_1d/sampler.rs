@@ -6,7 +6,7 @@ use anyhow::Result;
 
 use waragraph_core::graph::{Bp, PathId, PathIndex};
 
-use crate::app::resource::GraphDataCache;
+use crate::app::resource::{DataSourceId, GraphDataCache};
 
 // pub trait Sampler {
 //     fn sample_range_into_bins(&self,
@@ -34,19 +34,19 @@ pub trait Sampler: Send + Sync {
 pub struct PathDataSampler {
     path_index: Arc<PathIndex>,
     data_cache: Arc<GraphDataCache>,
-    data_key: Arc<String>,
+    data_key: DataSourceId,
 }
 
 impl PathDataSampler {
     pub fn new(
         path_index: Arc<PathIndex>,
         data_cache: Arc<GraphDataCache>,
-        data_key: &str,
+        data_key: DataSourceId,
     ) -> Self {
         Self {
             path_index,
             data_cache,
-            data_key: Arc::new(data_key.to_string()),
+            data_key,
         }
     }
 }
@@ -59,10 +59,7 @@ impl Sampler for PathDataSampler {
         path: PathId,
         view: std::ops::Range<Bp>,
     ) -> Result<Vec<u8>> {
-        let data = self
-            .data_cache
-            .fetch_path_data(&self.data_key, path)
-            .await?;
+        let data = self.data_cache.fetch_path_data(self.data_key, path).await?;
 
         let path_index = self.path_index.clone();
 
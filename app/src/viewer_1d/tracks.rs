@@ -0,0 +1,186 @@
+//! Stacked visualization tracks in the path rows of the 1D viewer.
+//!
+//! All path slots are drawn in a single draw call, with one color
+//! scheme texture, so the color schemes of the tracks are packed into
+//! a single texture, the [`TrackPalette`]. Each slot is given its
+//! track's color map, applied by the shader, which maps the sampled
+//! data to the track's scheme's part of the texture; the sampled data
+//! itself never depends on the color map.
+
+use std::sync::Arc;
+
+use crate::app::resource::DataSourceId;
+use crate::color::{ColorMap, ColorSchemeId, ColorStore};
+
+/// A sub-row of each path row, showing one data source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VizTrack {
+    pub data_key: DataSourceId,
+    /// Height of the track, in points
+    pub height: f32,
+    /// Overrides the color scheme of the data source
+    pub color_scheme: Option<ColorSchemeId>,
+}
+
+impl VizTrack {
+    pub const DEFAULT_HEIGHT: f32 = 20.0;
+
+    pub fn new(data_key: DataSourceId) -> Self {
+        Self {
+            data_key,
+            height: Self::DEFAULT_HEIGHT,
+            color_scheme: None,
+        }
+    }
+}
+
+/// The color schemes of the visible tracks, packed into one texture
+pub struct TrackPalette {
+    schemes: Vec<ColorSchemeId>,
+    // offset and length of each scheme in the texture, in texels
+    ranges: Vec<[u32; 2]>,
+    width: u32,
+
+    pub texture: Arc<(wgpu::Texture, wgpu::TextureView)>,
+}
+
+impl TrackPalette {
+    pub fn new(
+        state: &raving_wgpu::State,
+        colors: &ColorStore,
+        schemes: Vec<ColorSchemeId>,
+    ) -> Self {
+        let mut ranges = Vec::with_capacity(schemes.len());
+        let mut offset = 0;
+
+        for &id in &schemes {
+            let len = colors.get_color_scheme(id).colors.len() as u32;
+            ranges.push([offset, len]);
+            offset += len;
+        }
+
+        let (texture, width) =
+            colors.create_color_scheme_atlas(state, &schemes);
+
+        Self {
+            schemes,
+            ranges,
+            width,
+            texture,
+        }
+    }
+
+    pub fn schemes(&self) -> &[ColorSchemeId] {
+        &self.schemes
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The mapping from data values to texture coordinates in the
+    /// palette, for a track using `scheme` with `color_map`
+    pub fn track_mapping(
+        &self,
+        scheme: ColorSchemeId,
+        color_map: ColorMap,
+    ) -> Option<TrackColorMapping> {
+        let ix = self.schemes.iter().position(|&id| id == scheme)?;
+
+        Some(TrackColorMapping {
+            color_map,
+            texels: self.ranges[ix],
+            palette_width: self.width,
+        })
+    }
+}
+
+/// Maps the data of a track to its part of the palette texture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrackColorMapping {
+    pub color_map: ColorMap,
+    /// The offset and length of the track's color scheme in the
+    /// palette, in texels
+    pub texels: [u32; 2],
+    pub palette_width: u32,
+}
+
+impl TrackColorMapping {
+    /// The color map in the form used by the shader, `[x, y, z, w]`,
+    /// where a data value `v` is mapped to the palette coordinate
+    /// `clamp(v * x + y, z, w)`, i.e. the color map is applied to
+    /// `v`, and the resulting coordinate scaled to the track's part
+    /// of the palette; the coordinate is kept within the texel
+    /// centers of the track's scheme, so that neighboring schemes
+    /// never bleed into each other, also with linear interpolation.
+    ///
+    /// Infinite values are drawn as background by the shader.
+    pub fn shader_color_map(&self) -> [f32; 4] {
+        let [min_val, max_val] = self.color_map.value_range;
+        let [min_color, max_color] = self.color_map.color_range;
+
+        let [offset, len] = self.texels;
+        let len = len.max(1) as f32;
+        let width = self.palette_width as f32;
+
+        // an empty value range, e.g. from the color map widget, falls
+        // back to a unit range, as in `FStats::quantile_range`
+        let val_width = max_val - min_val;
+        let val_width = if val_width.is_normal() {
+            val_width
+        } else {
+            1.0
+        };
+
+        // color coordinate per unit of value
+        let slope = (max_color - min_color) / val_width;
+
+        let scale = slope * len / width;
+        let shift =
+            (min_color - slope * min_val) * len / width + offset as f32 / width;
+
+        let lo = (offset as f32 + 0.5) / width;
+        let hi = (offset as f32 + len - 0.5) / width;
+
+        [scale, shift, lo, hi]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn track_mapping_stays_in_scheme() {
+        // the second of two schemes, with 4 and 2 colors
+        let mapping = TrackColorMapping {
+            color_map: ColorMap {
+                value_range: [0.0, 10.0],
+                color_range: [0.0, 1.0],
+            },
+            texels: [4, 2],
+            palette_width: 6,
+        };
+
+        // as in the shader
+        let [x, y, z, w] = mapping.shader_color_map();
+        let texel = |v: f32| (v * x + y).clamp(z, w) * 6.0;
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+
+        assert!(close(texel(0.0), 4.5));
+        assert!(close(texel(-5.0), 4.5));
+        assert!(close(texel(10.0), 5.5));
+        assert!(close(texel(100.0), 5.5));
+        assert_eq!(texel(7.0).floor(), 5.0);
+
+        let empty_range = TrackColorMapping {
+            color_map: ColorMap {
+                value_range: [3.0, 3.0],
+                color_range: [0.0, 1.0],
+            },
+            ..mapping
+        };
+        let params = empty_range.shader_color_map();
+        assert!(params.iter().all(|p| p.is_finite()));
+    }
+}
@@ -1,7 +1,14 @@
 use std::{collections::HashMap, sync::Arc};
-use waragraph_core::graph::{PathId, PathIndex};
+use waragraph_core::graph::{Bp, PathId, PathIndex};
 
-use crate::{app::SharedState, color::ColorMap};
+use crate::{
+    annotations::AnnotationStore,
+    app::{
+        resource::{DataSourceId, DataSourceMeta, ValueDomain},
+        SharedState,
+    },
+    color::ColorMap,
+};
 
 use super::render::VizModeConfig;
 
@@ -25,12 +32,12 @@ pub(super) fn init_path_name_hash_viz_mode(
     state: &raving_wgpu::State,
     shared: &SharedState,
     viz_samplers: &mut HashMap<
-        String,
+        DataSourceId,
         Arc<dyn super::sampler::Sampler + 'static>,
     >,
 
-    viz_mode_config: &mut HashMap<String, VizModeConfig>,
-) {
+    viz_mode_config: &mut HashMap<DataSourceId, VizModeConfig>,
+) -> DataSourceId {
     // create sampler
     let path_count = shared.graph.path_names.len();
     let sampler = super::sampler::PathNodeSetSampler::new(
@@ -42,8 +49,6 @@ pub(super) fn init_path_name_hash_viz_mode(
         },
     );
 
-    viz_samplers.insert("path_name".to_string(), Arc::new(sampler) as Arc<_>);
-
     // create color buffer
     let path_names = shared
         .graph
@@ -55,14 +60,24 @@ pub(super) fn init_path_name_hash_viz_mode(
     // create color scheme & upload texture
     let color_scheme = {
         let mut colors = shared.colors.blocking_write();
-        let id = colors.add_color_scheme("path_name_hash", color_vec);
-        colors.create_color_scheme_texture(state, "path_name_hash");
-        id
+        colors.add_color_scheme(state, "path_name_hash", color_vec)
     };
 
+    // the path name mode has no data of its own, only the sampler, but
+    // still needs an ID
+    let data_key = shared.graph_data_cache.register_source(DataSourceMeta {
+        name: "path_name".to_string(),
+        description: "Color paths by a hash of their name".to_string(),
+        unit: None,
+        value_domain: ValueDomain::Categorical,
+        default_color_scheme: color_scheme,
+    });
+
+    viz_samplers.insert(data_key, Arc::new(sampler) as Arc<_>);
+
     let path_name = VizModeConfig {
         name: "path_name".to_string(),
-        data_key: "path_name".to_string(),
+        data_key,
         color_scheme,
         default_color_map: ColorMap {
             value_range: [0.0, 1.0],
@@ -70,10 +85,50 @@ pub(super) fn init_path_name_hash_viz_mode(
         },
     };
 
-    viz_mode_config.insert("path_name".to_string(), path_name);
+    viz_mode_config.insert(data_key, path_name);
+
+    data_key
+}
 
-    shared
-        .data_color_schemes
-        .blocking_write()
-        .insert("path_name".into(), color_scheme);
+/// Lists the annotations on `path` covering `pos`, or the closest
+/// annotation on the path if there are none
+pub(super) fn path_pos_annotations_tooltip(
+    annotations: &AnnotationStore,
+    path: PathId,
+    pos: Bp,
+    ui: &mut egui::Ui,
+) {
+    let mut labels = std::collections::BTreeSet::new();
+
+    for set in annotations.annotation_sets.values() {
+        for (_range, annot_id) in
+            set.index.path_overlapping(path, pos..Bp(pos.0 + 1))
+        {
+            if let Some(annot) = set.get(annot_id) {
+                labels.insert(annot.label.clone());
+            }
+        }
+    }
+
+    if !labels.is_empty() {
+        ui.separator();
+        for label in labels {
+            ui.label(label.as_str());
+        }
+        return;
+    }
+
+    let nearest = annotations
+        .annotation_sets
+        .values()
+        .filter_map(|set| {
+            let (annot_id, dist) = set.index.path_nearest(path, pos)?;
+            Some((dist, set.get(annot_id)?.label.clone()))
+        })
+        .min();
+
+    if let Some((dist, label)) = nearest {
+        ui.separator();
+        ui.label(format!("Nearest annotation: {label} ({dist} bp away)"));
+    }
 }
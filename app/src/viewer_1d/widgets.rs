@@ -4,26 +4,32 @@ use tokio::sync::RwLock;
 
 use std::sync::Arc;
 
+use super::tracks::VizTrack;
 use crate::app::{
+    resource::DataSourceId,
     settings_menu::{SettingsUiContext, SettingsUiResponse, SettingsWidget},
     SharedState,
 };
 
 pub struct VisualizationModesWidget {
     pub(super) shared: SharedState,
-    pub(super) active_viz_data_key: Arc<RwLock<String>>,
+    pub(super) viz_tracks: Arc<RwLock<Vec<VizTrack>>>,
+    /// Modes that aren't path data sources, listed after them
+    pub(super) extra_modes: Vec<DataSourceId>,
     pub(super) use_linear_sampler: Arc<AtomicCell<bool>>,
 }
 
 impl VisualizationModesWidget {
     pub fn new(
         shared: SharedState,
-        active_viz_data_key: Arc<RwLock<String>>,
+        viz_tracks: Arc<RwLock<Vec<VizTrack>>>,
+        extra_modes: Vec<DataSourceId>,
         use_linear_sampler: Arc<AtomicCell<bool>>,
     ) -> Self {
         Self {
             shared,
-            active_viz_data_key,
+            viz_tracks,
+            extra_modes,
             use_linear_sampler,
         }
     }
@@ -35,30 +41,120 @@ impl SettingsWidget for VisualizationModesWidget {
         ui: &mut egui::Ui,
         settings_ctx: &SettingsUiContext,
     ) -> SettingsUiResponse {
-        let mut current_key = self.active_viz_data_key.blocking_write();
+        let mut tracks = self.viz_tracks.blocking_write();
 
-        let viz_modes = ["depth", "strand", "path_name"];
-        // let mut path_data_sources = self
-        //     .shared
-        //     .graph_data_cache
-        //     .path_data_source_names()
-        //     .collect::<Vec<_>>();
-        // path_data_sources.sort();
+        let data_cache = &self.shared.graph_data_cache;
+
+        let mut viz_modes = data_cache.path_data_sources();
+        viz_modes.extend(self.extra_modes.iter().copied());
+
+        let colors = self.shared.colors.blocking_read();
 
         let resp = ui.vertical(|ui| {
-            let data_sources = ui.horizontal(|ui| {
-                for key in viz_modes {
+            ui.label("Tracks, from top to bottom");
+
+            // each data source can only be shown in one track
+            let in_use = tracks.iter().map(|t| t.data_key).collect::<Vec<_>>();
+
+            let mut move_up = None;
+            let mut remove = None;
+
+            let track_count = tracks.len();
+
+            for (ix, track) in tracks.iter_mut().enumerate() {
+                let id = egui::Id::new("Viewer1D-VizTrack").with(ix);
+
+                ui.horizontal(|ui| {
+                    let meta = data_cache.data_source_meta(track.data_key);
+
+                    egui::ComboBox::from_id_source(id.with("data"))
+                        .selected_text(meta.name.as_str())
+                        .show_ui(ui, |ui| {
+                            for &key in &viz_modes {
+                                if key != track.data_key
+                                    && in_use.contains(&key)
+                                {
+                                    continue;
+                                }
+
+                                let meta = data_cache.data_source_meta(key);
+                                ui.selectable_value(
+                                    &mut track.data_key,
+                                    key,
+                                    meta.name.as_str(),
+                                )
+                                .on_hover_text(meta.description.as_str());
+                            }
+                        });
+
+                    let scheme_name = track
+                        .color_scheme
+                        .map(|id| colors.get_scheme_name(id))
+                        .unwrap_or("Default colors");
+
+                    egui::ComboBox::from_id_source(id.with("colors"))
+                        .selected_text(scheme_name)
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut track.color_scheme,
+                                None,
+                                "Default colors",
+                            );
+                            for (name, scheme) in colors.color_schemes() {
+                                ui.selectable_value(
+                                    &mut track.color_scheme,
+                                    Some(scheme),
+                                    name,
+                                );
+                            }
+                        });
+
+                    ui.add(
+                        egui::DragValue::new(&mut track.height)
+                            .clamp_range(4.0..=200.0)
+                            .suffix(" pt"),
+                    )
+                    .on_hover_text("Track height");
+
+                    if ui.add_enabled(ix > 0, egui::Button::new("Up")).clicked()
+                    {
+                        move_up = Some(ix);
+                    }
+
                     if ui
                         .add_enabled(
-                            key != current_key.as_str(),
-                            egui::Button::new(key),
+                            track_count > 1,
+                            egui::Button::new("Remove"),
                         )
                         .clicked()
                     {
-                        *current_key = key.to_string();
+                        remove = Some(ix);
                     }
+                });
+            }
+
+            if let Some(ix) = move_up {
+                tracks.swap(ix - 1, ix);
+            }
+
+            if let Some(ix) = remove {
+                tracks.remove(ix);
+            }
+
+            let unused = viz_modes
+                .iter()
+                .find(|key| !tracks.iter().any(|t| t.data_key == **key));
+
+            if ui
+                .add_enabled(unused.is_some(), egui::Button::new("Add track"))
+                .clicked()
+            {
+                if let Some(&key) = unused {
+                    tracks.push(VizTrack::new(key));
                 }
-            });
+            }
+
+            ui.separator();
 
             let sampler = {
                 let mut use_linear = self.use_linear_sampler.load();
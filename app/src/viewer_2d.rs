@@ -1,9 +1,10 @@
-use crate::annotations::{AnnotationId, GlobalAnnotationId};
+use crate::annotations::GlobalAnnotationId;
+use crate::app::resource::{DataSourceId, FStats, GraphData};
 use crate::app::settings_menu::SettingsWindow;
 use crate::app::{AppWindow, SharedState};
 use crate::color::ColorMap;
 use crate::context::{ContextQuery, ContextState};
-use crate::gui::annotations::AnnotationListWidget;
+use crate::gui::annotations::{AnnotationListWidget, AnnotationSearchWidget};
 use crate::util::BufferDesc;
 use crate::viewer_2d::config::Config;
 
@@ -74,7 +75,12 @@ pub struct Viewer2D {
 
     annotation_layer: AnnotationLayer,
 
-    active_viz_data_key: String,
+    // the viz. mode whose data is in `data_buffer`
+    active_viz_data_key: DataSourceId,
+    // the viz. mode selected in the settings, which is loaded in the
+    // background if it's not the active one
+    viz_mode: Arc<RwLock<DataSourceId>>,
+    pending_viz_mode: Option<DataSourceId>,
     color_mapping: crate::util::Uniform<ColorMap, 16>,
     data_buffer: wgpu::Buffer,
 
@@ -86,6 +92,8 @@ pub struct Viewer2D {
     cfg: Config,
 
     annotation_list_widget: AnnotationListWidget,
+    annotation_search_widget: AnnotationSearchWidget,
+    annotation_export_widget: gui::AnnotationExportWidget,
 }
 
 impl Viewer2D {
@@ -253,12 +261,13 @@ impl Viewer2D {
             op_state.instances = Some(0..instances);
         });
 
-        // let active_viz_data_key = "node_id".to_string();
-        let active_viz_data_key = "depth".to_string();
+        // let active_viz_data_key = "node_id";
+        let active_viz_data_key =
+            shared.graph_data_cache.data_source_id("depth")?;
 
         let data = shared
             .graph_data_cache
-            .fetch_graph_data_blocking(&active_viz_data_key)
+            .fetch_graph_data_blocking(active_viz_data_key)
             .unwrap();
 
         let data_buffer = {
@@ -271,7 +280,7 @@ impl Viewer2D {
         };
 
         let color_mapping = ColorMap {
-            value_range: [0.0, 13.0],
+            value_range: data.stats.auto_range(),
             color_range: [0.0, 1.0],
         };
 
@@ -296,31 +305,10 @@ impl Viewer2D {
         let view_control_widget =
             ViewControlWidget::new(shared, msg_tx.clone());
 
-        let mut annotation_layer = AnnotationLayer::default();
+        let annotation_layer = AnnotationLayer::default();
 
         let node_positions = Arc::new(node_positions);
 
-        {
-            let annotations = shared
-                .annotations
-                .blocking_read()
-                .annotation_sets
-                .iter()
-                .flat_map(|(set_id, set)| {
-                    (0..set.annotations.len()).map(|i| GlobalAnnotationId {
-                        set_id: *set_id,
-                        annot_id: AnnotationId(i),
-                    })
-                })
-                .collect::<Vec<_>>();
-
-            annotation_layer.load_annotations(
-                shared,
-                node_positions.clone(),
-                annotations,
-            );
-        }
-
         let cfg = {
             let cfg = Config::default();
 
@@ -335,9 +323,25 @@ impl Viewer2D {
             cfg
         };
 
+        let viz_mode = Arc::new(RwLock::new(active_viz_data_key));
+
+        settings_window.register_widget(
+            "2D Viewer",
+            "Visualization Modes",
+            Arc::new(RwLock::new(config::VizModeWidget {
+                shared: shared.clone(),
+                viz_mode: viz_mode.clone(),
+            })),
+        );
+
         let annotation_list_widget =
             AnnotationListWidget::new(shared.annotations.clone());
 
+        let annotation_search_widget = AnnotationSearchWidget::new(
+            shared.graph.clone(),
+            shared.annotations.clone(),
+        );
+
         Ok(Self {
             node_positions,
 
@@ -358,6 +362,8 @@ impl Viewer2D {
 
             color_mapping,
             active_viz_data_key,
+            viz_mode,
+            pending_viz_mode: None,
             data_buffer,
 
             msg_tx,
@@ -370,6 +376,10 @@ impl Viewer2D {
             annotation_layer,
 
             annotation_list_widget,
+            annotation_search_widget,
+            annotation_export_widget: gui::AnnotationExportWidget::new(
+                shared.settings_ctx.clone(),
+            ),
         })
     }
 
@@ -382,6 +392,33 @@ impl Viewer2D {
         );
     }
 
+    fn set_viz_data(
+        &mut self,
+        state: &raving_wgpu::State,
+        data_key: DataSourceId,
+        data: &GraphData<f32, FStats>,
+    ) {
+        state.queue.write_buffer(
+            &self.data_buffer,
+            0,
+            bytemuck::cast_slice(&data.node_data),
+        );
+
+        let meta = self.shared.graph_data_cache.data_source_meta(data_key);
+
+        let value_range = meta
+            .value_domain
+            .fixed_range()
+            .unwrap_or_else(|| data.stats.auto_range());
+
+        self.color_mapping.update_data(|cmap| {
+            cmap.value_range = value_range;
+        });
+        self.color_mapping.write_buffer(state);
+
+        self.active_viz_data_key = data_key;
+    }
+
     fn update_vert_config_uniform(
         &self,
         queue: &wgpu::Queue,
@@ -416,6 +453,39 @@ impl AppWindow for Viewer2D {
                     &self.node_positions,
                     &mut self.view,
                 ),
+                control::Msg::VizData { data_key, data } => {
+                    // ignore data for modes that have been deselected
+                    // while loading
+                    if self.pending_viz_mode == Some(data_key) {
+                        self.pending_viz_mode = None;
+                        self.set_viz_data(state, data_key, &data);
+                    }
+                }
+            }
+        }
+
+        {
+            let selected = *self.viz_mode.blocking_read();
+
+            if selected != self.active_viz_data_key
+                && self.pending_viz_mode != Some(selected)
+            {
+                self.pending_viz_mode = Some(selected);
+
+                let data_cache = self.shared.graph_data_cache.clone();
+                let msg_tx = self.msg_tx.clone();
+
+                tokio_handle.spawn(async move {
+                    match data_cache.fetch_graph_data(selected).await {
+                        Ok(data) => {
+                            let _ = msg_tx.send(control::Msg::VizData {
+                                data_key: selected,
+                                data,
+                            });
+                        }
+                        Err(e) => log::error!("{e:?}"),
+                    }
+                });
             }
         }
 
@@ -448,6 +518,43 @@ impl AppWindow for Viewer2D {
                         ui,
                     );
 
+                    util::node_annotations_side_panel_info(
+                        &self.shared.graph,
+                        &self.shared.annotations.blocking_read(),
+                        context_state,
+                        ui,
+                    );
+
+                    ui.separator();
+
+                    egui::CollapsingHeader::new("Search annotations").show(
+                        ui,
+                        |ui| {
+                            if let Some(annot_id) =
+                                self.annotation_search_widget.show(ui)
+                            {
+                                context_state.set(
+                                    "AnnotationSearch",
+                                    ["goto"],
+                                    annot_id,
+                                );
+                            }
+                        },
+                    );
+
+                    egui::CollapsingHeader::new("Export annotations").show(
+                        ui,
+                        |ui| {
+                            self.annotation_export_widget.show(
+                                ui,
+                                &self.shared.graph,
+                                &self.shared.annotations,
+                                &self.node_positions,
+                                &self.view,
+                            );
+                        },
+                    );
+
                     ui.separator();
 
                     self.annotation_list_widget.show(
@@ -495,15 +602,11 @@ impl AppWindow for Viewer2D {
                             if label.clicked_by(egui::PointerButton::Primary) {
                                 // pan to annotation
                                 let nodes =
-                                    self.shared.graph.path_step_range_iter(
-                                        annotation.path,
-                                        annotation.range.clone(),
-                                    );
+                                    annotation.nodes(&self.shared.graph);
                                 let node_centers =
-                                    nodes.unwrap().map(|(_, step)| {
-                                        let (n0, n1) = self
-                                            .node_positions
-                                            .node_pos(step.node());
+                                    nodes.into_iter().map(|node| {
+                                        let (n0, n1) =
+                                            self.node_positions.node_pos(node);
                                         let mid = n0 + (n1 - n0) * 0.5;
                                         mid
                                     });
@@ -543,6 +646,34 @@ impl AppWindow for Viewer2D {
             .query_get_cast::<_, Node>(None, ["hover"])
             .copied();
 
+        gui::unpin_replaced_annotations(
+            egui_ctx.ctx(),
+            &self.shared.annotations.blocking_read(),
+        );
+
+        // annotations chosen in the search widget of any viewer
+        if let Some(&annot_id) = context_state
+            .query_get_cast::<_, GlobalAnnotationId>(
+                Some("AnnotationSearch"),
+                ["goto"],
+            )
+        {
+            use control::{Msg, ViewCmd};
+            let store = self.shared.annotations.blocking_read();
+
+            if let Some(annot) = store
+                .annotation_sets
+                .get(&annot_id.set_id)
+                .and_then(|set| set.get(annot_id.annot_id))
+            {
+                let _ = self.msg_tx.send(Msg::View(ViewCmd::GotoRange {
+                    path: annot.path(),
+                    range: annot.range.clone(),
+                }));
+                gui::pin_annotation(egui_ctx.ctx(), annot_id);
+            }
+        }
+
         let goto_node_1d = context_state
             .query_get_cast::<_, Node>(Some("Viewer1D"), ["goto"])
             .copied();
@@ -618,7 +749,9 @@ impl AppWindow for Viewer2D {
             let annotations = self.shared.annotations.blocking_read();
 
             for annot_id in highlight_annots {
-                let annot = annotations.get(annot_id);
+                let Some(annot) = annotations.get(annot_id) else {
+                    continue;
+                };
 
                 let stroke = egui::Stroke::new(
                     5.0,
@@ -627,14 +760,11 @@ impl AppWindow for Viewer2D {
 
                 let mut shapes_vec = Vec::new();
 
-                if let Some(nodes) = self
-                    .shared
-                    .graph
-                    .path_step_range_iter(annot.path, annot.range.clone())
-                {
-                    for (_offset, step) in nodes {
-                        let (n0, n1) =
-                            self.node_positions.node_pos(step.node());
+                let nodes = annot.nodes(&self.shared.graph);
+
+                if !nodes.is_empty() {
+                    for node in nodes {
+                        let (n0, n1) = self.node_positions.node_pos(node);
 
                         let p0 = (mat * n0.into_homogeneous_point()).xy();
                         let p1 = (mat * n1.into_homogeneous_point()).xy();
@@ -916,18 +1046,25 @@ impl AppWindow for Viewer2D {
 
             let sampler = colors.linear_sampler.clone();
 
-            let id = self
-                .shared
-                .data_color_schemes
-                .blocking_read()
-                .get(&self.active_viz_data_key)
-                .copied()
-                .unwrap();
+            let id = self.shared.data_color_scheme(self.active_viz_data_key);
+
+            // fall back to the default scheme rather than not drawing
+            let (id, texture) = match colors.get_color_scheme_texture(id) {
+                Some(texture) => (id, texture),
+                None => {
+                    log::warn!(
+                        "Color scheme `{}` has no texture, using `spectral`",
+                        colors.get_scheme_name(id)
+                    );
+                    let id = colors.get_color_scheme_id("spectral").unwrap();
+                    (id, colors.get_color_scheme_texture(id).unwrap())
+                }
+            };
 
             let scheme = colors.get_color_scheme(id);
             let size = [scheme.colors.len() as u32, 1];
 
-            (sampler, colors.get_color_scheme_texture(id).unwrap(), size)
+            (sampler, texture, size)
         };
 
         let texture = &tex.0;
@@ -1,4 +1,4 @@
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::sync::Arc;
 
 use egui::epaint::ahash;
@@ -10,7 +10,9 @@ use ultraviolet::{Rotor2, Vec2};
 use waragraph_core::graph::Node;
 
 use crate::{
-    annotations::{AnnotationSetId, GlobalAnnotationId},
+    annotations::{
+        AnnotationId, AnnotationSet, AnnotationSetId, GlobalAnnotationId,
+    },
     app::SharedState,
 };
 
@@ -42,17 +44,80 @@ pub struct AnnotationLayer {
     to_draw_task:
         Option<JoinHandle<(View2D, Vec<(GlobalAnnotationId, [f32; 2])>)>>,
     to_draw_cache: Vec<(GlobalAnnotationId, [f32; 2])>,
+
+    store_generation: Option<u64>,
+    // the annotation set each set's label anchors were loaded from
+    set_sources: BTreeMap<AnnotationSetId, Arc<AnnotationSet>>,
 }
 
 impl AnnotationLayer {
-    pub fn load_annotations(
-        &self,
+    /// Reloads the label anchors of the annotation sets that have been
+    /// added to, replaced in, or removed from the store since they were
+    /// last loaded
+    fn sync_with_store(
+        &mut self,
         shared: &SharedState,
-        node_positions: Arc<NodePositions>,
-        annot_ids: impl IntoIterator<Item = GlobalAnnotationId>,
+        node_positions: &NodePositions,
     ) {
-        let mut state = self.state.blocking_write();
-        state.load_annotations(shared, &node_positions, annot_ids);
+        let store = shared.annotations.blocking_read();
+
+        if self.store_generation == Some(store.generation()) {
+            return;
+        }
+        self.store_generation = Some(store.generation());
+
+        let stale = self
+            .set_sources
+            .iter()
+            .filter(|(set_id, source)| {
+                let current = store.annotation_sets.get(set_id);
+                !matches!(current, Some(current) if Arc::ptr_eq(current, source))
+            })
+            .map(|(set_id, _)| *set_id)
+            .collect::<HashSet<_>>();
+
+        let to_load = store
+            .annotation_sets
+            .iter()
+            .filter(|(set_id, _)| {
+                stale.contains(set_id) || !self.set_sources.contains_key(set_id)
+            })
+            .collect::<Vec<_>>();
+
+        if stale.is_empty() && to_load.is_empty() {
+            return;
+        }
+
+        let annot_ids = to_load
+            .into_iter()
+            .flat_map(|(set_id, set)| {
+                // child features are covered by their parents
+                (0..set.annotations.len())
+                    .filter(|&i| set.annotations[i].parent.is_none())
+                    .map(|i| GlobalAnnotationId {
+                        set_id: *set_id,
+                        annot_id: AnnotationId(i),
+                    })
+            })
+            .collect::<Vec<_>>();
+
+        self.set_sources = store.annotation_sets.clone();
+
+        // the store lock is taken again when loading
+        drop(store);
+
+        let mut state = self
+            .state
+            .blocking_read()
+            .without_sets(&stale, node_positions);
+        state.load_annotations(shared, node_positions, annot_ids);
+
+        // any running task refers to objects in the old state, so its
+        // results are discarded
+        self.state = Arc::new(RwLock::new(state));
+        self.to_draw_task = None;
+        self.to_draw_cache.clear();
+        self.last_view = None;
     }
 
     pub fn draw(
@@ -64,6 +129,8 @@ impl AnnotationLayer {
         dims: Vec2,
         painter: &egui::Painter,
     ) {
+        self.sync_with_store(shared, node_positions);
+
         if let Ok(mut state) = self.state.try_write() {
             if state.annot_shape_sizes.len() < state.annot_objs.len() {
                 painter.fonts(|fonts| state.prepare_labels(fonts));
@@ -129,7 +196,10 @@ impl AnnotationLayer {
 
         // use latest task results to draw labels
         for (annot_id, pos) in &self.to_draw_cache {
-            let text = &annots.get(*annot_id).label;
+            let Some(annot) = annots.get(*annot_id) else {
+                continue;
+            };
+            let text = &annot.label;
 
             let shape = painter.fonts(|fonts| {
                 let font = egui::FontId::proportional(16.0);
@@ -192,6 +262,55 @@ pub struct AnnotationLayerState {
 }
 
 impl AnnotationLayerState {
+    /// A copy of the state without the annotations of the sets in
+    /// `sets`, keeping the anchors and label sizes of the rest
+    fn without_sets(
+        &self,
+        sets: &HashSet<AnnotationSetId>,
+        node_positions: &NodePositions,
+    ) -> Self {
+        let mut result = Self::default();
+        let mut rtree_objs: Vec<AnchorTreeObj> = Vec::new();
+
+        for (old_id, obj) in self.annot_objs.iter().enumerate() {
+            if sets.contains(&obj.annot_id.set_id) {
+                continue;
+            }
+
+            let obj_id = result.annot_objs.len();
+            let anchor_set = &self.anchor_sets[old_id];
+
+            for &node in anchor_set.nodes.iter() {
+                let (p0, p1) = node_positions.node_pos(node);
+                rtree_objs.push(GeomWithData::new(
+                    Line::new(p0.into(), p1.into()),
+                    (node, obj_id),
+                ));
+            }
+
+            result.annot_objs.push(AnnotObj {
+                obj_id,
+                label: obj.label.clone(),
+                ..*obj
+            });
+            result.anchor_sets.push(AnchorSet {
+                nodes: anchor_set.nodes.clone(),
+            });
+
+            // the label sizes are only kept as long as they cover a
+            // prefix of the objects, see `prepare_labels`
+            if let Some(size) = self.annot_shape_sizes.get(old_id) {
+                if result.annot_shape_sizes.len() == obj_id {
+                    result.annot_shape_sizes.push(*size);
+                }
+            }
+        }
+
+        result.anchor_rtree = Some(RTree::bulk_load(rtree_objs));
+
+        result
+    }
+
     pub fn load_annotations(
         &mut self,
         shared: &SharedState,
@@ -200,11 +319,11 @@ impl AnnotationLayerState {
     ) {
         let annotations = shared.annotations.blocking_read();
 
+        let graph = &shared.graph;
+
         let get_annotation = |annot_id: GlobalAnnotationId| {
-            annotations
-                .annotation_sets
-                .get(&annot_id.set_id)
-                .and_then(|set| set.get(annot_id.annot_id))
+            let set = annotations.annotation_sets.get(&annot_id.set_id)?;
+            Some((set, set.get(annot_id.annot_id)?))
         };
 
         use rand::prelude::*;
@@ -215,21 +334,28 @@ impl AnnotationLayerState {
         for annot_id in annot_ids {
             let obj_id = self.annot_objs.len();
 
-            let annot = get_annotation(annot_id).unwrap();
-
-            // step through annot. path range to build anchor set
-            let anchor_nodes = shared
-                .graph
-                .path_step_range_iter(annot.path, annot.range.clone())
-                .unwrap()
-                .map(|(_pos, step)| step.node());
+            let Some((set, annot)) = get_annotation(annot_id) else {
+                continue;
+            };
 
-            let midpoint = annot.range.start.0
-                + (annot.range.end.0 - annot.range.start.0) / 2;
+            // the nodes covered by the annotation are its anchor set
+            let nodes = set
+                .index
+                .pangenome_ranges(annot_id.annot_id)
+                .iter()
+                .filter(|range| range.start < range.end)
+                .flat_map(|range| {
+                    let nodes =
+                        graph.pos_range_nodes(range.start.0..range.end.0 - 1);
+                    (nodes.start().ix()..=nodes.end().ix()).map(Node::from)
+                })
+                .collect::<BTreeSet<_>>();
+
+            if nodes.is_empty() {
+                continue;
+            }
 
-            let anchor_set = AnchorSet {
-                nodes: anchor_nodes.collect(),
-            };
+            let anchor_set = AnchorSet { nodes };
 
             for &node in anchor_set.nodes.iter() {
                 let (p0, p1) = node_positions.node_pos(node);
@@ -1,7 +1,8 @@
 use crossbeam::atomic::AtomicCell;
 use std::sync::Arc;
+use tokio::sync::RwLock;
 
-use crate::app::settings_menu;
+use crate::app::{resource::DataSourceId, settings_menu, SharedState};
 
 #[derive(Debug, Clone)]
 pub struct Config {
@@ -34,3 +35,39 @@ impl settings_menu::SettingsWidget for ConfigWidget {
         settings_menu::SettingsUiResponse { response }
     }
 }
+
+/// Lists the graph data sources, and sets the one that's visualized
+pub struct VizModeWidget {
+    pub(super) shared: SharedState,
+    pub(super) viz_mode: Arc<RwLock<DataSourceId>>,
+}
+
+impl settings_menu::SettingsWidget for VizModeWidget {
+    fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        _settings_ctx: &settings_menu::SettingsUiContext,
+    ) -> settings_menu::SettingsUiResponse {
+        let data_cache = &self.shared.graph_data_cache;
+        let mut current = self.viz_mode.blocking_write();
+
+        let resp = ui.horizontal_wrapped(|ui| {
+            for key in data_cache.graph_data_sources() {
+                let meta = data_cache.data_source_meta(key);
+                let button = egui::Button::new(meta.name.as_str());
+
+                if ui
+                    .add_enabled(key != *current, button)
+                    .on_hover_text(meta.description.as_str())
+                    .clicked()
+                {
+                    *current = key;
+                }
+            }
+        });
+
+        settings_menu::SettingsUiResponse {
+            response: resp.response,
+        }
+    }
+}
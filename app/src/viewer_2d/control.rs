@@ -1,11 +1,21 @@
 use waragraph_core::graph::{Bp, Node, PathId};
 
-use crate::app::SharedState;
+use std::sync::Arc;
+
+use crate::app::{
+    resource::{DataSourceId, FStats, GraphData},
+    SharedState,
+};
 
 use super::{layout::NodePositions, view::View2D};
 
 pub enum Msg {
     View(ViewCmd),
+    /// Graph data that's been loaded for the viz. mode `data_key`
+    VizData {
+        data_key: DataSourceId,
+        data: Arc<GraphData<f32, FStats>>,
+    },
 }
 
 struct ViewMsgParams {
@@ -1,10 +1,36 @@
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Range;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use egui::{mutex::Mutex, scroll_area::ScrollAreaOutput};
-use tokio::sync::RwLock;
+use tokio::sync::{oneshot, RwLock};
 
-use crate::annotations::{AnnotationId, AnnotationStore, GlobalAnnotationId};
+use ultraviolet::Vec2;
+use waragraph_core::graph::{Bp, Node, PathIndex};
+
+use crate::annotations::export::write_annotations_bed;
+use crate::annotations::{
+    AnnotationSetId, AnnotationStore, GlobalAnnotationId,
+};
+use crate::app::settings_menu::SettingsUiContext;
+
+use super::{layout::NodePositions, view::View2D};
+
+/// Pins the annotation, leaving it pinned if it already was
+pub(super) fn pin_annotation(
+    ctx: &egui::Context,
+    annot_id: GlobalAnnotationId,
+) {
+    ctx.data_mut(|data| {
+        let pinned_annots: &mut Arc<Mutex<HashSet<GlobalAnnotationId>>> = data
+            .get_temp_mut_or_insert_with(egui::Id::null(), || {
+                Arc::new(Mutex::new(Default::default()))
+            });
+
+        pinned_annots.lock().insert(annot_id);
+    })
+}
 
 // egui::util::id_type_map::
 pub(super) fn toggle_pinned_annotation(
@@ -26,3 +52,340 @@ pub(super) fn toggle_pinned_annotation(
         }
     })
 }
+
+/// The pinned annotations, in no particular order
+pub(super) fn pinned_annotations(
+    ctx: &egui::Context,
+) -> Vec<GlobalAnnotationId> {
+    ctx.data(|data| {
+        data.get_temp::<Arc<Mutex<HashSet<GlobalAnnotationId>>>>(
+            egui::Id::null(),
+        )
+        .map(|pinned| pinned.lock().iter().copied().collect())
+        .unwrap_or_default()
+    })
+}
+
+/// Unpins the annotations of sets that have been removed, or replaced
+/// (e.g. reloaded) since the last call, as their IDs may now refer to
+/// other annotations
+pub(super) fn unpin_replaced_annotations(
+    ctx: &egui::Context,
+    store: &AnnotationStore,
+) {
+    ctx.data_mut(|data| {
+        let seen: &mut HashMap<AnnotationSetId, u64> = data
+            .get_temp_mut_or_default(egui::Id::new("pinned_annotation_sets"));
+
+        let mut replaced = HashSet::new();
+
+        seen.retain(|set_id, generation| match store.set_generation(*set_id) {
+            Some(current) if current == *generation => true,
+            _ => {
+                replaced.insert(*set_id);
+                false
+            }
+        });
+
+        for set_id in store.annotation_sets.keys() {
+            if let Some(generation) = store.set_generation(*set_id) {
+                seen.insert(*set_id, generation);
+            }
+        }
+
+        if replaced.is_empty() {
+            return;
+        }
+
+        if let Some(pinned) = data
+            .get_temp::<Arc<Mutex<HashSet<GlobalAnnotationId>>>>(
+                egui::Id::null(),
+            )
+        {
+            pinned
+                .lock()
+                .retain(|annot_id| !replaced.contains(&annot_id.set_id));
+        }
+    })
+}
+
+/// Whether the line segment from `p0` to `p1` passes through the
+/// rectangle spanned by `min` and `max`
+fn segment_intersects_rect(p0: Vec2, p1: Vec2, min: Vec2, max: Vec2) -> bool {
+    let d = p1 - p0;
+
+    // clip the segment's parameter range to each axis' slab in turn
+    let mut t_range = (0f32, 1f32);
+
+    for (p, d, lo, hi) in [(p0.x, d.x, min.x, max.x), (p0.y, d.y, min.y, max.y)]
+    {
+        if d == 0.0 {
+            if p < lo || p > hi {
+                return false;
+            }
+            continue;
+        }
+
+        let (t0, t1) = ((lo - p) / d, (hi - p) / d);
+        t_range.0 = t_range.0.max(t0.min(t1));
+        t_range.1 = t_range.1.min(t0.max(t1));
+
+        if t_range.0 > t_range.1 {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// The top-level annotations with at least one node inside the view,
+/// sorted
+pub(super) fn annotations_in_view(
+    graph: &PathIndex,
+    store: &AnnotationStore,
+    node_positions: &NodePositions,
+    view: &View2D,
+) -> Vec<GlobalAnnotationId> {
+    let (x0, x1) = view.x_range();
+    let (y0, y1) = view.y_range();
+    let (min, max) = (Vec2::new(x0, y0), Vec2::new(x1, y1));
+
+    // the pangenome ranges of the visible nodes, with the ranges of
+    // consecutive nodes merged; a node is visible if any part of it
+    // is, even if both its endpoints are outside the view
+    let mut visible: Vec<Range<Bp>> = Vec::new();
+
+    for (ix, [p0, p1]) in node_positions.iter_nodes().enumerate() {
+        if !segment_intersects_rect(p0, p1, min, max) {
+            continue;
+        }
+
+        let range = graph.node_pangenome_range(Node::from(ix));
+
+        match visible.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ => visible.push(range),
+        }
+    }
+
+    let mut result = BTreeSet::new();
+
+    for (set_id, set) in store.annotation_sets.iter() {
+        for range in &visible {
+            for (_, annot_id) in set.index.pangenome_overlapping(range.clone())
+            {
+                let is_top_level = set
+                    .annotations
+                    .get(annot_id.0)
+                    .is_some_and(|annot| annot.parent.is_none());
+
+                if is_top_level {
+                    result.insert(GlobalAnnotationId {
+                        set_id: *set_id,
+                        annot_id,
+                    });
+                }
+            }
+        }
+    }
+
+    result.into_iter().collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExportSelection {
+    Pinned,
+    InView,
+}
+
+/// Exports the pinned annotations, or the ones in view, to a BED
+/// file chosen with a save dialog; see [`write_annotations_bed`]
+pub(super) struct AnnotationExportWidget {
+    settings_ctx: Arc<SettingsUiContext>,
+
+    selection: ExportSelection,
+    include_projections: bool,
+
+    // the annotations to export, and the path chosen in the save
+    // dialog, while the dialog is open
+    pending: Option<(Vec<GlobalAnnotationId>, oneshot::Receiver<PathBuf>)>,
+    status: Option<Result<String, String>>,
+}
+
+impl AnnotationExportWidget {
+    pub fn new(settings_ctx: Arc<SettingsUiContext>) -> Self {
+        Self {
+            settings_ctx,
+            selection: ExportSelection::Pinned,
+            include_projections: false,
+            pending: None,
+            status: None,
+        }
+    }
+
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        graph: &PathIndex,
+        annotations: &RwLock<AnnotationStore>,
+        node_positions: &NodePositions,
+        view: &View2D,
+    ) {
+        ui.horizontal(|ui| {
+            ui.radio_value(
+                &mut self.selection,
+                ExportSelection::Pinned,
+                "Pinned",
+            );
+            ui.radio_value(
+                &mut self.selection,
+                ExportSelection::InView,
+                "In view",
+            );
+        });
+
+        ui.checkbox(
+            &mut self.include_projections,
+            "Include projections onto other paths",
+        );
+
+        if let Some((annot_ids, recv)) = self.pending.as_mut() {
+            match recv.try_recv() {
+                Ok(path) => {
+                    let annot_ids = std::mem::take(annot_ids);
+                    self.pending = None;
+
+                    let store = annotations.blocking_read();
+
+                    let result = std::fs::File::create(&path)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|file| {
+                            write_annotations_bed(
+                                graph,
+                                &store,
+                                annot_ids,
+                                self.include_projections,
+                                std::io::BufWriter::new(file),
+                            )
+                        });
+
+                    self.status = Some(
+                        result
+                            .map(|lines| {
+                                format!(
+                                    "Wrote {lines} records to {}",
+                                    path.display()
+                                )
+                            })
+                            .map_err(|err| format!("{err:?}")),
+                    );
+                }
+                Err(oneshot::error::TryRecvError::Closed) => {
+                    self.pending = None;
+                }
+                Err(oneshot::error::TryRecvError::Empty) => (),
+            }
+        }
+
+        let export_button = ui.add_enabled(
+            self.pending.is_none(),
+            egui::Button::new("Export BED..."),
+        );
+
+        if export_button.clicked() {
+            let annot_ids = match self.selection {
+                ExportSelection::Pinned => {
+                    let mut pinned = pinned_annotations(ui.ctx());
+                    pinned.sort();
+                    pinned
+                }
+                ExportSelection::InView => {
+                    let store = annotations.blocking_read();
+                    annotations_in_view(graph, &store, node_positions, view)
+                }
+            };
+
+            let mut dialog = egui_file::FileDialog::save_file(None)
+                .default_filename("annotations.bed");
+            dialog.open();
+
+            let id = egui::Id::new("AnnotationExportWidget");
+            let recv = self.settings_ctx.with_window_file_dialog_oneshot(
+                ui.ctx(),
+                id,
+                dialog,
+            );
+            self.pending = Some((annot_ids, recv));
+        }
+
+        match &self.status {
+            Some(Ok(msg)) => {
+                ui.label(msg);
+            }
+            Some(Err(msg)) => {
+                ui.colored_label(egui::Color32::RED, msg);
+            }
+            None => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn node_segment_in_view() {
+        let (min, max) = (Vec2::new(0.0, 0.0), Vec2::new(10.0, 10.0));
+        let visible = |p0: [f32; 2], p1: [f32; 2]| {
+            segment_intersects_rect(p0.into(), p1.into(), min, max)
+        };
+
+        // one endpoint inside
+        assert!(visible([5.0, 5.0], [20.0, 5.0]));
+        // crossing the view, with both endpoints outside
+        assert!(visible([-5.0, 5.0], [15.0, 5.0]));
+        assert!(visible([-5.0, -5.0], [15.0, 15.0]));
+        // passing by a corner
+        assert!(!visible([-5.0, 8.0], [8.0, 21.0]));
+        assert!(!visible([-5.0, 12.0], [15.0, 12.0]));
+    }
+
+    #[test]
+    fn replaced_sets_are_unpinned() {
+        use crate::annotations::{Annotation, AnnotationId, AnnotationSet};
+
+        let graph = crate::test_util::test_graph();
+        let path = *graph.path_names.left_values().next().unwrap();
+
+        let set = || {
+            let label = Arc::new("a".to_string());
+            let annot = Annotation::new(path, Bp(10)..Bp(20), label, None);
+            let annotations = vec![annot];
+            AnnotationSet::new(&graph, "a".into(), annotations, HashMap::new())
+        };
+
+        let mut store = AnnotationStore::default();
+        let a = store.insert_set(set());
+        let b = store.insert_set(set());
+
+        let ctx = egui::Context::default();
+        let annot_id = AnnotationId(0);
+        for set_id in [a, b] {
+            pin_annotation(&ctx, GlobalAnnotationId { set_id, annot_id });
+        }
+
+        unpin_replaced_annotations(&ctx, &store);
+        assert_eq!(pinned_annotations(&ctx).len(), 2);
+
+        store.replace_set(a, set());
+        unpin_replaced_annotations(&ctx, &store);
+        assert_eq!(
+            pinned_annotations(&ctx),
+            [GlobalAnnotationId {
+                set_id: b,
+                annot_id
+            }]
+        );
+    }
+}
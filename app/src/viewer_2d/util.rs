@@ -1,5 +1,6 @@
 use waragraph_core::graph::{Node, PathId, PathIndex};
 
+use crate::annotations::AnnotationStore;
 use crate::context::ContextState;
 
 pub(super) fn path_list_labels(
@@ -55,3 +56,57 @@ pub(super) fn node_context_side_panel_info(
         });
     }
 }
+
+/// Lists the annotations covering the hovered node, or the closest
+/// annotation in pangenome space if there are none
+pub(super) fn node_annotations_side_panel_info(
+    graph: &PathIndex,
+    annotations: &AnnotationStore,
+    context_state: &ContextState,
+    ui: &mut egui::Ui,
+) {
+    let Some(node) = context_state
+        .query_get_cast::<_, Node>(Some("Viewer2D"), ["hover"])
+        .copied()
+    else {
+        return;
+    };
+
+    let node_range = graph.node_pangenome_range(node);
+
+    let mut labels = std::collections::BTreeSet::new();
+
+    for set in annotations.annotation_sets.values() {
+        for (_range, annot_id) in
+            set.index.pangenome_overlapping(node_range.clone())
+        {
+            if let Some(annot) = set.get(annot_id) {
+                labels.insert(annot.label.clone());
+            }
+        }
+    }
+
+    if !labels.is_empty() {
+        ui.label("Annotations on node");
+        ui.vertical(|ui| {
+            for label in labels {
+                ui.label(label.as_str());
+            }
+        });
+        return;
+    }
+
+    let nearest = annotations
+        .annotation_sets
+        .values()
+        .filter_map(|set| {
+            let (annot_id, dist) =
+                set.index.pangenome_nearest(node_range.start)?;
+            Some((dist, set.get(annot_id)?.label.clone()))
+        })
+        .min();
+
+    if let Some((dist, label)) = nearest {
+        ui.label(format!("Nearest annotation: {label} ({dist} bp away)"));
+    }
+}